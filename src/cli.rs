@@ -19,8 +19,11 @@ pub fn build_cli() -> Command {
                     Arg::new("inventory")
                         .short('i')
                         .long("inventory")
-                        .help("Specify inventory file path (default: 'inventory')")
-                        .value_name("INVENTORY"),
+                        .help(
+                            "Inventory file, directory or host1,host2, list (repeatable, default: 'inventory')",
+                        )
+                        .value_name("INVENTORY")
+                        .action(ArgAction::Append),
                 )
                 .arg(
                     Arg::new("verbose")
@@ -28,6 +31,16 @@ pub fn build_cli() -> Command {
                         .action(ArgAction::Count)
                         .help("Increase verbosity (up to -vvvv)"),
                 )
+                .arg(
+                    Arg::new("extra-vars")
+                        .short('e')
+                        .long("extra-vars")
+                        .help(
+                            "Set variables as key=value, a YAML/JSON mapping or @file (repeatable)",
+                        )
+                        .value_name("VARS")
+                        .action(ArgAction::Append),
+                )
                 .arg(
                     Arg::new("limit")
                         .short('l')
@@ -35,11 +48,68 @@ pub fn build_cli() -> Command {
                         .help("Limit to specified hosts or groups")
                         .value_name("SUBSET"),
                 )
+                .arg(
+                    Arg::new("forks")
+                        .short('f')
+                        .long("forks")
+                        .help("Number of hosts to run each task on in parallel (default: 5)")
+                        .value_name("FORKS")
+                        .value_parser(clap::value_parser!(u64).range(1..)),
+                )
                 .arg(
                     Arg::new("check")
                         .long("check")
                         .help("Perform a dry run without making changes")
                         .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("tags")
+                        .short('t')
+                        .long("tags")
+                        .help("Only run tasks tagged with these values (comma separated)")
+                        .value_name("TAGS")
+                        .value_delimiter(',')
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("skip-tags")
+                        .long("skip-tags")
+                        .help("Skip tasks tagged with these values (comma separated)")
+                        .value_name("TAGS")
+                        .value_delimiter(',')
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("list-tags")
+                        .long("list-tags")
+                        .help("List all available tags instead of running the playbook")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("list-hosts")
+                        .long("list-hosts")
+                        .help(
+                            "List the hosts each play would run on instead of running the playbook",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("list-tasks")
+                        .long("list-tasks")
+                        .help("List the tasks each play would run instead of running the playbook")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("debug-vars")
+                        .long("debug-vars")
+                        .help("Show the variables of a host in each play and where they come from")
+                        .value_name("HOST"),
+                )
+                .arg(
+                    Arg::new("syntax-check")
+                        .long("syntax-check")
+                        .help("Check the playbook syntax instead of running it")
+                        .action(ArgAction::SetTrue),
                 ),
         )
         .subcommand(
@@ -67,12 +137,25 @@ pub fn build_cli() -> Command {
                         .required(true)
                         .value_name("ARGS"),
                 )
+                .arg(
+                    Arg::new("extra-vars")
+                        .short('e')
+                        .long("extra-vars")
+                        .help(
+                            "Set variables as key=value, a YAML/JSON mapping or @file (repeatable)",
+                        )
+                        .value_name("VARS")
+                        .action(ArgAction::Append),
+                )
                 .arg(
                     Arg::new("inventory")
                         .short('i')
                         .long("inventory")
-                        .help("Specify inventory file path (default: 'inventory')")
-                        .value_name("INVENTORY"),
+                        .help(
+                            "Inventory file, directory or host1,host2, list (repeatable, default: 'inventory')",
+                        )
+                        .value_name("INVENTORY")
+                        .action(ArgAction::Append),
                 )
                 .arg(
                     Arg::new("verbose")
@@ -88,8 +171,11 @@ pub fn build_cli() -> Command {
                     Arg::new("inventory")
                         .short('i')
                         .long("inventory")
-                        .help("Specify inventory file path (default: 'inventory')")
-                        .value_name("INVENTORY"),
+                        .help(
+                            "Inventory file, directory or host1,host2, list (repeatable, default: 'inventory')",
+                        )
+                        .value_name("INVENTORY")
+                        .action(ArgAction::Append),
                 )
                 .arg(
                     Arg::new("verbose")
@@ -112,4 +198,29 @@ mod tests {
         assert!(subcommands.contains(&"ad-hoc"));
         assert!(subcommands.contains(&"inventory-debug"));
     }
+
+    #[test]
+    fn test_playbook_tag_options() {
+        let matches = build_cli()
+            .try_get_matches_from([
+                "rustsible",
+                "playbook",
+                "site.yml",
+                "--tags",
+                "config,web",
+                "-t",
+                "db",
+                "--skip-tags",
+                "slow",
+            ])
+            .unwrap();
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        let tags: Vec<&String> = sub_matches.get_many::<String>("tags").unwrap().collect();
+        assert_eq!(tags, ["config", "web", "db"]);
+        let skip_tags: Vec<&String> = sub_matches
+            .get_many::<String>("skip-tags")
+            .unwrap()
+            .collect();
+        assert_eq!(skip_tags, ["slow"]);
+    }
 }
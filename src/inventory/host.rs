@@ -1,4 +1,5 @@
 use log::debug;
+use serde_yaml::{Mapping, Value};
 use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
@@ -7,8 +8,14 @@ pub struct Host {
     pub name: String,
     pub hostname: String,
     pub port: u16,
-    pub variables: HashMap<String, String>,
-    pub inherited_variables: HashMap<String, String>,
+    pub variables: HashMap<String, Value>,
+    pub inherited_variables: HashMap<String, Value>,
+    /// Variables of each group the host belongs to, least specific group first
+    pub group_variables: Vec<(String, HashMap<String, Value>)>,
+    /// Variables from the `group_vars` files of the host's groups, in the same order
+    pub group_file_variables: Vec<(String, Mapping)>,
+    /// Variables from the host's `host_vars` files
+    pub file_variables: Mapping,
 }
 
 impl Host {
@@ -29,6 +36,9 @@ impl Host {
             port: 22,
             variables: HashMap::new(),
             inherited_variables: HashMap::new(),
+            group_variables: Vec::new(),
+            group_file_variables: Vec::new(),
+            file_variables: Mapping::new(),
         }
     }
 
@@ -37,36 +47,37 @@ impl Host {
         self
     }
 
-    pub fn add_inherited_variable(&mut self, key: &str, value: &str) -> bool {
+    pub fn add_inherited_variable(&mut self, key: &str, value: impl Into<Value>) -> bool {
         if !self.variables.contains_key(key) {
             self.inherited_variables
-                .insert(key.to_string(), value.to_string());
+                .insert(key.to_string(), value.into());
             true
         } else {
             false
         }
     }
 
-    pub fn get_variable(&self, key: &str) -> Option<&String> {
+    pub fn get_variable(&self, key: &str) -> Option<&Value> {
         self.variables
             .get(key)
             .or_else(|| self.inherited_variables.get(key))
     }
 
-    pub fn set_variable(&mut self, key: &str, value: &str) {
+    pub fn set_variable(&mut self, key: &str, value: impl Into<Value>) {
+        let value = value.into();
         debug!(
-            "Setting variable for host {}: {} = {}",
+            "Setting variable for host {}: {} = {:?}",
             self.name, key, value
         );
 
         // 处理特殊的Ansible变量
-        match key {
-            "ansible_host" | "ansible_ssh_host" => {
-                self.hostname = value.to_string();
-                debug!("Set hostname to {} from {}", value, key);
+        match (key, scalar_string(&value)) {
+            ("ansible_host" | "ansible_ssh_host", Some(hostname)) => {
+                debug!("Set hostname to {} from {}", hostname, key);
+                self.hostname = hostname;
             }
-            "ansible_port" | "ansible_ssh_port" => {
-                if let Ok(port_num) = value.parse::<u16>() {
+            ("ansible_port" | "ansible_ssh_port", Some(port)) => {
+                if let Ok(port_num) = port.parse::<u16>() {
                     self.port = port_num;
                     debug!("Set port to {} from {}", port_num, key);
                 }
@@ -74,25 +85,40 @@ impl Host {
             _ => {}
         }
 
-        self.variables.insert(key.to_string(), value.to_string());
+        self.variables.insert(key.to_string(), value);
     }
 
-    pub fn get_ssh_user(&self) -> Option<&String> {
-        self.get_variable("ansible_user")
-            .or_else(|| self.get_variable("ansible_ssh_user"))
+    /// A variable used as a connection setting, where `ansible_password=1234` is still a string
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.get_variable(key).and_then(scalar_string)
     }
 
-    pub fn get_ssh_password(&self) -> Option<&String> {
-        self.get_variable("ansible_password")
-            .or_else(|| self.get_variable("ansible_ssh_pass"))
+    pub fn get_ssh_user(&self) -> Option<String> {
+        self.get_string("ansible_user")
+            .or_else(|| self.get_string("ansible_ssh_user"))
     }
-    pub fn get_ssh_sudo_password(&self) -> Option<&String> {
-        self.get_variable("ansible_sudo_pass")
-            .or_else(|| self.get_variable("ansible_ssh_sudo_pass"))
+
+    pub fn get_ssh_password(&self) -> Option<String> {
+        self.get_string("ansible_password")
+            .or_else(|| self.get_string("ansible_ssh_pass"))
+    }
+    pub fn get_ssh_sudo_password(&self) -> Option<String> {
+        self.get_string("ansible_sudo_pass")
+            .or_else(|| self.get_string("ansible_ssh_sudo_pass"))
+    }
+
+    pub fn get_ssh_private_key(&self) -> Option<String> {
+        self.get_string("ansible_ssh_private_key_file")
     }
+}
 
-    pub fn get_ssh_private_key(&self) -> Option<&String> {
-        self.get_variable("ansible_ssh_private_key_file")
+/// A string, number or boolean variable as a string, `None` for lists, mappings and null
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
     }
 }
 
@@ -114,9 +140,11 @@ impl Hash for Host {
 pub struct HostGroup {
     pub name: String,
     pub hosts: HashSet<String>,
-    pub variables: HashMap<String, String>,
+    pub variables: HashMap<String, Value>,
     pub parent: Option<String>,
     pub children: HashSet<String>,
+    /// Variables from the group's `group_vars` files
+    pub file_variables: Mapping,
 }
 
 impl HostGroup {
@@ -128,6 +156,7 @@ impl HostGroup {
             variables: HashMap::new(),
             parent: None,
             children: HashSet::new(),
+            file_variables: Mapping::new(),
         }
     }
 
@@ -144,12 +173,12 @@ impl HostGroup {
         self.hosts.insert(host.to_string())
     }
 
-    pub fn add_variable(&mut self, key: &str, value: &str) {
-        self.variables.insert(key.to_string(), value.to_string());
+    pub fn add_variable(&mut self, key: &str, value: impl Into<Value>) {
+        self.variables.insert(key.to_string(), value.into());
     }
 
-    pub fn set_variable(&mut self, key: &str, value: &str) {
-        self.variables.insert(key.to_string(), value.to_string());
+    pub fn set_variable(&mut self, key: &str, value: impl Into<Value>) {
+        self.variables.insert(key.to_string(), value.into());
     }
 }
 
@@ -177,7 +206,7 @@ mod tests {
         let mut host = Host::new("h1");
         // Test setting normal variable
         host.set_variable("foo", "bar");
-        assert_eq!(host.get_variable("foo"), Some(&"bar".to_string()));
+        assert_eq!(host.get_variable("foo"), Some(&Value::from("bar")));
         // Inherited variable should not override explicit
         host.set_variable("baz", "qux");
         assert!(!host.add_inherited_variable("baz", "new"));
@@ -189,6 +218,8 @@ mod tests {
         // Test ansible_port changes port
         host.set_variable("ansible_port", "2023");
         assert_eq!(host.port, 2023);
+        host.set_variable("ansible_port", 2024);
+        assert_eq!(host.port, 2024);
     }
 
     #[test]
@@ -198,8 +229,12 @@ mod tests {
         host.set_variable("ansible_password", "pass1");
         host.set_variable("ansible_ssh_private_key_file", "/key");
 
-        assert_eq!(host.get_ssh_user(), Some(&"user1".to_string()));
-        assert_eq!(host.get_ssh_password(), Some(&"pass1".to_string()));
-        assert_eq!(host.get_ssh_private_key(), Some(&"/key".to_string()));
+        assert_eq!(host.get_ssh_user(), Some("user1".to_string()));
+        assert_eq!(host.get_ssh_password(), Some("pass1".to_string()));
+        assert_eq!(host.get_ssh_private_key(), Some("/key".to_string()));
+
+        // Numeric passwords from the inventory are still strings to SSH
+        host.set_variable("ansible_password", 1234);
+        assert_eq!(host.get_ssh_password(), Some("1234".to_string()));
     }
 }
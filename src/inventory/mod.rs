@@ -1,14 +1,25 @@
 pub mod host;
 mod parser;
+mod pattern;
+mod script;
+mod vars_dir;
+mod yaml;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 pub use host::{Host, HostGroup};
 use log::{debug, info, warn};
+use pattern::Operator;
+use serde_yaml::{Mapping, Value};
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
+#[derive(Clone)]
 pub struct Inventory {
     pub hosts: HashMap<String, Host>,
     pub groups: HashMap<String, HostGroup>,
+    /// The position of each host in the inventory, the order patterns return hosts in
+    host_order: HashMap<String, usize>,
+    matchers: pattern::Matchers,
 }
 
 impl Inventory {
@@ -20,11 +31,15 @@ impl Inventory {
         Inventory {
             hosts: HashMap::new(),
             groups,
+            host_order: HashMap::new(),
+            matchers: pattern::Matchers::default(),
         }
     }
 
     pub fn add_host(&mut self, host: Host) {
         let name = host.name.clone();
+        let position = self.host_order.len();
+        self.host_order.entry(name.clone()).or_insert(position);
         self.hosts.insert(name, host);
     }
 
@@ -37,70 +52,24 @@ impl Inventory {
         self.groups.get(name)
     }
 
+    /// The hosts matching an Ansible host pattern, in inventory order.
+    ///
+    /// A pattern is a list of group or host names separated by `:` or `,`, where names may
+    /// be `all`/`*`, globs like `web*.example.com` or regexes like `~web\d+`, optionally
+    /// followed by a subscript like `[0]` or `[1:3]`. `&name` keeps only the hosts also in
+    /// `name` and `!name` leaves out the hosts in `name`, as in `webservers:&prod:!db`.
     pub fn filter_hosts(&self, pattern: &str) -> Vec<Host> {
-        // Simple pattern matching for now (exact group or host names)
-        // In a full implementation, we would handle complex patterns with wildcards
-
-        let mut result = HashSet::new();
-
         info!("Filtering hosts with pattern: {}", pattern);
         debug!(
             "Available groups: {:?}",
             self.groups.keys().collect::<Vec<_>>()
         );
 
-        // 如果匹配一个组，添加该组的所有主机（包括子组的主机）
-        if let Some(group) = self.groups.get(pattern) {
-            info!("Found group '{}' with {} hosts", pattern, group.hosts.len());
-
-            // 获取直接主机成员
-            for host_name in &group.hosts {
-                if let Some(_host) = self.hosts.get(host_name) {
-                    debug!("Adding host '{}' from group '{}'", host_name, pattern);
-                    result.insert(host_name.clone());
-                } else {
-                    warn!(
-                        "Host '{}' referenced in group '{}' not found in inventory",
-                        host_name, pattern
-                    );
-                }
-            }
-
-            // 获取所有子组的主机
-            for child_name in &group.children {
-                self.add_group_hosts_recursive(child_name, &mut result);
-            }
-        } else {
-            debug!("No group found with name '{}'", pattern);
-
-            // 如果匹配一个主机，直接添加
-            if let Some(_host) = self.hosts.get(pattern) {
-                debug!("Adding host '{}' by direct name match", pattern);
-                result.insert(pattern.to_string());
-            } else {
-                warn!("No host or group found matching '{}'", pattern);
-            }
-        }
-
-        // 在返回主机列表前应用变量继承
-        // 创建可变副本以应用变量
-        let mut inventory_clone = Inventory::new();
-        for (name, host) in &self.hosts {
-            if result.contains(name) {
-                inventory_clone.hosts.insert(name.clone(), host.clone());
-            }
-        }
-        for (name, group) in &self.groups {
-            inventory_clone.groups.insert(name.clone(), group.clone());
-        }
-
-        // 应用组变量继承
-        inventory_clone.apply_group_vars();
-
-        // 转换为向量，但使用处理过变量的主机，返回克隆而非引用
-        let hosts: Vec<Host> = result
+        // The hosts already carry their group variables, applied when the inventory was loaded
+        let hosts: Vec<Host> = self
+            .match_terms(&pattern::parse(pattern))
             .iter()
-            .filter_map(|name| inventory_clone.hosts.get(name).cloned())
+            .filter_map(|name| self.hosts.get(name).cloned())
             .collect();
 
         info!("Found {} hosts matching pattern '{}'", hosts.len(), pattern);
@@ -120,6 +89,117 @@ impl Inventory {
         hosts
     }
 
+    /// Resolve a `--limit` expression into the set of host names it allows.
+    ///
+    /// The expression is a host pattern as accepted by `filter_hosts`; an `@path` entry
+    /// reads more entries of the pattern from a file, one per line.
+    pub fn limit_host_names(&self, limit: &str) -> Result<HashSet<String>> {
+        let mut entries = Vec::new();
+        for part in pattern::split(limit) {
+            match part.strip_prefix('@') {
+                Some(path) => {
+                    let content = std::fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read limit file: {}", path))?;
+                    entries.extend(
+                        content
+                            .lines()
+                            .map(str::trim)
+                            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                            .map(str::to_string),
+                    );
+                }
+                None => entries.push(part.to_string()),
+            }
+        }
+
+        let allowed: HashSet<String> = self
+            .match_terms(&pattern::parse(&entries.join(",")))
+            .into_iter()
+            .collect();
+        debug!("Limit '{}' allows hosts: {:?}", limit, allowed);
+        Ok(allowed)
+    }
+
+    /// The names of the hosts selected by the terms of a pattern
+    fn match_terms(&self, terms: &[pattern::Term]) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for term in terms {
+            let mut matched = self.match_names(term.pattern);
+            if let Some(subscript) = term.subscript {
+                matched = subscript.apply(matched);
+            }
+
+            match term.operator {
+                Operator::Union => {
+                    for name in matched {
+                        if !names.contains(&name) {
+                            names.push(name);
+                        }
+                    }
+                }
+                Operator::Intersection => names.retain(|name| matched.contains(name)),
+                Operator::Exclusion => names.retain(|name| !matched.contains(name)),
+            }
+        }
+        names
+    }
+
+    /// The hosts of the groups matching one name, glob or regex, and the hosts matching it
+    /// themselves, like Ansible only looking at host names when no group matches a plain name
+    fn match_names(&self, pattern: &str) -> Vec<String> {
+        if pattern == "all" || pattern == "*" {
+            return self.in_inventory_order(self.hosts.keys().cloned());
+        }
+
+        let wildcard = pattern::is_wildcard(pattern);
+        let matcher = if wildcard {
+            match self.matchers.get(pattern) {
+                Ok(matcher) => Some(matcher),
+                Err(e) => {
+                    warn!("{:#}", e);
+                    return Vec::new();
+                }
+            }
+        } else {
+            None
+        };
+        let matches = |name: &str| match &matcher {
+            Some(matcher) => matcher.is_match(name),
+            None => name == pattern,
+        };
+
+        let mut result = HashSet::new();
+        let groups: Vec<&String> = self.groups.keys().filter(|name| matches(name)).collect();
+        for group in &groups {
+            debug!(
+                "Adding hosts of group '{}' for pattern '{}'",
+                group, pattern
+            );
+            self.add_group_hosts_recursive(group, &mut result);
+        }
+        if groups.is_empty() || wildcard {
+            result.extend(self.hosts.keys().filter(|name| matches(name)).cloned());
+        }
+
+        if groups.is_empty() && result.is_empty() {
+            warn!("No host or group found matching '{}'", pattern);
+        }
+        self.in_inventory_order(result)
+    }
+
+    /// Host names in the order the hosts were added, which subscripts index into
+    fn in_inventory_order(&self, names: impl IntoIterator<Item = String>) -> Vec<String> {
+        let mut names: Vec<String> = names.into_iter().collect();
+        names.sort_by(|a, b| {
+            let position = |name: &String| self.host_order.get(name).copied();
+            position(a)
+                .unwrap_or(usize::MAX)
+                .cmp(&position(b).unwrap_or(usize::MAX))
+                .then_with(|| a.cmp(b))
+        });
+        names
+    }
+
     /// 递归地添加一个组及其子组的所有主机
     fn add_group_hosts_recursive(&self, group_name: &str, result: &mut HashSet<String>) {
         if let Some(group) = self.groups.get(group_name) {
@@ -147,100 +227,158 @@ impl Inventory {
         }
     }
 
-    /// 将组变量应用到组内的主机
-    fn apply_group_vars(&mut self) {
-        debug!("Applying group variables to hosts");
-
-        // 首先应用 all 组的变量
-        if let Some(all_group) = self.groups.get("all") {
-            debug!("Applying variables from 'all' group to all hosts");
-            for (name, host) in &mut self.hosts {
-                for (key, value) in &all_group.variables {
-                    if host.add_inherited_variable(key, value) {
-                        debug!(
-                            "Set inherited variable from 'all' for host {}: {} = {}",
-                            name, key, value
-                        );
-                    }
+    /// The groups a host belongs to, directly or through child groups, least specific first:
+    /// `all`, then every group after its parents, like Ansible orders group variables
+    pub fn host_groups(&self, host_name: &str) -> Vec<&HostGroup> {
+        let mut names: HashSet<&str> = self
+            .groups
+            .values()
+            .filter(|group| group.hosts.contains(host_name))
+            .map(|group| group.name.as_str())
+            .collect();
+        names.insert("all");
+
+        // Add the parents of every group until no new group shows up
+        let mut pending: Vec<&str> = names.iter().copied().collect();
+        while let Some(name) = pending.pop() {
+            for parent in self.parent_groups(name) {
+                if names.insert(parent) {
+                    pending.push(parent);
                 }
             }
         }
 
-        // 收集所有组和它们的变量
-        let mut group_vars: HashMap<String, HashMap<String, String>> = HashMap::new();
-        for (group_name, group) in &self.groups {
-            if !group.variables.is_empty() {
-                group_vars.insert(group_name.clone(), group.variables.clone());
+        let mut groups: Vec<(usize, &HostGroup)> = names
+            .into_iter()
+            .filter_map(|name| self.groups.get(name))
+            .map(|group| (self.group_depth(&group.name, 0), group))
+            .collect();
+        groups.sort_by(|(a_depth, a), (b_depth, b)| {
+            a_depth.cmp(b_depth).then_with(|| a.name.cmp(&b.name))
+        });
+        groups.into_iter().map(|(_, group)| group).collect()
+    }
+
+    /// The groups listing `name` as a child, besides the implicit `all`
+    fn parent_groups(&self, name: &str) -> Vec<&str> {
+        let mut parents: Vec<&str> = self
+            .groups
+            .values()
+            .filter(|group| group.children.contains(name))
+            .map(|group| group.name.as_str())
+            .collect();
+        if let Some(parent) = self
+            .groups
+            .get(name)
+            .and_then(|group| group.parent.as_deref())
+        {
+            if !parents.contains(&parent) {
+                parents.push(parent);
             }
         }
+        parents
+    }
 
-        // 为每个组应用变量
-        for (group_name, group) in &self.groups {
-            if group_vars.contains_key(group_name) && group_name != "all" {
-                debug!(
-                    "Applying variables from group '{}' to its hosts",
-                    group_name
-                );
+    /// How deep a group is nested: 0 for `all`, 1 for top-level groups
+    fn group_depth(&self, name: &str, visited: usize) -> usize {
+        if name == "all" {
+            return 0;
+        }
+        // Cycles in `:children` sections would otherwise never end
+        if visited > self.groups.len() {
+            return visited;
+        }
+        self.parent_groups(name)
+            .into_iter()
+            .filter(|parent| *parent != "all")
+            .map(|parent| self.group_depth(parent, visited + 1))
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
 
-                // 获取组内所有主机
-                let host_names: Vec<String> = group.hosts.iter().cloned().collect();
-
-                // 应用到组内的主机
-                for host_name in host_names {
-                    if let Some(host) = self.hosts.get_mut(&host_name) {
-                        let vars = &group_vars[group_name];
-                        for (key, value) in vars {
-                            // 只有在主机没有定义该变量时才应用组变量
-                            if host.add_inherited_variable(key, value) {
-                                debug!(
-                                    "Set inherited variable for host {}: {} = {}",
-                                    host_name, key, value
-                                );
+    /// Load the `group_vars` and `host_vars` directories found in `dir`, if any. Their
+    /// variables override the ones of files loaded before, such as the ones next to the
+    /// inventory when `dir` holds the playbook. Returns whether anything was loaded.
+    pub fn load_vars_dirs(&mut self, dir: &Path) -> Result<bool> {
+        let group_vars_dir = dir.join("group_vars");
+        let host_vars_dir = dir.join("host_vars");
+        if !group_vars_dir.is_dir() && !host_vars_dir.is_dir() {
+            return Ok(false);
+        }
+
+        if group_vars_dir.is_dir() {
+            let groups = vars_dir::load(&group_vars_dir, |name| self.groups.contains_key(name))?;
+            for (name, vars) in groups {
+                if let Some(group) = self.groups.get_mut(&name) {
+                    group.file_variables.extend(vars);
+                }
+            }
+        }
+        if host_vars_dir.is_dir() {
+            let hosts = vars_dir::load(&host_vars_dir, |name| self.hosts.contains_key(name))?;
+            for (name, vars) in hosts {
+                if let Some(host) = self.hosts.get_mut(&name) {
+                    // Connection settings also change how the host is reached
+                    for (key, value) in &vars {
+                        if let Value::String(key) = key {
+                            if key.starts_with("ansible_") {
+                                host.set_variable(key, value.clone());
                             }
                         }
                     }
+                    host.file_variables.extend(vars);
                 }
             }
         }
 
-        // 处理子组继承
-        self.apply_parent_group_vars();
+        self.apply_group_vars();
+        Ok(true)
     }
 
-    /// 处理子组从父组继承变量
-    fn apply_parent_group_vars(&mut self) {
-        debug!("Applying parent group variables to child groups");
-
-        // 创建组关系的副本以避免借用冲突
-        let mut parent_child_map: HashMap<String, Vec<String>> = HashMap::new();
-
-        for (group_name, group) in &self.groups {
-            if let Some(parent) = &group.parent {
-                parent_child_map
-                    .entry(parent.clone())
-                    .or_insert_with(Vec::new)
-                    .push(group_name.clone());
-            }
-        }
+    /// Give every host the variables of its groups, in group order so a child group's
+    /// value wins over its parents' and every group's over `all`
+    fn apply_group_vars(&mut self) {
+        debug!("Applying group variables to hosts");
 
-        // 递归应用父组变量到子组
-        for (parent_name, children) in &parent_child_map {
-            if let Some(parent) = self.groups.get(parent_name) {
-                let parent_vars = parent.variables.clone();
-
-                for child_name in children {
-                    if let Some(child) = self.groups.get_mut(child_name) {
-                        for (key, value) in &parent_vars {
-                            if !child.variables.contains_key(key) {
-                                debug!(
-                                    "Setting inherited variable for group {}: {} = {}",
-                                    child_name, key, value
-                                );
-                                child.add_variable(key, value);
+        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
+        for host_name in host_names {
+            let groups = self.host_groups(&host_name);
+            let group_vars: Vec<(String, HashMap<String, Value>)> = groups
+                .iter()
+                .filter(|group| !group.variables.is_empty())
+                .map(|group| (group.name.clone(), group.variables.clone()))
+                .collect();
+            let group_file_vars: Vec<(String, Mapping)> = groups
+                .iter()
+                .filter(|group| !group.file_variables.is_empty())
+                .map(|group| (group.name.clone(), group.file_variables.clone()))
+                .collect();
+
+            if let Some(host) = self.hosts.get_mut(&host_name) {
+                host.inherited_variables.clear();
+                for (group_name, vars) in &group_vars {
+                    for (key, value) in vars {
+                        if host.add_inherited_variable(key, value.clone()) {
+                            debug!(
+                                "Set inherited variable from '{}' for host {}: {} = {:?}",
+                                group_name, host_name, key, value
+                            );
+                        }
+                    }
+                }
+                // Connection settings from group_vars files are visible to the SSH client
+                for (_, vars) in &group_file_vars {
+                    for (key, value) in vars {
+                        if let Value::String(key) = key {
+                            if key.starts_with("ansible_") {
+                                host.add_inherited_variable(key, value.clone());
                             }
                         }
                     }
                 }
+                host.group_variables = group_vars;
+                host.group_file_variables = group_file_vars;
             }
         }
     }
@@ -249,3 +387,178 @@ impl Inventory {
 pub fn parse(inventory_path: &str) -> Result<Inventory> {
     parser::parse_inventory(inventory_path)
 }
+
+/// Parse several inventory sources (files, directories or `host1,host2,` lists) into one inventory
+pub fn parse_all(sources: &[String]) -> Result<Inventory> {
+    parser::parse_inventories(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn create_limit_inventory() -> Inventory {
+        let mut inventory = Inventory::new();
+        let mut web = HostGroup::new("web");
+        for name in ["web1", "web2"] {
+            inventory.add_host(Host::new(name));
+            web.add_host(name);
+        }
+        inventory.add_host(Host::new("db1"));
+        inventory.add_group(web);
+        inventory
+    }
+
+    #[test]
+    fn test_limit_host_names_patterns() {
+        let inventory = create_limit_inventory();
+
+        let allowed = inventory.limit_host_names("web").unwrap();
+        assert_eq!(allowed.len(), 2);
+        assert!(allowed.contains("web1") && allowed.contains("web2"));
+
+        let allowed = inventory.limit_host_names("web1,db1").unwrap();
+        assert_eq!(allowed.len(), 2);
+        assert!(allowed.contains("db1"));
+
+        assert!(inventory.limit_host_names("missing").unwrap().is_empty());
+
+        // An IPv6 host is one term, not colon separated ones
+        let mut inventory = inventory;
+        inventory.add_host(Host::new("fe80::2"));
+        let allowed = inventory.limit_host_names("fe80::2").unwrap();
+        assert_eq!(allowed, HashSet::from(["fe80::2".to_string()]));
+    }
+
+    #[test]
+    fn test_filter_hosts_patterns() {
+        let mut inventory = create_limit_inventory();
+        inventory.add_host(Host::new("web10.example.com"));
+        let mut prod = HostGroup::new("prod");
+        for name in ["web2", "db1", "web10.example.com"] {
+            prod.add_host(name);
+        }
+        inventory.add_group(prod);
+
+        let names = |pattern: &str| -> Vec<String> {
+            inventory
+                .filter_hosts(pattern)
+                .into_iter()
+                .map(|host| host.name)
+                .collect()
+        };
+        assert_eq!(names("all"), ["web1", "web2", "db1", "web10.example.com"]);
+        assert_eq!(names("*"), names("all"));
+        assert_eq!(names("web*"), ["web1", "web2", "web10.example.com"]);
+        assert_eq!(names("*.example.com"), ["web10.example.com"]);
+        assert_eq!(names("~web\\d$"), ["web1", "web2"]);
+        assert_eq!(names("db1:web"), ["db1", "web1", "web2"]);
+        assert_eq!(names("db1,web"), names("db1:web"));
+        assert_eq!(names("web:&prod"), ["web2"]);
+        assert_eq!(names("all:!prod"), ["web1"]);
+        assert_eq!(names("!web"), ["db1", "web10.example.com"]);
+        assert_eq!(names("prod[0]"), ["web2"]);
+        assert_eq!(names("prod[-1]"), ["web10.example.com"]);
+        assert_eq!(names("all[1:2]"), ["web2", "db1"]);
+        assert_eq!(names("all[2:]:!db1"), ["web10.example.com"]);
+        assert!(names("missing").is_empty());
+        assert!(names("~web(").is_empty());
+
+        let allowed = inventory.limit_host_names("all:!prod").unwrap();
+        assert_eq!(allowed, HashSet::from(["web1".to_string()]));
+    }
+
+    #[test]
+    fn test_nested_group_vars_order() {
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("web1"));
+        inventory.add_host(Host::new("db1"));
+
+        let mut prod = HostGroup::new("prod");
+        prod.add_child("web");
+        prod.set_variable("tier", "prod");
+        prod.set_variable("level", "prod");
+        let mut web = HostGroup::new("web").with_parent("prod");
+        web.add_host("web1");
+        web.set_variable("level", "web");
+        let mut aaa = HostGroup::new("aaa");
+        aaa.add_host("web1");
+        aaa.set_variable("level", "aaa");
+        inventory.add_group(prod);
+        inventory.add_group(web);
+        inventory.add_group(aaa);
+        if let Some(all) = inventory.groups.get_mut("all") {
+            all.set_variable("tier", "all");
+            all.set_variable("level", "all");
+        }
+
+        let groups: Vec<&str> = inventory
+            .host_groups("web1")
+            .iter()
+            .map(|group| group.name.as_str())
+            .collect();
+        assert_eq!(groups, ["all", "aaa", "prod", "web"]);
+
+        inventory.apply_group_vars();
+        let web1 = &inventory.hosts["web1"];
+        assert_eq!(web1.get_variable("level").unwrap(), "web");
+        assert_eq!(web1.get_variable("tier").unwrap(), "prod");
+        assert_eq!(web1.group_variables.len(), 4);
+        assert_eq!(inventory.hosts["db1"].get_variable("level").unwrap(), "all");
+        // Parent group vars are no longer copied into the child groups
+        assert!(!inventory.groups["web"].variables.contains_key("tier"));
+    }
+
+    #[test]
+    fn test_load_vars_dirs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for (file, content) in [
+            ("group_vars/all.yml", "level: all\nntp: pool\n"),
+            ("group_vars/web.yml", "level: web\nports: [80, 443]\n"),
+            ("group_vars/unknown.yml", "level: unknown\n"),
+            ("group_vars/README.md", "Web and db server variables.\n"),
+            (
+                "host_vars/web1.yml",
+                "ansible_host: 10.0.0.5\nansible_port: 2222\n",
+            ),
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let mut inventory = create_limit_inventory();
+        assert!(!inventory
+            .load_vars_dirs(&dir.path().join("missing"))
+            .unwrap());
+        assert!(inventory.load_vars_dirs(dir.path()).unwrap());
+
+        let web1 = &inventory.hosts["web1"];
+        assert_eq!((web1.hostname.as_str(), web1.port), ("10.0.0.5", 2222));
+        let groups: Vec<&str> = web1
+            .group_file_variables
+            .iter()
+            .map(|(group, _)| group.as_str())
+            .collect();
+        assert_eq!(groups, ["all", "web"]);
+        let ports = web1.group_file_variables[1].1.get("ports");
+        assert!(matches!(ports, Some(Value::Sequence(ports)) if ports.len() == 2));
+        assert_eq!(inventory.hosts["db1"].group_file_variables.len(), 1);
+    }
+
+    #[test]
+    fn test_limit_host_names_from_file() {
+        let inventory = create_limit_inventory();
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "# canary hosts\nweb2\n\ndb1").unwrap();
+
+        let limit = format!("@{}", file.path().display());
+        let allowed = inventory.limit_host_names(&limit).unwrap();
+        assert_eq!(allowed.len(), 2);
+        assert!(allowed.contains("web2") && allowed.contains("db1"));
+
+        assert!(inventory.limit_host_names("@/nonexistent/limit").is_err());
+    }
+}
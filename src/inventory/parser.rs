@@ -1,26 +1,199 @@
 use anyhow::{Context, Result};
 use log::{debug, info};
 use regex::Regex;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
+use serde_yaml::Value;
+use std::collections::HashSet;
+use std::net::Ipv6Addr;
+use std::path::{Path, PathBuf};
 
-use super::{Host, HostGroup, Inventory};
+use super::{script, yaml, Host, HostGroup, Inventory};
 
 pub fn parse_inventory(inventory_path: &str) -> Result<Inventory> {
-    let path = Path::new(inventory_path);
+    parse_inventories(&[inventory_path.to_string()])
+}
+
+/// Extensions of the files an inventory directory leaves out, besides dotfiles
+const IGNORED_EXTENSIONS: &[&str] = &[
+    ".pyc", ".pyo", ".swp", ".bak", "~", ".rpm", ".md", ".txt", ".rst", ".orig", ".cfg", ".retry",
+];
+
+/// Parse every inventory source into one inventory, where a source is a file, a directory
+/// of inventory files or a comma separated list of hosts such as `web1,web2` or `web1,`.
+/// Later sources add hosts to the groups of earlier ones and override their variables.
+pub fn parse_inventories(sources: &[String]) -> Result<Inventory> {
+    let mut inventory = Inventory::new();
+    let mut vars_dirs: Vec<PathBuf> = Vec::new();
+    for source in sources {
+        add_source(&mut inventory, source, &mut vars_dirs)?;
+    }
+
+    // Every host is in `all`, and in `ungrouped` only when it's in no other group
+    let all_hosts: Vec<String> = inventory.hosts.keys().cloned().collect();
+    if let Some(all_group) = inventory.groups.get_mut("all") {
+        for host_name in &all_hosts {
+            all_group.add_host(host_name);
+        }
+    }
+    let grouped: HashSet<String> = inventory
+        .groups
+        .values()
+        .filter(|group| group.name != "all" && group.name != "ungrouped")
+        .flat_map(|group| group.hosts.iter().cloned())
+        .collect();
+    if let Some(ungrouped) = inventory.groups.get_mut("ungrouped") {
+        ungrouped.hosts = all_hosts
+            .into_iter()
+            .filter(|host_name| !grouped.contains(host_name))
+            .collect();
+    }
+
+    // Log inventory summary
+    info!(
+        "Inventory parsed: {} hosts, {} groups",
+        inventory.hosts.len(),
+        inventory.groups.len()
+    );
+    for (name, group) in &inventory.groups {
+        info!("Group '{}': {} hosts", name, group.hosts.len());
+    }
+
+    // Apply group variables so hosts inherit group-level vars
+    inventory.apply_group_vars();
+
+    // group_vars/ and host_vars/ next to each inventory source
+    for dir in vars_dirs {
+        inventory.load_vars_dirs(&dir)?;
+    }
+    Ok(inventory)
+}
+
+/// Add the hosts and groups of one inventory source, remembering the directories to look
+/// for `group_vars` and `host_vars` in
+fn add_source(inventory: &mut Inventory, source: &str, vars_dirs: &mut Vec<PathBuf>) -> Result<()> {
+    let path = Path::new(source);
 
     if !path.exists() {
-        return Err(anyhow::anyhow!(
-            "Inventory file not found: {}",
-            inventory_path
-        ));
+        if source.contains(',') {
+            info!("Parsing host list: {}", source);
+            return add_host_list(inventory, source);
+        }
+        return Err(anyhow::anyhow!("Inventory file not found: {}", source));
+    }
+
+    let dir = if path.is_dir() {
+        path
+    } else {
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        }
+    };
+    if !vars_dirs.iter().any(|existing| existing == dir) {
+        vars_dirs.push(dir.to_path_buf());
     }
 
-    let file = File::open(path).context("Failed to open inventory file")?;
-    let reader = io::BufReader::new(file);
+    if path.is_dir() {
+        info!("Parsing inventory directory: {}", source);
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(path)
+            .with_context(|| format!("Failed to read inventory directory: {}", source))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        entries.sort();
 
-    let mut inventory = Inventory::new();
+        for entry in entries {
+            let Some(name) = entry.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if name.starts_with('.')
+                || name == "group_vars"
+                || name == "host_vars"
+                || IGNORED_EXTENSIONS
+                    .iter()
+                    .any(|extension| name.ends_with(extension))
+            {
+                debug!("Skipping {} in inventory directory", entry.display());
+                continue;
+            }
+            // Subdirectories are sources too, read with the same filters
+            add_source(inventory, &entry.to_string_lossy(), vars_dirs)?;
+        }
+    } else if script::is_inventory_script(path) {
+        script::parse_script_inventory(path, inventory)?;
+    } else {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read inventory file: {}", source))?;
+        if yaml::is_yaml_inventory(path, &content) {
+            info!("Parsing YAML inventory file: {}", source);
+            yaml::parse_yaml_inventory(&content, inventory)
+                .with_context(|| format!("Failed to parse YAML inventory file: {}", source))?;
+        } else {
+            info!("Parsing inventory file: {}", source);
+            parse_ini_inventory(&content, inventory)
+                .with_context(|| format!("Failed to parse inventory file: {}", source))?;
+        }
+    }
+    Ok(())
+}
+
+/// Add the hosts of a comma separated list like `web1,web2:2222,` to `ungrouped`
+fn add_host_list(inventory: &mut Inventory, list: &str) -> Result<()> {
+    for entry in list
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (host_name, port) = split_host_port(entry);
+        let port = match port {
+            Some(port) => port
+                .parse::<u16>()
+                .with_context(|| format!("Invalid port in host list: {}", entry))?,
+            None => 22,
+        };
+        if !inventory.hosts.contains_key(host_name) {
+            debug!("Adding new host: {}", host_name);
+            inventory.add_host(Host::new(host_name).with_port(port));
+        }
+    }
+    Ok(())
+}
+
+/// Split a host entry into its name and the `:port` it ends with, if any. An IPv6 address only
+/// takes a port in the bracketed `[addr]:port` form, and colons of host ranges like
+/// `web[01:03]` are not port separators.
+pub(super) fn split_host_port(entry: &str) -> (&str, Option<&str>) {
+    if let Some((address, rest)) = entry
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+    {
+        if address.parse::<Ipv6Addr>().is_ok() {
+            if rest.is_empty() {
+                return (address, None);
+            }
+            if let Some(port) = rest.strip_prefix(':') {
+                return (address, Some(port));
+            }
+        }
+    }
+    match entry.rsplit_once(':') {
+        Some((name, port)) if !has_colon_outside_ranges(name) => (name, Some(port)),
+        _ => (entry, None),
+    }
+}
+
+fn has_colon_outside_ranges(name: &str) -> bool {
+    let mut depth = 0;
+    name.chars().any(|c| {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        c == ':' && depth == 0
+    })
+}
+
+/// Add the hosts, groups and variables of an INI inventory
+fn parse_ini_inventory(content: &str, inventory: &mut Inventory) -> Result<()> {
     let mut current_group: Option<String> = None;
 
     // Regular expressions for parsing
@@ -30,13 +203,7 @@ pub fn parse_inventory(inventory_path: &str) -> Result<Inventory> {
     let host_port_re = Regex::new(r"^(.+):(\d+)$").unwrap();
     let var_line_re = Regex::new(r"^(\w+)=(.+)$").unwrap();
 
-    info!("Parsing inventory file: {}", inventory_path);
-
-    for (line_num, line_result) in reader.lines().enumerate() {
-        let line = line_result.context(format!(
-            "Failed to read line {} from inventory file",
-            line_num + 1
-        ))?;
+    for (line_num, line) in content.lines().enumerate() {
         let line = line.trim();
 
         // Skip empty lines and comments
@@ -105,7 +272,7 @@ pub fn parse_inventory(inventory_path: &str) -> Result<Inventory> {
                     );
 
                     if let Some(group) = inventory.groups.get_mut(group_name) {
-                        group.set_variable(var_name, var_value);
+                        group.set_variable(var_name, parse_value(var_value));
                     }
 
                     continue;
@@ -159,129 +326,235 @@ pub fn parse_inventory(inventory_path: &str) -> Result<Inventory> {
             );
         }
 
-        // Add host if it doesn't exist
-        if !inventory.hosts.contains_key(host_name) {
-            debug!("Adding new host: {}", host_name);
-            let host = Host::new(host_name).with_port(port);
-            inventory.add_host(host);
-        }
-
-        // Add host to current group
-        if let Some(group_name) = &current_group {
-            if !group_name.ends_with(":vars") {
-                if let Some(group) = inventory.groups.get_mut(group_name) {
-                    debug!("Adding host {} to group {}", host_name, group_name);
-                    group.add_host(host_name);
-                }
-            }
-        } else {
-            // If no group specified, add to "ungrouped"
-            if let Some(group) = inventory.groups.get_mut("ungrouped") {
-                debug!("Adding host {} to group ungrouped", host_name);
-                group.add_host(host_name);
-            }
+        // `web[01:20].example.com` stands for one host per value of the range
+        let host_names = expand_host_range(host_name).with_context(|| {
+            format!(
+                "Invalid host range on line {} of the inventory file",
+                line_num + 1
+            )
+        })?;
+        if host_names.len() > 1 {
+            debug!("Expanded {} into {} hosts", host_name, host_names.len());
         }
 
         // Process variables on the line (if any)
+        let mut host_vars = Vec::new();
         if parts.len() > 1 {
             let vars_part = parts[1].trim();
             debug!("Processing variables for host {}: {}", host_name, vars_part);
+            for (var_name, var_value) in split_host_vars(vars_part) {
+                host_vars.push((var_name, parse_value(var_value)));
+            }
+        }
 
-            // 识别并处理以空格分隔的多个变量
-            let mut var_start = 0;
-            let mut in_quote = false;
-            let mut quote_char = ' ';
-            let mut var_name = "";
-
-            for (i, c) in vars_part.char_indices() {
-                if c == '=' && var_name.is_empty() {
-                    var_name = vars_part[var_start..i].trim();
-                    var_start = i + 1;
-                } else if (c == ' ' || i == vars_part.len() - 1)
-                    && !in_quote
-                    && !var_name.is_empty()
-                {
-                    // 如果是最后一个字符，包括它
-                    let var_value_end = if i == vars_part.len() - 1 { i + 1 } else { i };
-                    let var_value = vars_part[var_start..var_value_end].trim();
-
-                    debug!("Extracted variable: {} = {}", var_name, var_value);
-
-                    // 清除引号
-                    let var_value = if (var_value.starts_with('\'') && var_value.ends_with('\''))
-                        || (var_value.starts_with('"') && var_value.ends_with('"'))
-                    {
-                        &var_value[1..var_value.len() - 1]
-                    } else {
-                        var_value
-                    };
-
-                    if let Some(host) = inventory.hosts.get_mut(host_name) {
-                        debug!(
-                            "Setting variable for host {}: {} = {}",
-                            host_name, var_name, var_value
-                        );
-                        host.set_variable(var_name, var_value);
-                    }
+        for host_name in host_names.iter().map(String::as_str) {
+            // Add host if it doesn't exist
+            if !inventory.hosts.contains_key(host_name) {
+                debug!("Adding new host: {}", host_name);
+                let host = Host::new(host_name).with_port(port);
+                inventory.add_host(host);
+            }
 
-                    var_name = "";
-                    var_start = i + 1;
-                } else if (c == '\'' || c == '"') && (!in_quote || c == quote_char) {
-                    in_quote = !in_quote;
-                    if in_quote {
-                        quote_char = c;
+            // Add host to current group
+            if let Some(group_name) = &current_group {
+                if !group_name.ends_with(":vars") {
+                    if let Some(group) = inventory.groups.get_mut(group_name) {
+                        debug!("Adding host {} to group {}", host_name, group_name);
+                        group.add_host(host_name);
                     }
                 }
+            } else {
+                // If no group specified, add to "ungrouped"
+                if let Some(group) = inventory.groups.get_mut("ungrouped") {
+                    debug!("Adding host {} to group ungrouped", host_name);
+                    group.add_host(host_name);
+                }
             }
 
-            // 简单的空格分隔的变量处理（备用方法）
-            if var_name.is_empty() {
-                for var_entry in vars_part.split_whitespace() {
-                    if let Some((name, value)) = var_entry.split_once('=') {
-                        let var_name = name.trim();
-                        let mut var_value = value.trim();
-
-                        // 清除引号
-                        if (var_value.starts_with('\'') && var_value.ends_with('\''))
-                            || (var_value.starts_with('"') && var_value.ends_with('"'))
-                        {
-                            var_value = &var_value[1..var_value.len() - 1];
-                        }
-
-                        if let Some(host) = inventory.hosts.get_mut(host_name) {
-                            debug!(
-                                "Setting variable for host {}: {} = {}",
-                                host_name, var_name, var_value
-                            );
-                            host.set_variable(var_name, var_value);
-                        }
-                    }
+            if let Some(host) = inventory.hosts.get_mut(host_name) {
+                for (var_name, var_value) in &host_vars {
+                    debug!(
+                        "Setting variable for host {}: {} = {:?}",
+                        host_name, var_name, var_value
+                    );
+                    host.set_variable(var_name, var_value.clone());
                 }
             }
         }
     }
 
-    // Make sure all hosts are also added to the "all" group
-    let all_hosts: Vec<String> = inventory.hosts.keys().cloned().collect();
-    if let Some(all_group) = inventory.groups.get_mut("all") {
-        for host_name in all_hosts {
-            all_group.add_host(&host_name);
+    Ok(())
+}
+
+/// Expand the `[start:end]` and `[start:end:stride]` ranges of a host name, like
+/// `web[01:20].example.com` or `db-[a:f]`, into one name per value. Numeric ranges keep the
+/// zero padding of their start and an empty start counts from 0. A name without a range
+/// comes back as it is.
+pub(super) fn expand_host_range(name: &str) -> Result<Vec<String>> {
+    let Some((head, rest)) = name.split_once('[') else {
+        return Ok(vec![name.to_string()]);
+    };
+    let Some((range, tail)) = rest.split_once(']') else {
+        return Ok(vec![name.to_string()]);
+    };
+
+    let bounds: Vec<&str> = range.split(':').collect();
+    let (start, end, stride) = match bounds[..] {
+        [start, end] => (start, end, 1),
+        [start, end, stride] => (
+            start,
+            end,
+            stride
+                .parse::<usize>()
+                .ok()
+                .filter(|stride| *stride > 0)
+                .ok_or_else(|| anyhow::anyhow!("Invalid stride '{}' in {}", stride, name))?,
+        ),
+        // Not a range, such as an IPv6 address in brackets
+        _ => return Ok(vec![name.to_string()]),
+    };
+
+    let values: Vec<String> =
+        if let (Some(first), Some(last)) = (single_letter(start), single_letter(end)) {
+            if first > last {
+                return Err(anyhow::anyhow!("Range start is after its end in {}", name));
+            }
+            (first..=last)
+                .step_by(stride)
+                .map(|letter| letter.to_string())
+                .collect()
+        } else {
+            let start = if start.is_empty() { "0" } else { start };
+            let (first, last) = match (start.parse::<u64>(), end.parse::<u64>()) {
+                (Ok(first), Ok(last)) => (first, last),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Range bounds must be numbers or single letters in {}",
+                        name
+                    ))
+                }
+            };
+            if first > last {
+                return Err(anyhow::anyhow!("Range start is after its end in {}", name));
+            }
+            let width = if start.len() > 1 && start.starts_with('0') {
+                if start.len() != end.len() {
+                    return Err(anyhow::anyhow!(
+                        "Zero padded range bounds must have the same length in {}",
+                        name
+                    ));
+                }
+                start.len()
+            } else {
+                0
+            };
+            (first..=last)
+                .step_by(stride)
+                .map(|number| format!("{:0width$}", number, width = width))
+                .collect()
+        };
+
+    // The tail may hold more ranges
+    let tails = expand_host_range(tail)?;
+    let mut names = Vec::new();
+    for value in &values {
+        for tail in &tails {
+            names.push(format!("{}{}{}", head, value, tail));
         }
     }
+    Ok(names)
+}
 
-    // Log inventory summary
-    info!(
-        "Inventory parsed: {} hosts, {} groups",
-        inventory.hosts.len(),
-        inventory.groups.len()
-    );
-    for (name, group) in &inventory.groups {
-        info!("Group '{}': {} hosts", name, group.hosts.len());
+fn single_letter(bound: &str) -> Option<char> {
+    let mut chars = bound.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => Some(c),
+        _ => None,
     }
+}
 
-    // Apply group variables so hosts inherit group-level vars
-    inventory.apply_group_vars();
-    Ok(inventory)
+/// Split the `key=value` pairs following a host name on whitespace, keeping quoted values
+/// and lists or dicts containing spaces in one piece
+fn split_host_vars(input: &str) -> Vec<(&str, &str)> {
+    let mut tokens = Vec::new();
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut start = None;
+
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth = depth.saturating_sub(1),
+            (None, c) if c.is_whitespace() && depth == 0 => {
+                if let Some(token_start) = start.take() {
+                    tokens.push(&input[token_start..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(token_start) = start {
+        tokens.push(&input[token_start..]);
+    }
+
+    tokens
+        .into_iter()
+        .filter_map(|token| match token.split_once('=') {
+            Some((name, value)) if !name.is_empty() => Some((name, value)),
+            _ => {
+                debug!("Ignoring host variable without a value: {}", token);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Give an INI variable value a type like Ansible does, which reads values as Python
+/// literals: quoted values stay strings, while integers, floats, booleans and JSON style lists
+/// or dicts are converted. Anything else, such as `1.2.3` or `yes`, is a plain string.
+fn parse_value(raw: &str) -> Value {
+    let raw = raw.trim();
+    for quote in ['"', '\''] {
+        if raw.len() >= 2 && raw.starts_with(quote) && raw.ends_with(quote) {
+            return Value::String(raw[1..raw.len() - 1].to_string());
+        }
+    }
+
+    match raw {
+        "true" | "True" => return Value::Bool(true),
+        "false" | "False" => return Value::Bool(false),
+        "None" => return Value::Null,
+        _ => {}
+    }
+
+    // Like Python, numbers with leading zeros such as a file mode `0644` aren't literals
+    let digits = raw.trim_start_matches(['-', '+']);
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    let numeric = digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'));
+    if numeric && !leading_zero {
+        if let Ok(n) = raw.parse::<i64>() {
+            return Value::Number(n.into());
+        }
+        if let Ok(n) = raw.parse::<f64>() {
+            return Value::Number(n.into());
+        }
+    }
+
+    if raw.starts_with('[') || raw.starts_with('{') {
+        match serde_yaml::from_str::<Value>(raw) {
+            Ok(value @ (Value::Sequence(_) | Value::Mapping(_))) => return value,
+            _ => debug!("Keeping inventory value as a string: {}", raw),
+        }
+    }
+    Value::String(raw.to_string())
 }
 
 #[cfg(test)]
@@ -340,4 +613,178 @@ ansible_ssh_pass=testpassword
         let test2 = inventory.hosts.get("test2.example.com").unwrap();
         assert_eq!(test2.port, 2222);
     }
+
+    #[test]
+    fn test_expand_host_range() {
+        let expand = |name: &str| expand_host_range(name).unwrap();
+        assert_eq!(expand("web1.example.com"), ["web1.example.com"]);
+        assert_eq!(
+            expand("web[01:03].example.com"),
+            [
+                "web01.example.com",
+                "web02.example.com",
+                "web03.example.com"
+            ]
+        );
+        assert_eq!(expand("node[1:10:4]"), ["node1", "node5", "node9"]);
+        assert_eq!(expand("node[:2]"), ["node0", "node1", "node2"]);
+        assert_eq!(expand("db-[a:e:2]"), ["db-a", "db-c", "db-e"]);
+        assert_eq!(expand("r[1:2]n[a:b]"), ["r1na", "r1nb", "r2na", "r2nb"]);
+
+        assert!(expand_host_range("web[3:1]").is_err());
+        assert!(expand_host_range("web[01:100]").is_err());
+        assert!(expand_host_range("web[1:5:0]").is_err());
+        assert!(expand_host_range("web[a:10]").is_err());
+    }
+
+    #[test]
+    fn test_inventory_host_ranges() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"
+[nodes]
+node[01:20:2].example.com:2222 rack=a1
+
+[db]
+db-[a:c]
+"#
+        )
+        .unwrap();
+
+        let inventory = parse_inventory(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(inventory.groups["nodes"].hosts.len(), 10);
+        assert_eq!(inventory.groups["db"].hosts.len(), 3);
+
+        let node = &inventory.hosts["node19.example.com"];
+        assert_eq!(node.port, 2222);
+        assert_eq!(node.get_variable("rack").unwrap(), "a1");
+        assert!(!inventory.hosts.contains_key("node02.example.com"));
+        assert!(inventory.hosts.contains_key("db-b"));
+
+        // Expanded hosts keep the order of the range for subscripts
+        let first: Vec<String> = inventory
+            .filter_hosts("nodes[0:1]")
+            .into_iter()
+            .map(|host| host.name)
+            .collect();
+        assert_eq!(first, ["node01.example.com", "node03.example.com"]);
+    }
+
+    #[test]
+    fn test_inventory_typed_values() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"
+[web]
+web1 http_port=8080 backup_enabled=true ratio=0.5 version="1.10" mode=0644 users='["alice", "bob"]' limits={{"cpu": 2, "mem": "1G"}} ports=[80, 443] msg="hello world" release=1.2.3
+
+[web:vars]
+workers=4
+debug=False
+owner='admin'
+"#
+        )
+        .unwrap();
+
+        let inventory = parse_inventory(temp_file.path().to_str().unwrap()).unwrap();
+        let web1 = &inventory.hosts["web1"];
+        let get = |name: &str| web1.get_variable(name).unwrap();
+
+        assert_eq!(get("http_port"), &Value::Number(8080.into()));
+        assert_eq!(get("backup_enabled"), &Value::Bool(true));
+        assert_eq!(get("ratio"), &Value::Number(0.5.into()));
+        assert_eq!(get("version"), "1.10");
+        assert_eq!(get("mode"), "0644");
+        assert_eq!(get("users"), "[\"alice\", \"bob\"]");
+        assert_eq!(get("msg"), "hello world");
+        assert_eq!(get("release"), "1.2.3");
+        assert_eq!(
+            get("ports"),
+            &serde_yaml::from_str::<Value>("[80, 443]").unwrap()
+        );
+        assert_eq!(
+            get("limits"),
+            &serde_yaml::from_str::<Value>("{cpu: 2, mem: 1G}").unwrap()
+        );
+        assert_eq!(get("workers"), &Value::Number(4.into()));
+        assert_eq!(get("debug"), &Value::Bool(false));
+        assert_eq!(get("owner"), "admin");
+    }
+
+    #[test]
+    fn test_parse_inventory_directory() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for (file, content) in [
+            (
+                "01-web.ini",
+                "[web]\nweb1 http_port=80\n\n[web:vars]\ntier=frontend\nowner=ops\n",
+            ),
+            (
+                "02-more.yml",
+                "web:\n  hosts:\n    web2:\n  vars:\n    owner: web-team\ndb:\n  hosts:\n    db1:\n",
+            ),
+            ("README.md", "not an inventory\n"),
+            ("ansible.cfg", "[defaults]\n"),
+            ("group_vars/db.yml", "backup: true\n"),
+            ("prod/hosts", "[web]\nweb4\n"),
+            ("prod/notes.txt", "not an inventory\n"),
+            ("prod/.hidden/hosts", "[web]\nhidden1\n"),
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let mut extra = NamedTempFile::new().unwrap();
+        writeln!(extra, "[web]\nweb3\n[solo]\nlonely").unwrap();
+        let sources = [
+            dir.path().to_string_lossy().to_string(),
+            extra.path().to_string_lossy().to_string(),
+            "jump:2200,".to_string(),
+        ];
+        let inventory = parse_inventories(&sources).unwrap();
+
+        // Group membership and variables are merged across files
+        assert_eq!(inventory.hosts.len(), 7);
+        assert_eq!(inventory.groups["web"].hosts.len(), 4);
+        assert!(!inventory.groups.contains_key("defaults"));
+        // Subdirectories are read as sources too, with the same filters
+        assert!(inventory.groups["web"].hosts.contains("web4"));
+        assert!(!inventory.hosts.contains_key("hidden1"));
+        let web2 = &inventory.hosts["web2"];
+        assert_eq!(web2.get_variable("tier").unwrap(), "frontend");
+        assert_eq!(web2.get_variable("owner").unwrap(), "web-team");
+        // group_vars/ of the directory apply too
+        let db_vars = &inventory.hosts["db1"].group_file_variables;
+        assert_eq!(db_vars.len(), 1);
+        assert_eq!(db_vars[0].1.get("backup"), Some(&Value::Bool(true)));
+
+        let jump = &inventory.hosts["jump"];
+        assert_eq!(jump.port, 2200);
+        assert!(inventory.groups["all"].hosts.contains("jump"));
+        let ungrouped = &inventory.groups["ungrouped"].hosts;
+        assert!(ungrouped.contains("jump") && !ungrouped.contains("lonely"));
+    }
+
+    #[test]
+    fn test_parse_host_list() {
+        let inventory = parse_inventory("localhost,").unwrap();
+        assert_eq!(inventory.hosts.len(), 1);
+        assert!(inventory.groups["ungrouped"].hosts.contains("localhost"));
+
+        let inventory = parse_inventory("web1, web2:2222,web1").unwrap();
+        assert_eq!(inventory.hosts.len(), 2);
+        assert_eq!(inventory.hosts["web2"].port, 2222);
+        assert_eq!(inventory.filter_hosts("all").len(), 2);
+
+        let inventory = parse_inventory("::1,[fe80::2]:2222,").unwrap();
+        assert_eq!(inventory.hosts.len(), 2);
+        assert_eq!(inventory.hosts["::1"].port, 22);
+        assert_eq!(inventory.hosts["fe80::2"].port, 2222);
+
+        assert!(parse_inventory("web1:ssh,").is_err());
+        assert!(parse_inventory("/nonexistent/inventory").is_err());
+    }
 }
@@ -0,0 +1,300 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::sync::{Arc, Mutex};
+
+/// How a term of a host pattern combines with the hosts matched by the terms before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Operator {
+    /// `web` adds the hosts of the term
+    Union,
+    /// `&prod` keeps only the hosts also matched by the term
+    Intersection,
+    /// `!db` removes the hosts matched by the term
+    Exclusion,
+}
+
+/// A `[0]`, `[-1]`, `[1:3]` or `[2:]` suffix selecting some of the hosts a term matches,
+/// in inventory order. Like in Ansible, the end of a range is included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Subscript {
+    Index(isize),
+    Range(usize, Option<usize>),
+}
+
+impl Subscript {
+    pub fn apply(&self, names: Vec<String>) -> Vec<String> {
+        match *self {
+            Subscript::Index(index) => {
+                let index = if index < 0 {
+                    names.len().checked_sub(index.unsigned_abs())
+                } else {
+                    Some(index as usize)
+                };
+                index
+                    .and_then(|index| names.into_iter().nth(index))
+                    .into_iter()
+                    .collect()
+            }
+            Subscript::Range(start, end) => {
+                let end = end.unwrap_or(usize::MAX);
+                names
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| *index >= start && *index <= end)
+                    .map(|(_, name)| name)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// One term of a host pattern such as `web*`, `&prod` or `!db[0]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Term<'a> {
+    pub operator: Operator,
+    pub pattern: &'a str,
+    pub subscript: Option<Subscript>,
+}
+
+/// Split a host pattern into its terms, in the order Ansible applies them: unions first,
+/// then intersections, then exclusions. A pattern with only intersections or exclusions
+/// starts from `all`.
+///
+/// Terms are separated by commas or, when there is no comma, by colons outside of
+/// subscripts, so `web:&prod:!web[0]` and `web,&prod,!web[0]` are the same pattern.
+/// Like in Ansible, a pattern that is an IPv6 address is a single term.
+pub(super) fn parse(pattern: &str) -> Vec<Term<'_>> {
+    let mut terms: Vec<Term> = split(pattern).into_iter().map(parse_term).collect();
+    terms.sort_by_key(|term| term.operator as u8);
+    if !terms.is_empty() && terms.first().map(|term| term.operator) != Some(Operator::Union) {
+        terms.insert(
+            0,
+            Term {
+                operator: Operator::Union,
+                pattern: "all",
+                subscript: None,
+            },
+        );
+    }
+    terms
+}
+
+pub(super) fn split(pattern: &str) -> Vec<&str> {
+    let parts = if pattern.contains(',') {
+        pattern.split(',').collect()
+    } else if pattern.trim().parse::<Ipv6Addr>().is_ok() {
+        vec![pattern]
+    } else {
+        let mut parts = Vec::new();
+        let mut depth = 0usize;
+        let mut start = 0;
+        for (i, c) in pattern.char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth = depth.saturating_sub(1),
+                ':' if depth == 0 => {
+                    parts.push(&pattern[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        parts.push(&pattern[start..]);
+        parts
+    };
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+fn parse_term(term: &str) -> Term<'_> {
+    let (operator, pattern) = if let Some(rest) = term.strip_prefix('&') {
+        (Operator::Intersection, rest)
+    } else if let Some(rest) = term.strip_prefix('!') {
+        (Operator::Exclusion, rest)
+    } else {
+        (Operator::Union, term)
+    };
+
+    // A regex keeps its brackets, anything else may end with a subscript
+    if !pattern.starts_with('~') {
+        if let Some((base, subscript)) = split_subscript(pattern) {
+            return Term {
+                operator,
+                pattern: base,
+                subscript: Some(subscript),
+            };
+        }
+    }
+    Term {
+        operator,
+        pattern,
+        subscript: None,
+    }
+}
+
+fn split_subscript(pattern: &str) -> Option<(&str, Subscript)> {
+    let inner = pattern.strip_suffix(']')?;
+    let open = inner.rfind('[')?;
+    let (base, subscript) = (&inner[..open], &inner[open + 1..]);
+    if base.is_empty() {
+        return None;
+    }
+
+    let subscript = match subscript.split_once(':') {
+        Some((start, end)) => Subscript::Range(
+            start.trim().parse().ok()?,
+            match end.trim() {
+                "" => None,
+                end => Some(end.parse().ok()?),
+            },
+        ),
+        None => Subscript::Index(subscript.trim().parse().ok()?),
+    };
+    Some((base, subscript))
+}
+
+/// Whether a term matches names with a glob or a regex rather than only by its exact name.
+/// Dots aren't glob characters, so `web1.example.com` and `10.0.0.1` are exact names.
+pub(super) fn is_wildcard(pattern: &str) -> bool {
+    pattern.starts_with('~') || pattern.contains(['*', '?', '['])
+}
+
+/// The matchers of the patterns used with an inventory, so each one is compiled only once
+#[derive(Debug, Clone, Default)]
+pub(super) struct Matchers(Arc<Mutex<HashMap<String, Regex>>>);
+
+impl Matchers {
+    pub fn get(&self, pattern: &str) -> Result<Regex> {
+        let mut matchers = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(matcher) = matchers.get(pattern) {
+            return Ok(matcher.clone());
+        }
+        let matcher = matcher(pattern)?;
+        matchers.insert(pattern.to_string(), matcher.clone());
+        Ok(matcher)
+    }
+}
+
+/// The regex a term matches group and host names with: `~regex` as written, matched from
+/// the start of the name, or a shell style glob such as `web*.example.com` or `db[12]`
+pub(super) fn matcher(pattern: &str) -> Result<Regex> {
+    let regex = match pattern.strip_prefix('~') {
+        Some(regex) => format!("^(?:{})", regex),
+        None => format!("^{}$", glob_to_regex(pattern)),
+    };
+    Regex::new(&regex).with_context(|| format!("Invalid host pattern: {}", pattern))
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+                if chars.next_if_eq(&'!').is_some() {
+                    class.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        class.push('\\');
+                    }
+                    class.push(c);
+                }
+                if closed && !class.is_empty() {
+                    regex.push_str(&format!("[{}]", class));
+                } else {
+                    regex.push_str(&regex::escape(&format!("[{}", class)));
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pattern_terms() {
+        let terms = parse("!db:web[1:3]:&prod:~web\\d+");
+        let summary: Vec<(Operator, &str, Option<Subscript>)> = terms
+            .iter()
+            .map(|term| (term.operator, term.pattern, term.subscript))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Operator::Union, "web", Some(Subscript::Range(1, Some(3)))),
+                (Operator::Union, "~web\\d+", None),
+                (Operator::Intersection, "prod", None),
+                (Operator::Exclusion, "db", None),
+            ]
+        );
+
+        // Commas take precedence over colons, and exclusions alone start from all
+        let terms = parse("!db, !web[-1]");
+        assert_eq!(terms[0].pattern, "all");
+        assert!(parse(" , ").is_empty());
+        assert_eq!(terms[2].subscript, Some(Subscript::Index(-1)));
+        assert_eq!(
+            parse("web[2:]")[0].subscript,
+            Some(Subscript::Range(2, None))
+        );
+
+        let names: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+        assert_eq!(Subscript::Index(-1).apply(names.clone()), ["d"]);
+        assert_eq!(
+            Subscript::Range(1, Some(2)).apply(names.clone()),
+            ["b", "c"]
+        );
+        assert!(Subscript::Index(9).apply(names).is_empty());
+
+        // An IPv6 address isn't split on its colons
+        assert_eq!(split("fe80::2"), ["fe80::2"]);
+        assert_eq!(split("2001:db8:0:0:0:0:0:1"), ["2001:db8:0:0:0:0:0:1"]);
+        assert_eq!(split("::1,web"), ["::1", "web"]);
+    }
+
+    #[test]
+    fn test_pattern_matcher() {
+        let glob = matcher("web*.example.com").unwrap();
+        assert!(glob.is_match("web1.example.com"));
+        assert!(!glob.is_match("web1.example.org"));
+        assert!(!glob.is_match("web1xexample.com"));
+        assert!(matcher("db[12]").unwrap().is_match("db2"));
+        assert!(!matcher("db[!12]").unwrap().is_match("db2"));
+
+        let regex = matcher("~web\\d+").unwrap();
+        assert!(regex.is_match("web10.example.com"));
+        assert!(!regex.is_match("db-web1"));
+        assert!(matcher("~web(").is_err());
+
+        assert!(!is_wildcard("web1.example.com"));
+        assert!(!is_wildcard("10.0.0.1"));
+        assert!(is_wildcard("web?.example.com"));
+
+        let matchers = Matchers::default();
+        assert!(matchers.get("db[12]").unwrap().is_match("db1"));
+        assert_eq!(matchers.0.lock().unwrap().len(), 1);
+        matchers.get("db[12]").unwrap();
+        assert_eq!(matchers.0.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,276 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+use std::process::Command;
+
+use super::{Host, HostGroup, Inventory};
+
+/// Whether an inventory file is a dynamic inventory script, which is any executable file
+#[cfg(unix)]
+pub(super) fn is_inventory_script(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Whether an inventory file is a dynamic inventory script (Windows - not supported)
+#[cfg(windows)]
+pub(super) fn is_inventory_script(_path: &Path) -> bool {
+    false
+}
+
+/// Add the groups and hosts of a dynamic inventory script, following Ansible's protocol: the
+/// script prints the groups as JSON when run with `--list`,
+///
+/// ```json
+/// {
+///   "webservers": {"hosts": ["web1"], "vars": {"http_port": 80}, "children": ["canary"]},
+///   "dbservers": ["db1", "db2"],
+///   "_meta": {"hostvars": {"web1": {"ansible_host": "10.0.0.5"}}}
+/// }
+/// ```
+///
+/// and, only when there is no `_meta.hostvars`, the variables of a host with `--host <name>`.
+pub(super) fn parse_script_inventory(path: &Path, inventory: &mut Inventory) -> Result<()> {
+    info!("Running inventory script: {}", path.display());
+    let groups = match run_script(path, &["--list"])? {
+        Value::Mapping(groups) => groups,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Inventory script {} must print a JSON object of groups",
+                path.display()
+            ))
+        }
+    };
+
+    let mut host_vars = None;
+    let mut host_names: Vec<String> = Vec::new();
+    for (name, group) in &groups {
+        let name = name
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Group names must be strings, found {:?}", name))?;
+        if name == "_meta" {
+            host_vars = Some(group.get("hostvars").cloned().unwrap_or_default());
+            continue;
+        }
+        for host_name in add_group(inventory, name, group)
+            .with_context(|| format!("Invalid group '{}' from {}", name, path.display()))?
+        {
+            if !host_names.contains(&host_name) {
+                host_names.push(host_name);
+            }
+        }
+    }
+
+    for host_name in host_names {
+        let vars = match &host_vars {
+            Some(host_vars) => host_vars.get(&host_name).cloned().unwrap_or_default(),
+            None => run_script(path, &["--host", &host_name])?,
+        };
+        let vars = match vars {
+            Value::Mapping(vars) => vars,
+            Value::Null => Mapping::new(),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Variables of host '{}' from {} must be a JSON object",
+                    host_name,
+                    path.display()
+                ))
+            }
+        };
+
+        if let Some(host) = inventory.hosts.get_mut(&host_name) {
+            for (key, value) in vars {
+                if let Value::String(key) = key {
+                    host.set_variable(&key, value);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the script with `args` and parse what it prints as JSON
+fn run_script(path: &Path, args: &[&str]) -> Result<Value> {
+    // A bare file name would otherwise be looked up in PATH
+    let program = path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve inventory script: {}", path.display()))?;
+    debug!("Running {} {}", program.display(), args.join(" "));
+
+    let output = Command::new(&program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run inventory script: {}", path.display()))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Inventory script {} {} failed with {}: {}",
+            path.display(),
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout).with_context(|| {
+        format!(
+            "Inventory script {} {} printed invalid JSON",
+            path.display(),
+            args.join(" ")
+        )
+    })
+}
+
+/// Add a group given either as a list of hosts or as an object with `hosts`, `vars` and
+/// `children`, returning the names of its hosts
+fn add_group(inventory: &mut Inventory, name: &str, group: &Value) -> Result<Vec<String>> {
+    if !inventory.groups.contains_key(name) {
+        inventory.add_group(HostGroup::new(name));
+    }
+
+    let (hosts, vars, children) = match group {
+        Value::Sequence(_) => (Some(group), None, None),
+        Value::Mapping(_) => (group.get("hosts"), group.get("vars"), group.get("children")),
+        Value::Null => (None, None, None),
+        _ => return Err(anyhow::anyhow!("expected a list of hosts or an object")),
+    };
+
+    let host_names = names(hosts, "hosts")?;
+    for host_name in &host_names {
+        if !inventory.hosts.contains_key(host_name) {
+            debug!("Adding new host: {}", host_name);
+            inventory.add_host(Host::new(host_name));
+        }
+        if let Some(group) = inventory.groups.get_mut(name) {
+            group.add_host(host_name);
+        }
+    }
+
+    match vars {
+        Some(Value::Mapping(vars)) => {
+            if let Some(group) = inventory.groups.get_mut(name) {
+                for (key, value) in vars {
+                    if let Value::String(key) = key {
+                        group.set_variable(key, value.clone());
+                    }
+                }
+            }
+        }
+        None | Some(Value::Null) => {}
+        Some(_) => return Err(anyhow::anyhow!("vars must be an object")),
+    }
+
+    for child in names(children, "children")? {
+        match inventory.groups.get_mut(&child) {
+            Some(child_group) => child_group.parent = Some(name.to_string()),
+            None => inventory.add_group(HostGroup::new(&child).with_parent(name)),
+        }
+        if let Some(group) = inventory.groups.get_mut(name) {
+            group.add_child(&child);
+        }
+    }
+    Ok(host_names)
+}
+
+/// The host or group names of a list
+fn names(list: Option<&Value>, what: &str) -> Result<Vec<String>> {
+    match list {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(name) => Ok(name.clone()),
+                Value::Number(number) => Ok(number.to_string()),
+                _ => Err(anyhow::anyhow!("{} must be a list of names", what)),
+            })
+            .collect(),
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(_) => Err(anyhow::anyhow!("{} must be a list of names", what)),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn write_script(dir: &TempDir, name: &str, content: &str) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_script_inventory_with_meta() {
+        let dir = TempDir::new().unwrap();
+        let path = write_script(
+            &dir,
+            "inventory.sh",
+            r#"#!/bin/sh
+[ "$1" = "--list" ] || exit 1
+cat <<'EOF'
+{
+  "web": {"hosts": ["web1", "web2"], "vars": {"http_port": 80}, "children": ["canary"]},
+  "canary": {"hosts": ["web1"]},
+  "db": ["db1"],
+  "all": {"hosts": ["jump"], "vars": {"ansible_user": "admin"}},
+  "_meta": {"hostvars": {"web1": {"ansible_host": "10.0.0.5", "ports": [80, 443]}}}
+}
+EOF
+"#,
+        );
+        assert!(is_inventory_script(&path));
+
+        let mut inventory = Inventory::new();
+        parse_script_inventory(&path, &mut inventory).unwrap();
+        assert_eq!(inventory.hosts.len(), 4);
+        assert_eq!(inventory.groups["web"].hosts.len(), 2);
+        assert_eq!(inventory.groups["canary"].parent.as_deref(), Some("web"));
+        assert!(inventory.groups["db"].hosts.contains("db1"));
+        assert!(inventory.groups["all"].hosts.contains("jump"));
+        assert_eq!(
+            inventory.groups["web"].variables["http_port"],
+            Value::Number(80.into())
+        );
+
+        let web1 = &inventory.hosts["web1"];
+        assert_eq!(web1.hostname, "10.0.0.5");
+        assert!(
+            matches!(web1.get_variable("ports"), Some(Value::Sequence(ports)) if ports.len() == 2)
+        );
+    }
+
+    #[test]
+    fn test_script_inventory_host_vars_per_host() {
+        let dir = TempDir::new().unwrap();
+        let path = write_script(
+            &dir,
+            "inventory.sh",
+            r#"#!/bin/sh
+case "$1" in
+  --list) echo '{"web": ["web1", "web2"]}' ;;
+  --host) [ "$2" = db1 ] && exit 1; echo "{\"rack\": \"rack-$2\"}" ;;
+  *) exit 1 ;;
+esac
+"#,
+        );
+
+        // Only the hosts of the script are looked up, not the ones of other sources
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("db1"));
+        parse_script_inventory(&path, &mut inventory).unwrap();
+        assert_eq!(
+            inventory.hosts["web2"].get_variable("rack").unwrap(),
+            "rack-web2"
+        );
+
+        let failing = write_script(&dir, "broken.sh", "#!/bin/sh\necho broken >&2\nexit 3\n");
+        let error = parse_script_inventory(&failing, &mut inventory).unwrap_err();
+        assert!(format!("{:#}", error).contains("broken"));
+    }
+}
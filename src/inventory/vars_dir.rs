@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the variable files in `group_vars` and `host_vars`, besides files without one
+const VARS_EXTENSIONS: &[&str] = &["yml", "yaml", "json"];
+
+/// Read the variable files of a `group_vars` or `host_vars` directory, keyed by group or host
+/// name: `<name>.yml` (or `.yaml`, `.json`, no extension) and the files with these
+/// extensions under `<name>/`. Entries whose name is not `known` are skipped unread, so notes
+/// such as `README.md` next to the variable files are left alone.
+/// Later files override the variables of earlier ones.
+pub(super) fn load(dir: &Path, known: impl Fn(&str) -> bool) -> Result<Vec<(String, Mapping)>> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read variables directory: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+
+    let mut vars: Vec<(String, Mapping)> = Vec::new();
+    for path in entries {
+        let Some(name) = vars_name(&path) else {
+            continue;
+        };
+        if !known(&name) {
+            debug!("Ignoring variables of unknown group or host '{}'", name);
+            continue;
+        }
+
+        let mut files = Vec::new();
+        if path.is_dir() {
+            collect_files(&path, &mut files)?;
+        } else {
+            files.push(path);
+        }
+
+        for file in files {
+            debug!("Loading variables of '{}' from {}", name, file.display());
+            let file_vars = read_vars_file(&file)?;
+            match vars.iter_mut().find(|(existing, _)| *existing == name) {
+                Some((_, existing)) => existing.extend(file_vars),
+                None => vars.push((name.clone(), file_vars)),
+            }
+        }
+    }
+    Ok(vars)
+}
+
+/// The group or host a file or directory holds variables for, `None` for files to ignore
+fn vars_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    if file_name.starts_with('.') {
+        return None;
+    }
+    if path.is_dir() {
+        return Some(file_name.to_string());
+    }
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if VARS_EXTENSIONS.contains(&extension) => {
+            Some(path.file_stem()?.to_str()?.to_string())
+        }
+        // Host names often contain dots, so a file like `web1.example.com` has no extension
+        Some(_) if !file_name.ends_with('~') => Some(file_name.to_string()),
+        Some(_) => None,
+        None => Some(file_name.to_string()),
+    }
+}
+
+/// The variable files of a `<name>/` directory and its subdirectories, in name order. Names are
+/// not host names there, so any other extension (`README.md`, `main.yml.orig`) is skipped.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read variables directory: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if is_vars_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_vars_file(path: &Path) -> bool {
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    if file_name.starts_with('.') {
+        return false;
+    }
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => VARS_EXTENSIONS.contains(&extension),
+        None => true,
+    }
+}
+
+fn read_vars_file(path: &Path) -> Result<Mapping> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read variables file: {}", path.display()))?;
+    match serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse variables file: {}", path.display()))?
+    {
+        Value::Mapping(vars) => Ok(vars),
+        Value::Null => Ok(Mapping::new()),
+        _ => Err(anyhow::anyhow!(
+            "Variables file {} must contain a mapping of variables",
+            path.display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_vars_dir() {
+        let dir = TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("all.yml", "ntp: pool.ntp.org\n");
+        write("web/10-main.yml", "http_port: 80\nusers: [alice]\n");
+        write("web/20-override.yaml", "http_port: 8080\n");
+        write("web/30-extra", "workers: 4\n");
+        write("web/README.md", "# Web servers\n");
+        write("web/notes.txt", "not yaml: [\n");
+        write("web/10-main.yml.orig", "- an old copy\n");
+        write("web1.example.com", "rack: a1\n");
+        write(".hidden.yml", "ignored: true\n");
+        write("web.yml~", "ignored: true\n");
+        write("README.md", "Variables of the web servers.\n");
+        write("notes.txt", "not yaml: [\n");
+        write("unknown.yml", "- not\n- a mapping\n");
+
+        let known = |name: &str| ["all", "web", "web1.example.com", "broken"].contains(&name);
+        let vars = load(dir.path(), known).unwrap();
+        let names: Vec<&str> = vars.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["all", "web", "web1.example.com"]);
+
+        let web = &vars[1].1;
+        assert_eq!(web.get("http_port"), Some(&Value::Number(8080.into())));
+        assert!(matches!(web.get("users"), Some(Value::Sequence(users)) if users.len() == 1));
+        assert_eq!(web.get("workers"), Some(&Value::Number(4.into())));
+
+        write("broken.yml", "- not\n- a mapping\n");
+        assert!(load(dir.path(), known).is_err());
+    }
+}
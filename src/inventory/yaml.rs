@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+
+use super::parser::{expand_host_range, split_host_port};
+use super::{Host, HostGroup, Inventory};
+
+/// Whether an inventory file uses the YAML format: a `.yml`, `.yaml` or `.json` file, or a
+/// file without one of these extensions whose content is a mapping of group names to groups
+pub(super) fn is_yaml_inventory(path: &Path, content: &str) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yml" | "yaml" | "json") => true,
+        _ => match serde_yaml::from_str::<Value>(content) {
+            Ok(Value::Mapping(groups)) => groups
+                .values()
+                .all(|group| matches!(group, Value::Mapping(_) | Value::Null)),
+            _ => false,
+        },
+    }
+}
+
+/// Add the groups and hosts of a YAML inventory, where every group may have `hosts`, `vars` and `children`:
+///
+/// ```yaml
+/// all:
+///   vars:
+///     ansible_user: admin
+///   children:
+///     webservers:
+///       hosts:
+///         web[01:03].example.com:
+///           http_port: 8080
+/// ```
+pub(super) fn parse_yaml_inventory(content: &str, inventory: &mut Inventory) -> Result<()> {
+    match serde_yaml::from_str(content).context("Invalid YAML")? {
+        Value::Mapping(groups) => {
+            for (name, group) in &groups {
+                let name = group_name(name)?;
+                add_group(inventory, name, None, group)?;
+            }
+        }
+        Value::Null => {}
+        _ => {
+            return Err(anyhow::anyhow!(
+                "A YAML inventory must be a mapping of group names to groups"
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn group_name(name: &Value) -> Result<&str> {
+    name.as_str()
+        .ok_or_else(|| anyhow::anyhow!("Group names must be strings, found {:?}", name))
+}
+
+/// Add a group with its hosts, variables and, recursively, its children
+fn add_group(
+    inventory: &mut Inventory,
+    name: &str,
+    parent: Option<&str>,
+    group: &Value,
+) -> Result<()> {
+    debug!("Found group: {}", name);
+    if !inventory.groups.contains_key(name) {
+        inventory.add_group(HostGroup::new(name));
+    }
+    if let (Some(parent), Some(group)) = (parent, inventory.groups.get_mut(name)) {
+        group.parent = Some(parent.to_string());
+    }
+
+    let group = match group {
+        Value::Mapping(group) => group,
+        Value::Null => return Ok(()),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Group '{}' must be a mapping with hosts, vars and children",
+                name
+            ))
+        }
+    };
+
+    for (key, value) in group {
+        match key.as_str() {
+            Some("hosts") => add_hosts(inventory, name, value)?,
+            Some("vars") => {
+                let vars = mapping(value)
+                    .with_context(|| format!("Invalid vars of group '{}'", name))?;
+                if let Some(group) = inventory.groups.get_mut(name) {
+                    for (key, value) in &vars {
+                        group.set_variable(group_name(key)?, value.clone());
+                    }
+                }
+            }
+            Some("children") => {
+                let children = mapping(value)
+                    .with_context(|| format!("Invalid children of group '{}'", name))?;
+                for (child, child_group) in &children {
+                    let child = group_name(child)?;
+                    if let Some(group) = inventory.groups.get_mut(name) {
+                        group.add_child(child);
+                    }
+                    add_group(inventory, child, Some(name), child_group)?;
+                }
+            }
+            _ => warn!(
+                "Skipping unexpected key {:?} in group '{}', only hosts, vars and children are valid",
+                key, name
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Add the hosts of a group, with their variables. Host names may hold ranges like in the
+/// INI format and end with a `:port`, written `[addr]:port` for IPv6 addresses.
+fn add_hosts(inventory: &mut Inventory, group_name: &str, hosts: &Value) -> Result<()> {
+    let hosts =
+        mapping(hosts).with_context(|| format!("Invalid hosts of group '{}'", group_name))?;
+
+    for (entry, vars) in &hosts {
+        let entry = match entry {
+            Value::String(entry) => entry.clone(),
+            Value::Number(number) => number.to_string(),
+            _ => return Err(anyhow::anyhow!("Invalid host name: {:?}", entry)),
+        };
+        let vars = mapping(vars).with_context(|| format!("Invalid vars of host '{}'", entry))?;
+
+        let (pattern, port) = match split_host_port(&entry) {
+            (name, Some(port)) => match port.parse::<u16>() {
+                Ok(port) => (name, Some(port)),
+                Err(_) => (entry.as_str(), None),
+            },
+            (name, None) => (name, None),
+        };
+
+        for host_name in expand_host_range(pattern)? {
+            if !inventory.hosts.contains_key(&host_name) {
+                debug!("Adding new host: {}", host_name);
+                inventory.add_host(Host::new(&host_name));
+            }
+            if let Some(group) = inventory.groups.get_mut(group_name) {
+                group.add_host(&host_name);
+            }
+
+            if let Some(host) = inventory.hosts.get_mut(&host_name) {
+                if let Some(port) = port {
+                    host.port = port;
+                }
+                for (key, value) in &vars {
+                    let key = key.as_str().ok_or_else(|| {
+                        anyhow::anyhow!("Variable names of host '{}' must be strings", host_name)
+                    })?;
+                    host.set_variable(key, value.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The entries of a mapping, where an empty value counts as an empty mapping
+fn mapping(value: &Value) -> Result<Mapping> {
+    match value {
+        Value::Mapping(mapping) => Ok(mapping.clone()),
+        Value::Null => Ok(Mapping::new()),
+        _ => Err(anyhow::anyhow!("expected a mapping, found {:?}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_yaml_inventory() {
+        let content = r#"
+all:
+  vars:
+    ansible_user: admin
+    ntp_servers: [pool.ntp.org]
+  hosts:
+    jump.example.com:
+  children:
+    webservers:
+      vars:
+        http_port: 8080
+      hosts:
+        web[01:02].example.com:
+        web03.example.com:2222:
+          backup_enabled: true
+      children:
+        canary:
+          hosts:
+            web01.example.com:
+              canary: true
+    dbservers:
+      hosts:
+        db1:
+        2001:db8:0:0:0:0:0:1:
+        "[2001:db8::2]:2222":
+"#;
+        let mut inventory = Inventory::new();
+        parse_yaml_inventory(content, &mut inventory).unwrap();
+        assert_eq!(inventory.hosts.len(), 7);
+        assert!(inventory.groups["all"].hosts.contains("jump.example.com"));
+
+        let webservers = &inventory.groups["webservers"];
+        assert_eq!(webservers.hosts.len(), 3);
+        assert!(webservers.children.contains("canary"));
+        assert_eq!(
+            webservers.variables["http_port"],
+            Value::Number(8080.into())
+        );
+        assert_eq!(
+            inventory.groups["canary"].parent.as_deref(),
+            Some("webservers")
+        );
+        assert_eq!(
+            inventory.groups["webservers"].parent.as_deref(),
+            Some("all")
+        );
+        assert!(matches!(
+            inventory.groups["all"].variables.get("ntp_servers"),
+            Some(Value::Sequence(servers)) if servers.len() == 1
+        ));
+
+        let web03 = &inventory.hosts["web03.example.com"];
+        assert_eq!(web03.port, 2222);
+        assert_eq!(
+            web03.get_variable("backup_enabled"),
+            Some(&Value::Bool(true))
+        );
+        // IPv6 addresses keep every group, a port needs the bracketed form
+        assert_eq!(inventory.hosts["2001:db8:0:0:0:0:0:1"].port, 22);
+        assert_eq!(inventory.hosts["2001:db8::2"].port, 2222);
+        let web01 = &inventory.hosts["web01.example.com"];
+        assert_eq!(web01.get_variable("canary"), Some(&Value::Bool(true)));
+
+        assert!(parse_yaml_inventory("all: [web1]", &mut inventory).is_err());
+        assert!(parse_yaml_inventory("all:\n  hosts: [web1]", &mut inventory).is_err());
+    }
+
+    #[test]
+    fn test_is_yaml_inventory() {
+        let path = Path::new("inventory");
+        assert!(is_yaml_inventory(Path::new("hosts.yml"), ""));
+        assert!(is_yaml_inventory(path, "all:\n  hosts:\n    web1:\n"));
+        assert!(!is_yaml_inventory(path, "[web]\nweb1 ansible_port=22\n"));
+        assert!(!is_yaml_inventory(path, "web1\nweb2\n"));
+        assert!(!is_yaml_inventory(path, "web1 ansible_host=10.0.0.1\n"));
+    }
+}
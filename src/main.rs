@@ -5,6 +5,23 @@ use env_logger::Builder;
 use log::{info, LevelFilter};
 use std::io::Write;
 
+/// The variables of every `-e/--extra-vars` option, in order
+fn extra_vars(sub_matches: &clap::ArgMatches) -> Result<serde_yaml::Mapping> {
+    let values: Vec<String> = sub_matches
+        .get_many::<String>("extra-vars")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    playbook::parse_extra_vars(&values)
+}
+
+/// The inventory sources given with `-i`, defaulting to the `inventory` file
+fn inventory_sources(sub_matches: &clap::ArgMatches) -> Vec<String> {
+    sub_matches
+        .get_many::<String>("inventory")
+        .map(|sources| sources.cloned().collect())
+        .unwrap_or_else(|| vec!["inventory".to_string()])
+}
+
 fn main() -> Result<()> {
     // Delay logger initialization until after parsing arguments
     let app = cli::build_cli();
@@ -43,31 +60,77 @@ fn main() -> Result<()> {
     match matches.subcommand() {
         Some(("playbook", sub_matches)) => {
             let playbook_file = sub_matches.get_one::<String>("playbook").unwrap();
-            let inventory_file = sub_matches
-                .get_one::<String>("inventory")
-                .map(|s| s.as_str())
-                .unwrap_or("inventory");
+            let inventory_sources = inventory_sources(sub_matches);
+
+            let options = playbook::PlaybookOptions {
+                check_mode: sub_matches.get_flag("check"),
+                limit: sub_matches.get_one::<String>("limit").cloned(),
+                forks: sub_matches
+                    .get_one::<u64>("forks")
+                    .map(|forks| *forks as usize),
+                tags: sub_matches
+                    .get_many::<String>("tags")
+                    .map(|tags| tags.cloned().collect())
+                    .unwrap_or_default(),
+                skip_tags: sub_matches
+                    .get_many::<String>("skip-tags")
+                    .map(|tags| tags.cloned().collect())
+                    .unwrap_or_default(),
+                extra_vars: extra_vars(sub_matches)?,
+            };
+
+            if sub_matches.get_flag("syntax-check") {
+                if let Err(e) = playbook::syntax_check(playbook_file) {
+                    eprintln!("Syntax error in playbook: {:#}", e);
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
 
-            info!("Running playbook: {}", playbook_file);
-            let inventory = inventory::parse(inventory_file)?;
-            let result = playbook::execute(playbook_file, &inventory);
+            if let Some(host) = sub_matches.get_one::<String>("debug-vars") {
+                let inventory = inventory::parse_all(&inventory_sources)?;
+                playbook::debug_vars(playbook_file, &inventory, &options, host)?;
+                return Ok(());
+            }
 
-            if let Err(e) = result {
-                eprintln!("Error executing playbook: {}", e);
-                std::process::exit(1);
+            let listing = playbook::Listing {
+                hosts: sub_matches.get_flag("list-hosts"),
+                tasks: sub_matches.get_flag("list-tasks"),
+                tags: sub_matches.get_flag("list-tags"),
+            };
+            if listing.hosts || listing.tasks || listing.tags {
+                // The tasks and tags can be listed without an inventory
+                let inventory = if listing.hosts {
+                    inventory::parse_all(&inventory_sources)?
+                } else {
+                    inventory::Inventory::new()
+                };
+                playbook::list(playbook_file, &inventory, &options, listing)?;
+                return Ok(());
+            }
+
+            info!("Running playbook: {}", playbook_file);
+            let inventory = inventory::parse_all(&inventory_sources)?;
+            let result = playbook::execute_with_options(playbook_file, &inventory, &options);
+
+            match result {
+                // Failed hosts exit with 2, unreachable ones with 4
+                Ok(stats) if stats.exit_code() != 0 => std::process::exit(stats.exit_code()),
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Error executing playbook: {}", e);
+                    std::process::exit(1);
+                }
             }
         }
         Some(("ad-hoc", sub_matches)) => {
             let module = sub_matches.get_one::<String>("module").unwrap();
             let args = sub_matches.get_one::<String>("args").unwrap();
             let host_pattern = sub_matches.get_one::<String>("pattern").unwrap();
-            let inventory_file = sub_matches
-                .get_one::<String>("inventory")
-                .map(|s| s.as_str())
-                .unwrap_or("inventory");
+            let inventory_sources = inventory_sources(sub_matches);
 
             info!("Running ad-hoc command with module: {}", module);
-            let inventory = inventory::parse(inventory_file)?;
+            let inventory = inventory::parse_all(&inventory_sources)?;
             let hosts = inventory.filter_hosts(host_pattern);
 
             if hosts.is_empty() {
@@ -75,20 +138,18 @@ fn main() -> Result<()> {
                 std::process::exit(1);
             }
 
-            let result = modules::run_adhoc(&hosts, module, args);
+            let extra_vars = extra_vars(sub_matches)?;
+            let result = modules::run_adhoc(&hosts, module, args, &extra_vars);
             if let Err(e) = result {
                 eprintln!("Error executing ad-hoc command: {}", e);
                 std::process::exit(1);
             }
         }
         Some(("inventory-debug", sub_matches)) => {
-            let inventory_file = sub_matches
-                .get_one::<String>("inventory")
-                .map(|s| s.as_str())
-                .unwrap_or("inventory");
+            let inventory_sources = inventory_sources(sub_matches);
 
-            info!("Debugging inventory file: {}", inventory_file);
-            let inventory = inventory::parse(inventory_file)?;
+            info!("Debugging inventory: {}", inventory_sources.join(", "));
+            let inventory = inventory::parse_all(&inventory_sources)?;
 
             println!("\n=== Inventory Debug Information ===");
             println!("Total hosts: {}", inventory.hosts.len());
@@ -111,7 +172,7 @@ fn main() -> Result<()> {
                 if !group.variables.is_empty() {
                     println!("  Variables:");
                     for (key, value) in &group.variables {
-                        println!("    - {} = {}", key, value);
+                        println!("    - {} = {}", key, serde_json::to_string(value)?);
                     }
                 }
             }
@@ -122,7 +183,7 @@ fn main() -> Result<()> {
                 if !host.variables.is_empty() {
                     println!("  Variables:");
                     for (key, value) in &host.variables {
-                        println!("    - {} = {}", key, value);
+                        println!("    - {} = {}", key, serde_json::to_string(value)?);
                     }
                 }
             }
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use log::debug;
+
+use crate::ssh::connection::SshClient;
+
+/// Quote an argument for a POSIX shell, so paths with spaces or metacharacters stay literal
+pub(crate) fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Run a read-only probe command, escalating only when the task uses become
+fn probe(
+    ssh_client: &SshClient,
+    cmd: &str,
+    use_become: bool,
+    become_user: &str,
+) -> Result<(i32, String, String)> {
+    if use_become {
+        ssh_client.execute_sudo_command(cmd, become_user)
+    } else {
+        ssh_client.execute_command(cmd)
+    }
+}
+
+/// Check whether the remote file already holds the desired content
+pub fn remote_content_matches(
+    ssh_client: &SshClient,
+    path: &str,
+    content: &str,
+    use_become: bool,
+    become_user: &str,
+) -> Result<bool> {
+    let (exit_code, stdout, _) = probe(
+        ssh_client,
+        &format!("cat {} 2>/dev/null", shell_quote(path)),
+        use_become,
+        become_user,
+    )?;
+    if exit_code != 0 {
+        debug!("{} is missing or unreadable, it would be created", path);
+        return Ok(false);
+    }
+    Ok(content_matches(&stdout, content))
+}
+
+/// Check whether the remote path already has the requested mode, owner and group
+pub fn remote_attributes_match(
+    ssh_client: &SshClient,
+    path: &str,
+    mode: Option<&str>,
+    owner: Option<&str>,
+    group: Option<&str>,
+    use_become: bool,
+    become_user: &str,
+) -> Result<bool> {
+    if mode.is_none() && owner.is_none() && group.is_none() {
+        return Ok(true);
+    }
+
+    let (exit_code, stdout, _) = probe(
+        ssh_client,
+        &format!("stat -c '%a %U %G' {} 2>/dev/null", shell_quote(path)),
+        use_become,
+        become_user,
+    )?;
+    if exit_code != 0 {
+        return Ok(false);
+    }
+    Ok(attributes_match(&stdout, mode, owner, group))
+}
+
+/// Check whether a remote path exists and passes the given `test` flag (e.g. "-f", "-d")
+pub fn remote_path_test(
+    ssh_client: &SshClient,
+    flag: &str,
+    path: &str,
+    use_become: bool,
+    become_user: &str,
+) -> Result<bool> {
+    let (exit_code, _, _) = probe(
+        ssh_client,
+        &format!("test {} {}", flag, shell_quote(path)),
+        use_become,
+        become_user,
+    )?;
+    Ok(exit_code == 0)
+}
+
+/// Compare file content, tolerating the trailing newline added when files are written
+fn content_matches(current: &str, desired: &str) -> bool {
+    current == desired || current.strip_suffix('\n') == Some(desired)
+}
+
+/// Compare `stat -c '%a %U %G'` output against the requested attributes
+fn attributes_match(
+    stat_output: &str,
+    mode: Option<&str>,
+    owner: Option<&str>,
+    group: Option<&str>,
+) -> bool {
+    let fields: Vec<&str> = stat_output.split_whitespace().collect();
+    if fields.len() != 3 {
+        return false;
+    }
+
+    let normalize = |m: &str| {
+        let trimmed = m.trim_start_matches('0');
+        if trimmed.is_empty() {
+            "0".to_string()
+        } else {
+            trimmed.to_string()
+        }
+    };
+
+    mode.is_none_or(|m| normalize(m) == normalize(fields[0]))
+        && owner.is_none_or(|o| o == fields[1])
+        && group.is_none_or(|g| g == fields[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/etc/app.conf"), "'/etc/app.conf'");
+        assert_eq!(shell_quote("/tmp/my file"), "'/tmp/my file'");
+        assert_eq!(shell_quote("/tmp/x; rm -rf /"), "'/tmp/x; rm -rf /'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_content_matches() {
+        assert!(content_matches("hello\n", "hello"));
+        assert!(content_matches("hello\n", "hello\n"));
+        assert!(!content_matches("hello\n", "world"));
+    }
+
+    #[test]
+    fn test_attributes_match() {
+        assert!(attributes_match(
+            "644 root root\n",
+            Some("0644"),
+            None,
+            None
+        ));
+        assert!(attributes_match(
+            "755 app staff",
+            Some("755"),
+            Some("app"),
+            Some("staff")
+        ));
+        assert!(!attributes_match("644 root root", Some("0600"), None, None));
+        assert!(!attributes_match("644 root root", None, Some("app"), None));
+        assert!(!attributes_match("", Some("644"), None, None));
+    }
+}
@@ -15,12 +15,7 @@ impl ModuleExecutor for CommandModule {
         use_become: bool,
         become_user: &str,
     ) -> Result<ModuleResult> {
-        let command_str = Self::extract_command_arg(command_args)?;
-
-        info!("Executing command: {}", command_str);
-
-        let (exit_code, stdout, stderr) =
-            Self::execute_command(ssh_client, &command_str, use_become, become_user)?;
+        let (exit_code, stdout, stderr) = run(ssh_client, command_args, use_become, become_user)?;
 
         // 处理结果
         if !stdout.trim().is_empty() {
@@ -41,6 +36,20 @@ impl ModuleExecutor for CommandModule {
     }
 }
 
+/// Run the command and return its exit code and output, without treating a non-zero exit code as an error
+pub fn run(
+    ssh_client: &SshClient,
+    command_args: &Value,
+    use_become: bool,
+    become_user: &str,
+) -> Result<(i32, String, String)> {
+    let command_str = CommandModule::extract_command_arg(command_args)?;
+
+    info!("Executing command: {}", command_str);
+
+    CommandModule::execute_command(ssh_client, &command_str, use_become, become_user)
+}
+
 pub fn execute(
     ssh_client: &SshClient,
     command_args: &Value,
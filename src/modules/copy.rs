@@ -5,8 +5,7 @@ use std::path::Path;
 
 use crate::inventory::Host;
 use crate::modules::param::{get_optional_param, get_param};
-use crate::modules::ModuleExecutor;
-use crate::modules::ModuleResult;
+use crate::modules::{check, is_check_mode, ModuleExecutor, ModuleResult};
 use crate::ssh::connection::SshClient;
 
 pub struct CopyModule;
@@ -16,7 +15,7 @@ impl ModuleExecutor for CopyModule {
         ssh_client: &SshClient,
         copy_args: &Value,
         use_become: bool,
-        _become_user: &str,
+        become_user: &str,
     ) -> Result<ModuleResult> {
         let dest = get_param::<String>(copy_args, "dest")?;
 
@@ -54,6 +53,34 @@ impl ModuleExecutor for CopyModule {
             ));
         };
 
+        if is_check_mode(copy_args) {
+            let up_to_date = check::remote_content_matches(
+                ssh_client,
+                &dest,
+                &content,
+                use_become,
+                become_user,
+            )? && check::remote_attributes_match(
+                ssh_client,
+                &dest,
+                mode.as_deref(),
+                owner.as_deref(),
+                group.as_deref(),
+                use_become,
+                become_user,
+            )?;
+
+            return Ok(ModuleResult {
+                changed: !up_to_date,
+                msg: if up_to_date {
+                    format!("{} is already up to date", dest)
+                } else {
+                    format!("{} would be updated", dest)
+                },
+                ..Default::default()
+            });
+        }
+
         info!(
             "Copying content to {}{}",
             dest,
@@ -4,7 +4,7 @@ use serde_yaml::Value;
 
 use crate::inventory::Host;
 use crate::modules::param::{get_optional_param, get_param};
-use crate::modules::ModuleResult;
+use crate::modules::{check, is_check_mode, ModuleResult};
 use crate::ssh::connection::SshClient;
 
 #[derive(Debug, PartialEq)]
@@ -51,6 +51,27 @@ pub fn execute(
     let owner = get_optional_param::<String>(file_args, "owner");
     let group = get_optional_param::<String>(file_args, "group");
 
+    if is_check_mode(file_args) {
+        let changed = predict_file_change(
+            ssh_client,
+            file_args,
+            &path,
+            &state,
+            (mode.as_deref(), owner.as_deref(), group.as_deref()),
+            use_become,
+            become_user,
+        )?;
+        return Ok(ModuleResult {
+            changed,
+            msg: if changed {
+                format!("File {} would be changed to {:?}", path, state)
+            } else {
+                format!("File {} is already {:?}", path, state)
+            },
+            ..Default::default()
+        });
+    }
+
     match state {
         FileState::File => {
             create_file(ssh_client, &path, use_become, become_user)?;
@@ -100,6 +121,56 @@ pub fn execute(
     })
 }
 
+/// Work out whether applying the requested state would change the path, without touching it
+fn predict_file_change(
+    ssh_client: &SshClient,
+    file_args: &Value,
+    path: &str,
+    state: &FileState,
+    (mode, owner, group): (Option<&str>, Option<&str>, Option<&str>),
+    use_become: bool,
+    become_user: &str,
+) -> Result<bool> {
+    let in_state = match state {
+        FileState::File => {
+            check::remote_path_test(ssh_client, "-f", path, use_become, become_user)?
+        }
+        FileState::Directory => {
+            check::remote_path_test(ssh_client, "-d", path, use_become, become_user)?
+        }
+        FileState::Absent => {
+            // `test -e` follows links, so check for dangling links separately
+            return Ok(
+                check::remote_path_test(ssh_client, "-e", path, use_become, become_user)?
+                    || check::remote_path_test(ssh_client, "-L", path, use_become, become_user)?,
+            );
+        }
+        // Touching always updates the timestamps
+        FileState::Touch => return Ok(true),
+        FileState::Link => {
+            let src = get_param::<String>(file_args, "src")?;
+            let cmd = format!("readlink {}", check::shell_quote(path));
+            let (exit_code, stdout, _) = if use_become {
+                ssh_client.execute_sudo_command(&cmd, become_user)?
+            } else {
+                ssh_client.execute_command(&cmd)?
+            };
+            exit_code == 0 && stdout.trim_end() == src
+        }
+    };
+
+    Ok(!in_state
+        || !check::remote_attributes_match(
+            ssh_client,
+            path,
+            mode,
+            owner,
+            group,
+            use_become,
+            become_user,
+        )?)
+}
+
 /// Set file permissions and ownership with proper sudo handling
 fn set_file_permissions_and_ownership(
     ssh_client: &SshClient,
@@ -271,18 +342,7 @@ fn create_symlink(
 pub fn execute_adhoc(host: &Host, file_args: &Value) -> Result<ModuleResult> {
     info!("Connecting to host: {}", host.name);
     let ssh_client = SshClient::connect(host)?;
-    execute(&ssh_client, file_args, false, "")?;
-    Ok(ModuleResult {
-        stdout: String::new(),
-        stderr: String::new(),
-        changed: true,
-        failed: false,
-        msg: format!(
-            "File {} state changed to {}",
-            get_param::<String>(file_args, "path")?,
-            get_param::<String>(file_args, "state")?
-        ),
-    })
+    execute(&ssh_client, file_args, false, "")
 }
 
 #[cfg(test)]
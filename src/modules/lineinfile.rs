@@ -6,9 +6,45 @@ use std::fs;
 
 use crate::inventory::Host;
 use crate::modules::param::{get_optional_param, get_param};
-use crate::modules::ModuleResult;
+use crate::modules::{is_check_mode, ModuleResult};
 use crate::ssh::connection::SshClient;
 
+/// Parameters of the lineinfile module
+struct LineInFileOptions {
+    path: String,
+    line: Option<String>,
+    regexp: Option<String>,
+    state: String,
+    backup: bool,
+    create: bool,
+    insertafter: Option<String>,
+    insertbefore: Option<String>,
+    owner: Option<String>,
+    group: Option<String>,
+    mode: Option<String>,
+    check_mode: bool, // Report the change without writing the file
+}
+
+impl LineInFileOptions {
+    fn from_args(args: &Value) -> Result<Self> {
+        Ok(LineInFileOptions {
+            path: get_param::<String>(args, "path")?,
+            line: get_optional_param::<String>(args, "line"),
+            regexp: get_optional_param::<String>(args, "regexp"),
+            state: get_optional_param::<String>(args, "state")
+                .unwrap_or_else(|| "present".to_string()),
+            backup: get_optional_param::<bool>(args, "backup").unwrap_or(false),
+            create: get_optional_param::<bool>(args, "create").unwrap_or(false),
+            insertafter: get_optional_param::<String>(args, "insertafter"),
+            insertbefore: get_optional_param::<String>(args, "insertbefore"),
+            owner: get_optional_param::<String>(args, "owner"),
+            group: get_optional_param::<String>(args, "group"),
+            mode: get_optional_param::<String>(args, "mode"),
+            check_mode: is_check_mode(args),
+        })
+    }
+}
+
 /// Execute the lineinfile module logic: manage lines in a file
 pub fn execute(
     ssh_client: &SshClient,
@@ -16,22 +52,9 @@ pub fn execute(
     use_become: bool,
     _become_user: &str,
 ) -> Result<ModuleResult> {
-    let path = get_param::<String>(args, "path")?;
-
-    // Extract parameters
-    let line = get_optional_param::<String>(args, "line");
-    let regexp = get_optional_param::<String>(args, "regexp");
-    let state =
-        get_optional_param::<String>(args, "state").unwrap_or_else(|| "present".to_string());
-    let backup = get_optional_param::<bool>(args, "backup").unwrap_or(false);
-    let create = get_optional_param::<bool>(args, "create").unwrap_or(false);
-    let insertafter = get_optional_param::<String>(args, "insertafter");
-    let insertbefore = get_optional_param::<String>(args, "insertbefore");
-    let owner = get_optional_param::<String>(args, "owner");
-    let group = get_optional_param::<String>(args, "group");
-    let mode = get_optional_param::<String>(args, "mode");
-
-    info!("Managing line in file: {}", path);
+    let options = LineInFileOptions::from_args(args)?;
+
+    info!("Managing line in file: {}", options.path);
 
     // Check if we need _host_type for local execution
     let is_local = if let Value::Mapping(args_map) = args {
@@ -47,52 +70,30 @@ pub fn execute(
     };
 
     if is_local {
-        execute_local(
-            &path,
-            line,
-            regexp,
-            &state,
-            backup,
-            create,
-            insertafter,
-            insertbefore,
-            owner,
-            group,
-            mode,
-        )
+        execute_local(options)
     } else {
-        execute_remote(
-            ssh_client,
-            &path,
-            line,
-            regexp,
-            &state,
-            backup,
-            create,
-            insertafter,
-            insertbefore,
-            owner,
-            group,
-            mode,
-            use_become,
-        )
+        execute_remote(ssh_client, options, use_become)
     }
 }
 
 /// Execute lineinfile locally
-fn execute_local(
-    path: &str,
-    line: Option<String>,
-    regexp: Option<String>,
-    state: &str,
-    backup: bool,
-    create: bool,
-    insertafter: Option<String>,
-    insertbefore: Option<String>,
-    owner: Option<String>,
-    group: Option<String>,
-    mode: Option<String>,
-) -> Result<ModuleResult> {
+fn execute_local(options: LineInFileOptions) -> Result<ModuleResult> {
+    let LineInFileOptions {
+        path,
+        line,
+        regexp,
+        state,
+        backup,
+        create,
+        insertafter,
+        insertbefore,
+        owner,
+        group,
+        mode,
+        check_mode,
+    } = options;
+    let (path, state) = (path.as_str(), state.as_str());
+
     let path_obj = std::path::Path::new(path);
 
     // Check if file exists
@@ -114,7 +115,7 @@ fn execute_local(
     let _original_content = content.clone();
 
     // Create backup if requested
-    if backup && file_exists {
+    if backup && file_exists && !check_mode {
         let backup_path = format!("{}.backup", path);
         fs::copy(path, &backup_path)
             .with_context(|| format!("Failed to create backup: {}", backup_path))?;
@@ -125,7 +126,7 @@ fn execute_local(
         process_line_modifications(&mut content, line, regexp, state, insertafter, insertbefore)?;
     let changed = result;
 
-    if changed || !file_exists {
+    if (changed || !file_exists) && !check_mode {
         // Write the file
         fs::write(path, &content).with_context(|| format!("Failed to write file: {}", path))?;
 
@@ -169,19 +170,25 @@ fn execute_local(
 /// Execute lineinfile remotely via SSH
 fn execute_remote(
     ssh_client: &SshClient,
-    path: &str,
-    line: Option<String>,
-    regexp: Option<String>,
-    state: &str,
-    backup: bool,
-    create: bool,
-    insertafter: Option<String>,
-    insertbefore: Option<String>,
-    owner: Option<String>,
-    group: Option<String>,
-    mode: Option<String>,
+    options: LineInFileOptions,
     use_become: bool,
 ) -> Result<ModuleResult> {
+    let LineInFileOptions {
+        path,
+        line,
+        regexp,
+        state,
+        backup,
+        create,
+        insertafter,
+        insertbefore,
+        owner,
+        group,
+        mode,
+        check_mode,
+    } = options;
+    let (path, state) = (path.as_str(), state.as_str());
+
     // Check if file exists
     let check_cmd = format!("test -f {}", path);
     let (exit_code, _, _) = if use_become {
@@ -219,7 +226,7 @@ fn execute_remote(
     let _original_content = content.clone();
 
     // Create backup if requested
-    if backup && file_exists {
+    if backup && file_exists && !check_mode {
         let backup_cmd = format!("cp {} {}.backup", path, path);
         let (exit_code, _, stderr) = if use_become {
             ssh_client.execute_sudo_command(&backup_cmd, "")?
@@ -237,7 +244,7 @@ fn execute_remote(
     let changed =
         process_line_modifications(&mut content, line, regexp, state, insertafter, insertbefore)?;
 
-    if changed || !file_exists {
+    if (changed || !file_exists) && !check_mode {
         // Write the modified content to the file
         if use_become {
             ssh_client.write_file_with_sudo(
@@ -510,32 +517,7 @@ fn set_file_ownership(path: &str, owner: Option<&str>, group: Option<&str>) -> R
 pub fn execute_adhoc(host: &Host, args: &Value) -> Result<ModuleResult> {
     if host.hostname == "localhost" || host.hostname == "127.0.0.1" {
         // For localhost, execute directly without SSH
-        let path = get_param::<String>(args, "path")?;
-        let line = get_optional_param::<String>(args, "line");
-        let regexp = get_optional_param::<String>(args, "regexp");
-        let state =
-            get_optional_param::<String>(args, "state").unwrap_or_else(|| "present".to_string());
-        let backup = get_optional_param::<bool>(args, "backup").unwrap_or(false);
-        let create = get_optional_param::<bool>(args, "create").unwrap_or(false);
-        let insertafter = get_optional_param::<String>(args, "insertafter");
-        let insertbefore = get_optional_param::<String>(args, "insertbefore");
-        let owner = get_optional_param::<String>(args, "owner");
-        let group = get_optional_param::<String>(args, "group");
-        let mode = get_optional_param::<String>(args, "mode");
-
-        return execute_local(
-            &path,
-            line,
-            regexp,
-            &state,
-            backup,
-            create,
-            insertafter,
-            insertbefore,
-            owner,
-            group,
-            mode,
-        );
+        return execute_local(LineInFileOptions::from_args(args)?);
     }
 
     info!("Connecting to host: {}", host.name);
@@ -548,6 +530,33 @@ mod tests {
     use super::*;
     use serde_yaml::{Mapping, Value};
 
+    #[test]
+    fn test_execute_local_check_mode_leaves_file_untouched() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+        fs::write(&path, "line1\n").unwrap();
+
+        let result = execute_local(LineInFileOptions {
+            path: path.clone(),
+            line: Some("line2".to_string()),
+            regexp: None,
+            state: "present".to_string(),
+            backup: true,
+            create: false,
+            insertafter: None,
+            insertbefore: None,
+            owner: None,
+            group: None,
+            mode: None,
+            check_mode: true,
+        })
+        .unwrap();
+
+        assert!(result.changed);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line1\n");
+        assert!(!std::path::Path::new(&format!("{}.backup", path)).exists());
+    }
+
     #[test]
     fn test_process_line_modifications_present() {
         let mut content = "line1\nline2\nline3\n".to_string();
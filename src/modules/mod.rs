@@ -1,3 +1,4 @@
+pub mod check;
 pub mod command;
 pub mod copy;
 pub mod debug;
@@ -8,6 +9,7 @@ pub mod package;
 pub mod param;
 pub mod remote;
 pub mod service;
+pub mod setup;
 pub mod shell;
 pub mod template;
 pub mod user;
@@ -15,7 +17,7 @@ pub mod user;
 use anyhow::Result;
 use colored::Colorize;
 use log::info;
-use serde_yaml::Value;
+use serde_yaml::{Mapping, Value};
 use std::collections::HashMap;
 use std::time::Instant;
 
@@ -32,6 +34,20 @@ pub struct ModuleResult {
     pub msg: String,
 }
 
+/// Private argument injected by the playbook runner when a task runs in check mode
+pub const CHECK_MODE_ARG: &str = "_check_mode";
+
+/// Whether the module arguments ask for a dry run instead of applying changes
+pub fn is_check_mode(args: &Value) -> bool {
+    match args {
+        Value::Mapping(map) => matches!(
+            map.get(Value::String(CHECK_MODE_ARG.to_string())),
+            Some(Value::Bool(true))
+        ),
+        _ => false,
+    }
+}
+
 /// Trait for common module execution patterns
 pub trait ModuleExecutor {
     /// Execute the module with the given SSH client and arguments
@@ -115,8 +131,13 @@ pub trait ModuleExecutor {
     }
 }
 
-/// Run an ad-hoc command on a list of hosts
-pub fn run_adhoc(hosts: &[Host], module_name: &str, args: &str) -> Result<()> {
+/// Run an ad-hoc command on a list of hosts; `extra_vars` come from `-e/--extra-vars`
+pub fn run_adhoc(
+    hosts: &[Host],
+    module_name: &str,
+    args: &str,
+    extra_vars: &Mapping,
+) -> Result<()> {
     info!(
         "Running ad-hoc module '{}' on {} hosts",
         module_name,
@@ -134,6 +155,16 @@ pub fn run_adhoc(hosts: &[Host], module_name: &str, args: &str) -> Result<()> {
         info!("Running module {} on host {}", module_name, host.name);
         let start_time = Instant::now();
 
+        let args = match adhoc_args(host, args, extra_vars) {
+            Ok(args) => args,
+            Err(e) => {
+                failed_hosts.push(host.name.clone());
+                println!("{} | {} | rc=1 >>>\n{}", host.name.red(), "FAILED".red(), e);
+                continue;
+            }
+        };
+        let args = args.as_str();
+
         let result = match module_name {
             "command" => {
                 let value = Value::String(args.to_string());
@@ -191,6 +222,12 @@ pub fn run_adhoc(hosts: &[Host], module_name: &str, args: &str) -> Result<()> {
                 let value = Value::Mapping(params);
                 user::execute_adhoc(host, &value)
             }
+            "setup" => {
+                // Parse args in the format "gather_subset=network"
+                let params = parse_args(args)?;
+                let value = Value::Mapping(params);
+                setup::execute_adhoc(host, &value)
+            }
             _ => {
                 return Err(anyhow::anyhow!("Unsupported module: {}", module_name));
             }
@@ -272,6 +309,29 @@ pub fn run_adhoc(hosts: &[Host], module_name: &str, args: &str) -> Result<()> {
     Ok(())
 }
 
+/// Template the ad-hoc arguments with the host's inventory variables and the extra vars
+fn adhoc_args(host: &Host, args: &str, extra_vars: &Mapping) -> Result<String> {
+    if !args.contains("{{") {
+        return Ok(args.to_string());
+    }
+
+    let mut vars: HashMap<String, Value> = HashMap::new();
+    for (key, value) in host.inherited_variables.iter().chain(&host.variables) {
+        vars.insert(key.clone(), value.clone());
+    }
+    vars.insert(
+        "inventory_hostname".to_string(),
+        Value::String(host.name.clone()),
+    );
+    for (key, value) in extra_vars {
+        if let Value::String(key) = key {
+            vars.insert(key.clone(), value.clone());
+        }
+    }
+
+    crate::playbook::render_string(args, &vars)
+}
+
 /// Parse command line arguments in format "key1=value1 key2=value2"
 fn parse_args(args_str: &str) -> Result<serde_yaml::Mapping> {
     let mut mapping = serde_yaml::Mapping::new();
@@ -313,6 +373,16 @@ mod tests {
         assert_eq!(result.msg, "");
     }
 
+    #[test]
+    fn test_is_check_mode() {
+        let mut map = serde_yaml::Mapping::new();
+        assert!(!is_check_mode(&Value::Mapping(map.clone())));
+
+        map.insert(Value::String(CHECK_MODE_ARG.to_string()), Value::Bool(true));
+        assert!(is_check_mode(&Value::Mapping(map)));
+        assert!(!is_check_mode(&Value::String("x".to_string())));
+    }
+
     #[test]
     fn test_parse_args() {
         // Test normal parameter parsing
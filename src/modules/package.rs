@@ -91,12 +91,15 @@ pub fn execute(
         _ => false, // Default to false if not specified
     };
 
+    let check_mode = crate::modules::is_check_mode(args);
+    let mut pending_packages = Vec::new();
+
     // Detect package manager
     let pkg_manager = detect_package_manager(ssh_client, use_become, become_user)?;
     info!("Detected package manager: {:?}", pkg_manager);
 
-    // Update package cache if requested
-    if update_cache {
+    // Update package cache if requested (a dry run must not refresh it)
+    if update_cache && !check_mode {
         let update_cmd = match pkg_manager {
             PackageManager::Apt => "apt-get update",
             PackageManager::Yum => "yum check-update || true", // yum check-update returns 100 if updates are available
@@ -206,6 +209,11 @@ pub fn execute(
             continue;
         }
 
+        if check_mode {
+            pending_packages.push(package_name);
+            continue;
+        }
+
         // Run the command with privilege escalation (always needed for package operations)
         let result = if use_become {
             ssh_client.execute_sudo_command(&command, become_user)?
@@ -235,6 +243,22 @@ pub fn execute(
 
         info!("Package '{}' is now in state '{}'", package_name, state_str);
     }
+
+    if check_mode {
+        return Ok(ModuleResult {
+            changed: !pending_packages.is_empty(),
+            msg: if pending_packages.is_empty() {
+                format!("Package(s) {} already {}", packages.join(", "), state_str)
+            } else {
+                format!(
+                    "Package(s) {} would be changed to {}",
+                    pending_packages.join(", "),
+                    state_str
+                )
+            },
+            ..Default::default()
+        });
+    }
     let state_str = match state {
         PackageState::Present => "installed",
         PackageState::Absent => "removed",
@@ -259,46 +283,7 @@ pub fn execute_adhoc(host: &Host, package_args: &Value) -> Result<ModuleResult>
     info!("Connecting to host: {}", host.name);
     let ssh_client = SshClient::connect(host)?;
 
-    // 执行包操作
-    execute(&ssh_client, package_args, false, "")?;
-
-    // 获取包名和状态用于输出消息
-    let map = match package_args {
-        Value::Mapping(map) => map,
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Package module requires a mapping of arguments"
-            ))
-        }
-    };
-
-    // 尝试构建信息消息，包括包名和状态
-    let package_info = match map.get(&Value::String("name".to_string())) {
-        Some(Value::String(name)) => name.clone(),
-        Some(Value::Sequence(names)) => {
-            let mut package_names = Vec::new();
-            for name_value in names {
-                if let Value::String(name) = name_value {
-                    package_names.push(name.clone());
-                }
-            }
-            package_names.join(", ")
-        }
-        _ => "packages".to_string(),
-    };
-
-    let state = match map.get(&Value::String("state".to_string())) {
-        Some(Value::String(state)) => state.clone(),
-        _ => "present".to_string(),
-    };
-
-    Ok(ModuleResult {
-        stdout: String::new(),
-        stderr: String::new(),
-        changed: true,
-        failed: false,
-        msg: format!("Package(s) {} state changed to {}", package_info, state),
-    })
+    execute(&ssh_client, package_args, false, "")
 }
 
 /// Detect the package manager used by the remote host
@@ -3,6 +3,7 @@ use log::{error, info, warn};
 use serde_yaml::Value;
 
 use crate::inventory::Host;
+use crate::modules::check::shell_quote;
 use crate::modules::ModuleResult;
 use crate::ssh::connection::SshClient;
 
@@ -69,6 +70,44 @@ pub fn execute(
     let init_system = detect_init_system(ssh_client, use_become, become_user)?;
     info!("Detected init system: {}", init_system);
 
+    if crate::modules::is_check_mode(args) {
+        let status_cmd = match init_system.as_str() {
+            "systemd" => format!("systemctl is-active {}", shell_quote(name)),
+            "sysvinit" => format!("service {} status", shell_quote(name)),
+            "upstart" => format!("initctl status {}", shell_quote(name)),
+            _ => return Err(anyhow::anyhow!("Unsupported init system: {}", init_system)),
+        };
+        let (exit_code, stdout, _) = if use_become {
+            ssh_client.execute_sudo_command(&status_cmd, become_user)?
+        } else {
+            ssh_client.execute_command(&status_cmd)?
+        };
+        let running = if init_system == "upstart" {
+            stdout.contains("start/running")
+        } else {
+            exit_code == 0
+        };
+        let changed = match state {
+            ServiceState::Started => !running,
+            ServiceState::Stopped => running,
+            ServiceState::Restarted | ServiceState::Reloaded => true,
+        };
+
+        return Ok(ModuleResult {
+            changed,
+            msg: if changed {
+                format!("Service {} would be {}", name, get_state_past_tense(&state))
+            } else {
+                format!(
+                    "Service {} is already {}",
+                    name,
+                    get_state_past_tense(&state)
+                )
+            },
+            ..Default::default()
+        });
+    }
+
     // Build the command based on the detected init system
     let command = match (init_system.as_str(), &state) {
         ("systemd", ServiceState::Started) => format!("systemctl start {}", name),
@@ -137,18 +176,10 @@ pub fn execute_adhoc(host: &Host, service_args: &Value) -> Result<ModuleResult>
     info!("Connecting to host: {}", host.name);
     let ssh_client = SshClient::connect(host)?;
 
-    let service_name = get_param(service_args, "name")?;
-    let state = get_param(service_args, "state").unwrap_or_else(|_| "started".to_string());
+    let service_name: String = get_param(service_args, "name")?;
+    info!("Managing service {} on {}", service_name, host.name);
 
-    execute(&ssh_client, service_args, false, "")?;
-
-    Ok(ModuleResult {
-        stdout: String::new(),
-        stderr: String::new(),
-        changed: true,
-        failed: false,
-        msg: format!("Service {} state changed to {}", service_name, state),
-    })
+    execute(&ssh_client, service_args, false, "")
 }
 
 /// Detect the init system used by the remote host
@@ -0,0 +1,773 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::inventory::Host;
+use crate::modules::ModuleResult;
+use crate::ssh::connection::SshClient;
+
+/// Prefix of the marker lines separating the sections of the probe script output
+const SECTION_MARKER: &str = "@@rustsible_section:";
+
+/// Runs a shell command on the target and returns its exit code, stdout and stderr
+pub type CommandRunner<'a> = dyn Fn(&str) -> Result<(i32, String, String)> + 'a;
+
+/// Groups of facts that can be selected with `gather_subset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FactSubset {
+    Min,
+    Hardware,
+    Network,
+}
+
+impl FactSubset {
+    fn name(&self) -> &'static str {
+        match self {
+            FactSubset::Min => "min",
+            FactSubset::Hardware => "hardware",
+            FactSubset::Network => "network",
+        }
+    }
+
+    /// Shell commands whose output is parsed into this subset's facts
+    fn sections(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            FactSubset::Min => &[
+                ("hostname", "hostname 2>/dev/null || uname -n"),
+                ("fqdn", "hostname -f 2>/dev/null || hostname 2>/dev/null"),
+                ("uname", "uname -s; uname -r; uname -m; uname -v"),
+                (
+                    "os_release",
+                    "cat /etc/os-release 2>/dev/null || cat /usr/lib/os-release 2>/dev/null || sw_vers 2>/dev/null",
+                ),
+                (
+                    "date",
+                    "date +'%Y|%m|%d|%H|%M|%S|%s|%Z|%z|%A|%a|%w|%W'; date -u +'%Y-%m-%dT%H:%M:%SZ'",
+                ),
+                ("user", "id -un; id -u; id -g; echo \"$HOME\"; echo \"$SHELL\""),
+            ],
+            FactSubset::Hardware => &[
+                ("nproc", "nproc 2>/dev/null || getconf _NPROCESSORS_ONLN 2>/dev/null"),
+                (
+                    "cpuinfo",
+                    "grep -E '^(model name|physical id|cpu cores)' /proc/cpuinfo 2>/dev/null",
+                ),
+                ("meminfo", "cat /proc/meminfo 2>/dev/null"),
+                ("proc_mounts", "cat /proc/mounts 2>/dev/null"),
+                ("df", "df -P -k 2>/dev/null"),
+                ("uptime", "cat /proc/uptime 2>/dev/null"),
+            ],
+            FactSubset::Network => &[
+                ("ip_addr", "ip -o addr show 2>/dev/null"),
+                ("ip_link", "ip -o link show 2>/dev/null"),
+                ("ip_route", "ip -4 route show default 2>/dev/null"),
+            ],
+        }
+    }
+}
+
+/// Resolve a `gather_subset` value (string, comma separated string or list) into subsets.
+/// `min` is always collected; `!name` excludes a subset and `!all` leaves only `min`.
+pub fn resolve_subsets(spec: Option<&Value>) -> Result<BTreeSet<FactSubset>> {
+    let entries: Vec<String> = match spec {
+        None | Some(Value::Null) => vec!["all".to_string()],
+        Some(Value::String(s)) => s.split(',').map(|e| e.trim().to_string()).collect(),
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => Ok(s.trim().to_string()),
+                _ => Err(anyhow!("gather_subset entries must be strings")),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => return Err(anyhow!("gather_subset must be a string or a list")),
+    };
+
+    let all = [FactSubset::Min, FactSubset::Hardware, FactSubset::Network];
+    let lookup = |name: &str| -> Result<Vec<FactSubset>> {
+        match name {
+            "all" => Ok(all.to_vec()),
+            "min" => Ok(vec![FactSubset::Min]),
+            "hardware" => Ok(vec![FactSubset::Hardware]),
+            "network" => Ok(vec![FactSubset::Network]),
+            other => Err(anyhow!("Unsupported gather_subset entry: {}", other)),
+        }
+    };
+
+    let mut included = BTreeSet::new();
+    let mut excluded = BTreeSet::new();
+    let mut only_exclusions = true;
+    for entry in entries.iter().filter(|e| !e.is_empty()) {
+        if let Some(name) = entry.strip_prefix('!') {
+            excluded.extend(lookup(name)?);
+        } else {
+            only_exclusions = false;
+            included.extend(lookup(entry)?);
+        }
+    }
+
+    // Like Ansible, a list of exclusions only ("!hardware") starts from everything
+    if only_exclusions {
+        included.extend(all);
+    }
+    included.retain(|subset| !excluded.contains(subset));
+    included.insert(FactSubset::Min);
+    Ok(included)
+}
+
+/// Collect facts by running a probe script through `run`, which executes a shell command on the target
+pub fn gather_facts(run: &CommandRunner, args: &Value) -> Result<Mapping> {
+    let subset_spec = match args {
+        Value::Mapping(map) => map.get(Value::String("gather_subset".to_string())),
+        _ => None,
+    };
+    let subsets = resolve_subsets(subset_spec)?;
+
+    // One round trip for everything: each section is announced by a marker line
+    let script = subsets
+        .iter()
+        .flat_map(|subset| subset.sections().iter())
+        .map(|(name, cmd)| format!("echo '{}{}'; {{ {}; }}", SECTION_MARKER, name, cmd))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let (exit_code, stdout, stderr) = run(&script)?;
+    if exit_code != 0 && stdout.trim().is_empty() {
+        return Err(anyhow!("Failed to gather facts: {}", stderr.trim()));
+    }
+
+    let sections = split_sections(&stdout);
+    debug!("Collected fact sections: {:?}", sections.keys());
+    Ok(build_facts(&sections, &subsets))
+}
+
+/// Execute the setup module over SSH; the facts are returned as YAML in stdout
+pub fn execute(
+    ssh_client: &SshClient,
+    args: &Value,
+    _use_become: bool,
+    _become_user: &str,
+) -> Result<ModuleResult> {
+    // Facts are read-only, so they never need privilege escalation
+    let facts = gather_facts(&|cmd| ssh_client.execute_command(cmd), args)?;
+    facts_result(facts)
+}
+
+/// Execute the setup module in ad-hoc mode for a single host.
+pub fn execute_adhoc(host: &Host, args: &Value) -> Result<ModuleResult> {
+    if host.hostname == "localhost" || host.hostname == "127.0.0.1" {
+        let facts = gather_facts(&crate::modules::local::execute_local_command, args)?;
+        return facts_result(facts);
+    }
+
+    info!("Connecting to host: {}", host.name);
+    let ssh_client = SshClient::connect(host)?;
+    execute(&ssh_client, args, false, "")
+}
+
+fn facts_result(facts: Mapping) -> Result<ModuleResult> {
+    let count = facts.len();
+    let mut wrapped = Mapping::new();
+    wrapped.insert(
+        Value::String("ansible_facts".to_string()),
+        Value::Mapping(facts),
+    );
+
+    Ok(ModuleResult {
+        stdout: serde_yaml::to_string(&wrapped)?,
+        msg: format!("Gathered {} facts", count),
+        ..Default::default()
+    })
+}
+
+/// Split the probe output into its named sections
+fn split_sections(output: &str) -> HashMap<String, String> {
+    let mut sections: HashMap<String, String> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix(SECTION_MARKER) {
+            current = Some(name.trim().to_string());
+            sections.entry(name.trim().to_string()).or_default();
+        } else if let Some(name) = &current {
+            let section = sections.entry(name.clone()).or_default();
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+
+    sections
+}
+
+fn build_facts(sections: &HashMap<String, String>, subsets: &BTreeSet<FactSubset>) -> Mapping {
+    let section = |name: &str| sections.get(name).map(String::as_str).unwrap_or("");
+    let mut facts = Mapping::new();
+
+    facts.insert(
+        str_value("gather_subset"),
+        Value::Sequence(subsets.iter().map(|s| str_value(s.name())).collect()),
+    );
+
+    if subsets.contains(&FactSubset::Min) {
+        let hostname = section("hostname").trim().to_string();
+        let short_hostname = hostname.split('.').next().unwrap_or("").to_string();
+        insert_str(&mut facts, "nodename", &hostname);
+        insert_str(&mut facts, "hostname", &short_hostname);
+        insert_str(&mut facts, "fqdn", section("fqdn").trim());
+
+        let uname: Vec<&str> = section("uname").lines().collect();
+        let system = uname.first().copied().unwrap_or("").trim();
+        insert_str(&mut facts, "system", system);
+        insert_str(
+            &mut facts,
+            "kernel",
+            uname.get(1).copied().unwrap_or("").trim(),
+        );
+        insert_str(
+            &mut facts,
+            "architecture",
+            uname.get(2).copied().unwrap_or("").trim(),
+        );
+        insert_str(
+            &mut facts,
+            "kernel_version",
+            uname.get(3).copied().unwrap_or("").trim(),
+        );
+
+        for (key, value) in parse_distribution(system, section("os_release")) {
+            insert_str(&mut facts, key, &value);
+        }
+
+        if let Some(date_time) = parse_date_time(section("date")) {
+            facts.insert(str_value("date_time"), Value::Mapping(date_time));
+        }
+
+        let user: Vec<&str> = section("user").lines().collect();
+        for (idx, key) in ["user_id", "user_uid", "user_gid", "user_dir", "user_shell"]
+            .iter()
+            .enumerate()
+        {
+            insert_str(&mut facts, key, user.get(idx).copied().unwrap_or("").trim());
+        }
+    }
+
+    if subsets.contains(&FactSubset::Hardware) {
+        facts.extend(parse_cpu(section("nproc"), section("cpuinfo")));
+        facts.extend(parse_meminfo(section("meminfo")));
+        facts.insert(
+            str_value("mounts"),
+            parse_mounts(section("proc_mounts"), section("df")),
+        );
+        if let Some(uptime) = section("uptime")
+            .split_whitespace()
+            .next()
+            .and_then(|s| s.parse::<f64>().ok())
+        {
+            facts.insert(
+                str_value("uptime_seconds"),
+                Value::Number((uptime as u64).into()),
+            );
+        }
+    }
+
+    if subsets.contains(&FactSubset::Network) {
+        facts.extend(parse_network(
+            section("ip_addr"),
+            section("ip_link"),
+            section("ip_route"),
+        ));
+    }
+
+    facts
+}
+
+/// Derive distribution facts from /etc/os-release (or `sw_vers` on macOS)
+fn parse_distribution(system: &str, os_release: &str) -> Vec<(&'static str, String)> {
+    let fields: HashMap<&str, String> = os_release
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim().trim_matches('"').to_string()))
+        .collect();
+
+    if system == "Darwin" {
+        let version = os_release
+            .lines()
+            .find_map(|line| line.strip_prefix("ProductVersion:"))
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        return vec![
+            ("distribution", "MacOSX".to_string()),
+            ("os_family", "Darwin".to_string()),
+            (
+                "distribution_major_version",
+                version.split('.').next().unwrap_or("").to_string(),
+            ),
+            ("distribution_version", version),
+            ("distribution_release", String::new()),
+        ];
+    }
+
+    let id = fields.get("ID").cloned().unwrap_or_default().to_lowercase();
+    let id_like = fields
+        .get("ID_LIKE")
+        .cloned()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let distribution = match id.as_str() {
+        "ubuntu" => "Ubuntu".to_string(),
+        "debian" => "Debian".to_string(),
+        "linuxmint" => "Linux Mint".to_string(),
+        "centos" => "CentOS".to_string(),
+        "rhel" => "RedHat".to_string(),
+        "fedora" => "Fedora".to_string(),
+        "rocky" => "Rocky".to_string(),
+        "almalinux" => "AlmaLinux".to_string(),
+        "ol" => "OracleLinux".to_string(),
+        "amzn" => "Amazon".to_string(),
+        "sles" => "SLES".to_string(),
+        "opensuse-leap" => "openSUSE Leap".to_string(),
+        "opensuse-tumbleweed" => "openSUSE Tumbleweed".to_string(),
+        "arch" => "Archlinux".to_string(),
+        "alpine" => "Alpine".to_string(),
+        "" => system.to_string(),
+        _ => fields.get("NAME").cloned().unwrap_or_else(|| id.clone()),
+    };
+
+    let family_of = |name: &str| -> Option<&'static str> {
+        match name {
+            "debian" | "ubuntu" | "linuxmint" | "raspbian" | "pop" | "kali" => Some("Debian"),
+            "rhel" | "centos" | "fedora" | "rocky" | "almalinux" | "ol" | "amzn" | "scientific"
+            | "cloudlinux" => Some("RedHat"),
+            "sles" | "sled" | "suse" | "opensuse" | "opensuse-leap" | "opensuse-tumbleweed" => {
+                Some("Suse")
+            }
+            "arch" | "manjaro" => Some("Archlinux"),
+            "alpine" => Some("Alpine"),
+            "gentoo" => Some("Gentoo"),
+            _ => None,
+        }
+    };
+    let os_family = family_of(&id)
+        .or_else(|| id_like.split_whitespace().find_map(family_of))
+        .map(str::to_string)
+        .unwrap_or_else(|| distribution.clone());
+
+    let version = fields.get("VERSION_ID").cloned().unwrap_or_default();
+    vec![
+        ("distribution", distribution),
+        ("os_family", os_family),
+        (
+            "distribution_major_version",
+            version.split('.').next().unwrap_or("").to_string(),
+        ),
+        ("distribution_version", version),
+        (
+            "distribution_release",
+            fields.get("VERSION_CODENAME").cloned().unwrap_or_default(),
+        ),
+    ]
+}
+
+/// Parse the two `date` lines of the probe into `ansible_date_time`
+fn parse_date_time(output: &str) -> Option<Mapping> {
+    let mut lines = output.lines();
+    let fields: Vec<&str> = lines.next()?.trim().split('|').collect();
+    if fields.len() != 13 {
+        return None;
+    }
+
+    let keys = [
+        "year",
+        "month",
+        "day",
+        "hour",
+        "minute",
+        "second",
+        "epoch",
+        "tz",
+        "tz_offset",
+        "weekday",
+        "weekday_short",
+        "weekday_number",
+        "weeknumber",
+    ];
+    let mut date_time = Mapping::new();
+    for (key, value) in keys.iter().zip(fields.iter()) {
+        insert_str(&mut date_time, key, value);
+    }
+    insert_str(
+        &mut date_time,
+        "date",
+        &format!("{}-{}-{}", fields[0], fields[1], fields[2]),
+    );
+    insert_str(
+        &mut date_time,
+        "time",
+        &format!("{}:{}:{}", fields[3], fields[4], fields[5]),
+    );
+    if let Some(iso8601) = lines.next() {
+        insert_str(&mut date_time, "iso8601", iso8601.trim());
+    }
+
+    Some(date_time)
+}
+
+fn parse_cpu(nproc: &str, cpuinfo: &str) -> Mapping {
+    let mut facts = Mapping::new();
+    let mut models = Vec::new();
+    let mut physical_ids = BTreeSet::new();
+    let mut cores = None;
+
+    for line in cpuinfo.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key.trim() {
+            "model name" => models.push(str_value(value.trim())),
+            "physical id" => {
+                physical_ids.insert(value.trim().to_string());
+            }
+            "cpu cores" => cores = cores.or_else(|| value.trim().parse::<u64>().ok()),
+            _ => {}
+        }
+    }
+
+    let vcpus = nproc.trim().parse::<u64>().unwrap_or(models.len() as u64);
+    facts.insert(str_value("processor"), Value::Sequence(models));
+    facts.insert(
+        str_value("processor_count"),
+        Value::Number((physical_ids.len().max(1) as u64).into()),
+    );
+    facts.insert(
+        str_value("processor_cores"),
+        Value::Number(cores.unwrap_or(vcpus).into()),
+    );
+    facts.insert(str_value("processor_vcpus"), Value::Number(vcpus.into()));
+    facts
+}
+
+fn parse_meminfo(meminfo: &str) -> Mapping {
+    let kb: HashMap<&str, u64> = meminfo
+        .lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let value = rest.split_whitespace().next()?.parse().ok()?;
+            Some((key.trim(), value))
+        })
+        .collect();
+    let mb = |key: &str| kb.get(key).copied().unwrap_or(0) / 1024;
+
+    let mut facts = Mapping::new();
+    for (fact, key) in [
+        ("memtotal_mb", "MemTotal"),
+        ("memfree_mb", "MemFree"),
+        ("swaptotal_mb", "SwapTotal"),
+        ("swapfree_mb", "SwapFree"),
+    ] {
+        facts.insert(str_value(fact), Value::Number(mb(key).into()));
+    }
+    facts
+}
+
+/// Combine /proc/mounts with `df -P -k` sizes; only block-device backed filesystems are reported
+fn parse_mounts(proc_mounts: &str, df: &str) -> Value {
+    let sizes: HashMap<&str, (u64, u64)> = df
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 6 {
+                return None;
+            }
+            let total = cols[1].parse::<u64>().ok()? * 1024;
+            let available = cols[3].parse::<u64>().ok()? * 1024;
+            Some((cols[5], (total, available)))
+        })
+        .collect();
+
+    let mounts = proc_mounts
+        .lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 4 || !cols[0].starts_with('/') {
+                return None;
+            }
+            let mut mount = Mapping::new();
+            insert_str(&mut mount, "device", cols[0]);
+            insert_str(&mut mount, "mount", cols[1]);
+            insert_str(&mut mount, "fstype", cols[2]);
+            insert_str(&mut mount, "options", cols[3]);
+            if let Some((total, available)) = sizes.get(cols[1]) {
+                mount.insert(str_value("size_total"), Value::Number((*total).into()));
+                mount.insert(
+                    str_value("size_available"),
+                    Value::Number((*available).into()),
+                );
+            }
+            Some(Value::Mapping(mount))
+        })
+        .collect();
+
+    Value::Sequence(mounts)
+}
+
+/// Parse `ip -o addr`, `ip -o link` and the default route into interface facts
+fn parse_network(ip_addr: &str, ip_link: &str, ip_route: &str) -> Mapping {
+    let mut interfaces: Vec<String> = Vec::new();
+    let mut per_interface: HashMap<String, Mapping> = HashMap::new();
+    let mut all_ipv4 = Vec::new();
+    let mut all_ipv6 = Vec::new();
+
+    // "2: eth0: <BROADCAST,...> mtu 1500 ... link/ether 52:54:00:12:34:56 brd ..."
+    for line in ip_link.lines() {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let Some(name) = cols.get(1).map(|n| n.trim_end_matches(':')) else {
+            continue;
+        };
+        let name = name.split('@').next().unwrap_or(name).to_string();
+        let entry = per_interface.entry(name.clone()).or_default();
+        insert_str(entry, "device", &name);
+        if let Some(pos) = cols.iter().position(|c| c.starts_with("link/")) {
+            if let Some(mac) = cols.get(pos + 1) {
+                insert_str(entry, "macaddress", mac);
+            }
+        }
+        if let Some(pos) = cols.iter().position(|c| *c == "mtu") {
+            if let Some(mtu) = cols.get(pos + 1).and_then(|m| m.parse::<u64>().ok()) {
+                entry.insert(str_value("mtu"), Value::Number(mtu.into()));
+            }
+        }
+        if !interfaces.contains(&name) {
+            interfaces.push(name);
+        }
+    }
+
+    // "2: eth0    inet 10.0.0.5/24 brd 10.0.0.255 scope global eth0"
+    for line in ip_addr.lines() {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 4 {
+            continue;
+        }
+        let name = cols[1].trim_end_matches(':').to_string();
+        let (address, prefix) = cols[3].split_once('/').unwrap_or((cols[3], ""));
+        let mut addr = Mapping::new();
+        insert_str(&mut addr, "address", address);
+        insert_str(&mut addr, "prefix", prefix);
+
+        let entry = per_interface.entry(name.clone()).or_default();
+        insert_str(entry, "device", &name);
+        match cols[2] {
+            "inet" => {
+                if !entry.contains_key(str_value("ipv4")) {
+                    entry.insert(str_value("ipv4"), Value::Mapping(addr));
+                }
+                if !address.starts_with("127.") {
+                    all_ipv4.push(str_value(address));
+                }
+            }
+            "inet6" => {
+                let list = entry
+                    .entry(str_value("ipv6"))
+                    .or_insert_with(|| Value::Sequence(Vec::new()));
+                if let Value::Sequence(list) = list {
+                    list.push(Value::Mapping(addr));
+                }
+                if address != "::1" {
+                    all_ipv6.push(str_value(address));
+                }
+            }
+            _ => continue,
+        }
+        if !interfaces.contains(&name) {
+            interfaces.push(name);
+        }
+    }
+
+    let mut facts = Mapping::new();
+
+    // "default via 10.0.0.1 dev eth0 proto dhcp ..."
+    let mut default_ipv4 = Mapping::new();
+    if let Some(line) = ip_route.lines().find(|l| l.starts_with("default")) {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let after = |word: &str| {
+            cols.iter()
+                .position(|c| *c == word)
+                .and_then(|pos| cols.get(pos + 1))
+        };
+        if let Some(gateway) = after("via") {
+            insert_str(&mut default_ipv4, "gateway", gateway);
+        }
+        if let Some(dev) = after("dev") {
+            insert_str(&mut default_ipv4, "interface", dev);
+            if let Some(Value::Mapping(ipv4)) = per_interface
+                .get(*dev)
+                .and_then(|iface| iface.get(str_value("ipv4")))
+            {
+                for (key, value) in ipv4 {
+                    default_ipv4.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+    facts.insert(str_value("default_ipv4"), Value::Mapping(default_ipv4));
+
+    facts.insert(
+        str_value("interfaces"),
+        Value::Sequence(interfaces.iter().map(|name| str_value(name)).collect()),
+    );
+    facts.insert(str_value("all_ipv4_addresses"), Value::Sequence(all_ipv4));
+    facts.insert(str_value("all_ipv6_addresses"), Value::Sequence(all_ipv6));
+
+    // Each interface is also exposed under its own name, with dashes made variable-safe
+    for name in &interfaces {
+        if let Some(entry) = per_interface.remove(name) {
+            facts.insert(
+                str_value(&name.replace(['-', '.'], "_")),
+                Value::Mapping(entry),
+            );
+        }
+    }
+
+    facts
+}
+
+fn str_value(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+fn insert_str(map: &mut Mapping, key: &str, value: &str) {
+    map.insert(str_value(key), str_value(value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get<'a>(map: &'a Mapping, key: &str) -> &'a Value {
+        map.get(str_value(key))
+            .unwrap_or_else(|| panic!("missing fact {}", key))
+    }
+
+    #[test]
+    fn test_resolve_subsets() {
+        let all = resolve_subsets(None).unwrap();
+        assert_eq!(all.len(), 3);
+
+        let min = resolve_subsets(Some(&str_value("!all"))).unwrap();
+        assert_eq!(min.into_iter().collect::<Vec<_>>(), vec![FactSubset::Min]);
+
+        let no_hw = resolve_subsets(Some(&str_value("!hardware"))).unwrap();
+        assert!(no_hw.contains(&FactSubset::Network));
+        assert!(!no_hw.contains(&FactSubset::Hardware));
+
+        let list = Value::Sequence(vec![str_value("network")]);
+        let network = resolve_subsets(Some(&list)).unwrap();
+        assert!(network.contains(&FactSubset::Min) && network.contains(&FactSubset::Network));
+        assert!(!network.contains(&FactSubset::Hardware));
+
+        assert!(resolve_subsets(Some(&str_value("bogus"))).is_err());
+    }
+
+    #[test]
+    fn test_parse_distribution() {
+        let ubuntu = "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nID=ubuntu\nID_LIKE=debian\nVERSION_CODENAME=jammy\n";
+        let facts: HashMap<_, _> = parse_distribution("Linux", ubuntu).into_iter().collect();
+        assert_eq!(facts["distribution"], "Ubuntu");
+        assert_eq!(facts["os_family"], "Debian");
+        assert_eq!(facts["distribution_version"], "22.04");
+        assert_eq!(facts["distribution_major_version"], "22");
+        assert_eq!(facts["distribution_release"], "jammy");
+
+        let derivative =
+            "NAME=\"Foo Linux\"\nID=foo\nID_LIKE=\"rhel centos fedora\"\nVERSION_ID=9.2\n";
+        let facts: HashMap<_, _> = parse_distribution("Linux", derivative)
+            .into_iter()
+            .collect();
+        assert_eq!(facts["distribution"], "Foo Linux");
+        assert_eq!(facts["os_family"], "RedHat");
+    }
+
+    #[test]
+    fn test_parse_date_time() {
+        let output =
+            "2024|03|05|14|07|09|1709647629|CET|+0100|Tuesday|Tue|2|10\n2024-03-05T13:07:09Z\n";
+        let date_time = parse_date_time(output).unwrap();
+        assert_eq!(get(&date_time, "date"), &str_value("2024-03-05"));
+        assert_eq!(get(&date_time, "time"), &str_value("14:07:09"));
+        assert_eq!(get(&date_time, "epoch"), &str_value("1709647629"));
+        assert_eq!(get(&date_time, "tz_offset"), &str_value("+0100"));
+        assert_eq!(
+            get(&date_time, "iso8601"),
+            &str_value("2024-03-05T13:07:09Z")
+        );
+
+        assert!(parse_date_time("garbage").is_none());
+    }
+
+    #[test]
+    fn test_parse_hardware() {
+        let cpuinfo = "model name\t: Intel Xeon\nphysical id\t: 0\ncpu cores\t: 2\nmodel name\t: Intel Xeon\nphysical id\t: 0\ncpu cores\t: 2\n";
+        let cpu = parse_cpu("2\n", cpuinfo);
+        assert_eq!(get(&cpu, "processor_vcpus"), &Value::Number(2.into()));
+        assert_eq!(get(&cpu, "processor_count"), &Value::Number(1.into()));
+
+        let mem = parse_meminfo("MemTotal:       2048000 kB\nMemFree:         512000 kB\n");
+        assert_eq!(get(&mem, "memtotal_mb"), &Value::Number(2000.into()));
+        assert_eq!(get(&mem, "swaptotal_mb"), &Value::Number(0.into()));
+
+        let proc_mounts = "/dev/sda1 / ext4 rw,relatime 0 0\nproc /proc proc rw 0 0\n";
+        let df = "Filesystem 1024-blocks Used Available Capacity Mounted on\n/dev/sda1 1000 400 600 40% /\n";
+        let Value::Sequence(mounts) = parse_mounts(proc_mounts, df) else {
+            panic!("mounts should be a list");
+        };
+        assert_eq!(mounts.len(), 1);
+        let Value::Mapping(root) = &mounts[0] else {
+            panic!("mount should be a mapping");
+        };
+        assert_eq!(get(root, "mount"), &str_value("/"));
+        assert_eq!(get(root, "size_total"), &Value::Number(1024000.into()));
+    }
+
+    #[test]
+    fn test_parse_network() {
+        let ip_link = "1: lo: <LOOPBACK,UP> mtu 65536 qdisc noqueue state UNKNOWN\\    link/loopback 00:00:00:00:00:00 brd 00:00:00:00:00:00\n2: eth0: <BROADCAST,UP> mtu 1500 qdisc fq state UP\\    link/ether 52:54:00:12:34:56 brd ff:ff:ff:ff:ff:ff\n";
+        let ip_addr = "1: lo    inet 127.0.0.1/8 scope host lo\n2: eth0    inet 10.0.0.5/24 brd 10.0.0.255 scope global eth0\n2: eth0    inet6 fe80::1/64 scope link\n";
+        let ip_route = "default via 10.0.0.1 dev eth0 proto dhcp metric 100\n";
+
+        let facts = parse_network(ip_addr, ip_link, ip_route);
+        assert_eq!(
+            get(&facts, "interfaces"),
+            &Value::Sequence(vec![str_value("lo"), str_value("eth0")])
+        );
+        assert_eq!(
+            get(&facts, "all_ipv4_addresses"),
+            &Value::Sequence(vec![str_value("10.0.0.5")])
+        );
+
+        let Value::Mapping(default_ipv4) = get(&facts, "default_ipv4") else {
+            panic!("default_ipv4 should be a mapping");
+        };
+        assert_eq!(get(default_ipv4, "address"), &str_value("10.0.0.5"));
+        assert_eq!(get(default_ipv4, "gateway"), &str_value("10.0.0.1"));
+
+        let Value::Mapping(eth0) = get(&facts, "eth0") else {
+            panic!("eth0 should be a mapping");
+        };
+        assert_eq!(get(eth0, "macaddress"), &str_value("52:54:00:12:34:56"));
+    }
+
+    #[test]
+    fn test_gather_facts_on_localhost() {
+        let args = Value::Mapping(Mapping::new());
+        let facts = gather_facts(&crate::modules::local::execute_local_command, &args).unwrap();
+
+        assert!(facts.contains_key(str_value("system")));
+        assert!(facts.contains_key(str_value("os_family")));
+        let Value::Mapping(date_time) = get(&facts, "date_time") else {
+            panic!("date_time should be a mapping");
+        };
+        assert!(date_time.contains_key(str_value("epoch")));
+    }
+}
@@ -9,7 +9,7 @@ use tera::{Context as TeraContext, Tera};
 
 use crate::inventory::Host;
 use crate::modules::param::{get_optional_param, get_param};
-use crate::modules::ModuleResult;
+use crate::modules::{check, is_check_mode, ModuleResult};
 use crate::playbook::filters::register_ansible_filters;
 use crate::ssh::connection::SshClient;
 
@@ -18,7 +18,7 @@ pub fn execute(
     ssh_client: &SshClient,
     template_args: &Value,
     use_become: bool,
-    _become_user: &str,
+    become_user: &str,
 ) -> Result<ModuleResult> {
     let dest = get_param::<String>(template_args, "dest")?;
 
@@ -207,6 +207,34 @@ pub fn execute(
             }
         })?;
 
+    if is_check_mode(template_args) {
+        let up_to_date = check::remote_content_matches(
+            ssh_client,
+            &dest,
+            &rendered_content,
+            use_become,
+            become_user,
+        )? && check::remote_attributes_match(
+            ssh_client,
+            &dest,
+            mode.as_deref(),
+            owner.as_deref(),
+            group.as_deref(),
+            use_become,
+            become_user,
+        )?;
+
+        return Ok(ModuleResult {
+            changed: !up_to_date,
+            msg: if up_to_date {
+                format!("{} is already up to date", dest)
+            } else {
+                format!("Template {} would be applied to {}", src_display, dest)
+            },
+            ..Default::default()
+        });
+    }
+
     info!(
         "Template rendered successfully, uploading to remote host{}",
         if use_become { " (with sudo)" } else { "" }
@@ -268,33 +296,7 @@ pub fn execute_adhoc(host: &Host, template_args: &Value) -> Result<ModuleResult>
     info!("Connecting to host: {}", host.name);
     let ssh_client = SshClient::connect(host)?;
 
-    // 解析目标文件路径
-    let dest_file = get_param::<String>(template_args, "dest")?;
-
-    // 检查是否使用 src 或 content
-    let src_display = if let Value::Mapping(args_map) = template_args {
-        if let Some(Value::String(_)) = args_map.get(&Value::String("content".to_string())) {
-            "<inline_template>".to_string()
-        } else if let Some(Value::String(src)) = args_map.get(&Value::String("src".to_string())) {
-            src.clone()
-        } else {
-            return Err(anyhow::anyhow!(
-                "Template requires either 'src' or 'content' parameter"
-            ));
-        }
-    } else {
-        return Err(anyhow::anyhow!("Template arguments must be a mapping"));
-    };
-
-    execute(&ssh_client, template_args, false, "")?;
-
-    Ok(ModuleResult {
-        stdout: String::new(),
-        stderr: String::new(),
-        changed: true,
-        failed: false,
-        msg: format!("Template {} applied to {}", src_display, dest_file),
-    })
+    execute(&ssh_client, template_args, false, "")
 }
 
 #[cfg(test)]
@@ -5,8 +5,8 @@ use std::process::Command;
 use uuid::Uuid;
 
 use crate::inventory::Host;
-use crate::modules::param::{get_optional_param, get_param};
-use crate::modules::ModuleResult;
+use crate::modules::param::{get_optional_param, get_param, has_param};
+use crate::modules::{is_check_mode, ModuleResult};
 use crate::ssh::connection::SshClient;
 
 /// Execute the user module logic: manage user accounts
@@ -48,6 +48,15 @@ pub fn execute(
         false
     };
 
+    if is_check_mode(args) {
+        let user_exists = if is_local {
+            check_user_exists_local(&name)?
+        } else {
+            check_user_exists_remote(ssh_client, &name, use_become)?
+        };
+        return predict_user_change(&name, &state, user_exists, args);
+    }
+
     if is_local {
         execute_local(
             &name,
@@ -287,6 +296,47 @@ fn execute_remote(
     })
 }
 
+/// Predict the outcome of the user module for check mode, mirroring the apply logic
+fn predict_user_change(
+    name: &str,
+    state: &str,
+    user_exists: bool,
+    args: &Value,
+) -> Result<ModuleResult> {
+    let msg = match state {
+        "present" if !user_exists => Some(format!("User {} would be created", name)),
+        "present" => {
+            // usermod, the password and the group membership are applied unconditionally
+            let modifies = ["uid", "gid", "home", "shell", "comment"]
+                .iter()
+                .any(|key| has_param(args, key));
+            if modifies {
+                Some(format!("User {} would be modified", name))
+            } else if has_param(args, "password") {
+                Some(format!("User {} password would be updated", name))
+            } else if has_param(args, "groups") {
+                Some(format!("User {} groups would be updated", name))
+            } else {
+                None
+            }
+        }
+        "absent" if user_exists => Some(format!("User {} would be removed", name)),
+        "absent" => None,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid state: {}. Must be 'present' or 'absent'",
+                state
+            ));
+        }
+    };
+
+    Ok(ModuleResult {
+        changed: msg.is_some(),
+        msg: msg.unwrap_or_else(|| format!("User {} is already {}", name, state)),
+        ..Default::default()
+    })
+}
+
 /// Check if user exists locally
 fn check_user_exists_local(name: &str) -> Result<bool> {
     let output = Command::new("id")
@@ -822,6 +872,10 @@ pub fn execute_adhoc(host: &Host, args: &Value) -> Result<ModuleResult> {
         let system = get_optional_param::<bool>(args, "system").unwrap_or(false);
         let remove = get_optional_param::<bool>(args, "remove").unwrap_or(false);
 
+        if is_check_mode(args) {
+            return predict_user_change(&name, &state, check_user_exists_local(&name)?, args);
+        }
+
         return execute_local(
             &name,
             &state,
@@ -861,6 +915,49 @@ mod tests {
         assert!(!result.unwrap());
     }
 
+    #[test]
+    fn test_predict_user_change() {
+        let mut map = Mapping::new();
+        map.insert(
+            Value::String("name".to_string()),
+            Value::String("testuser".to_string()),
+        );
+        let args = Value::Mapping(map.clone());
+
+        assert!(
+            predict_user_change("testuser", "present", false, &args)
+                .unwrap()
+                .changed
+        );
+        assert!(
+            !predict_user_change("testuser", "present", true, &args)
+                .unwrap()
+                .changed
+        );
+        assert!(
+            !predict_user_change("testuser", "absent", false, &args)
+                .unwrap()
+                .changed
+        );
+        assert!(
+            predict_user_change("testuser", "absent", true, &args)
+                .unwrap()
+                .changed
+        );
+        assert!(predict_user_change("testuser", "bogus", true, &args).is_err());
+
+        map.insert(
+            Value::String("shell".to_string()),
+            Value::String("/bin/bash".to_string()),
+        );
+        let args = Value::Mapping(map);
+        assert!(
+            predict_user_change("testuser", "present", true, &args)
+                .unwrap()
+                .changed
+        );
+    }
+
     #[test]
     fn test_user_module_params() {
         let mut map = Mapping::new();
@@ -0,0 +1,150 @@
+use serde_yaml::Value;
+
+use crate::playbook::Task;
+
+/// Tasks grouped under a `block` keyword with optional `rescue` and `always` sections
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+    pub block: Vec<Task>,
+    pub rescue: Vec<Task>, // Runs on hosts where a block task failed
+    pub always: Vec<Task>, // Runs on every host that entered the block
+}
+
+impl Block {
+    /// Apply the keywords set on the block itself to every task it contains
+    pub fn inherit_from(&mut self, parent: &Task) {
+        for child in self.tasks_mut() {
+            inherit(parent, child);
+        }
+    }
+
+    fn tasks_mut(&mut self) -> impl Iterator<Item = &mut Task> {
+        self.block
+            .iter_mut()
+            .chain(self.rescue.iter_mut())
+            .chain(self.always.iter_mut())
+    }
+}
+
+fn inherit(parent: &Task, child: &mut Task) {
+    // Both conditions must hold, the block's one is checked first
+    child.when = match (parent.when.clone(), child.when.take()) {
+        (None, own) => own,
+        (inherited, None) => inherited,
+        (Some(inherited), Some(own)) => {
+            let mut conditions = condition_list(inherited);
+            conditions.extend(condition_list(own));
+            Some(Value::Sequence(conditions))
+        }
+    };
+
+    if !child.is_become && parent.is_become {
+        child.is_become = true;
+        child.become_user = parent.become_user.clone();
+    }
+
+    for tag in &parent.tags {
+        if !child.tags.contains(tag) {
+            child.tags.push(tag.clone());
+        }
+    }
+
+    // Variables set on the task itself win over the block ones
+    for (vars, parent_vars) in [
+        (&mut child.vars, &parent.vars),
+        (&mut child.role_vars, &parent.role_vars),
+        (&mut child.role_params, &parent.role_params),
+        (&mut child.role_defaults, &parent.role_defaults),
+    ] {
+        for (key, value) in parent_vars {
+            if !vars.contains_key(key) {
+                vars.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    child.ignore_errors = child.ignore_errors.or(parent.ignore_errors);
+    child.check_mode = child.check_mode.or(parent.check_mode);
+
+    // Nested blocks pass the keywords on to their own children
+    if let Some(block) = child.block.as_mut() {
+        block.inherit_from(parent);
+    }
+}
+
+fn condition_list(condition: Value) -> Vec<Value> {
+    match condition {
+        Value::Sequence(conditions) => conditions,
+        other => vec![other],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml::Mapping;
+
+    #[test]
+    fn test_block_keywords_are_inherited() {
+        let mut parent = Task::new("block", "block", Mapping::new());
+        parent.when = Some(Value::String("deploy".to_string()));
+        parent.is_become = true;
+        parent.become_user = "app".to_string();
+        parent.tags = vec!["web".to_string()];
+        parent.ignore_errors = Some(true);
+        parent
+            .vars
+            .insert(Value::String("port".to_string()), Value::Number(80.into()));
+
+        let mut own = Task::new("own", "command", Mapping::new());
+        own.when = Some(Value::String("ready".to_string()));
+        own.vars.insert(
+            Value::String("port".to_string()),
+            Value::Number(8080.into()),
+        );
+
+        let mut inner = Task::new("inner", "command", Mapping::new());
+        inner.is_become = true;
+        inner.ignore_errors = Some(false);
+        inner.become_user = "root".to_string();
+        let mut nested = Task::new("nested", "block", Mapping::new());
+        nested.block = Some(Block {
+            block: vec![inner],
+            ..Default::default()
+        });
+
+        let mut block = Block {
+            block: vec![own],
+            rescue: Vec::new(),
+            always: vec![nested],
+        };
+        block.inherit_from(&parent);
+
+        let own = &block.block[0];
+        assert_eq!(
+            own.when,
+            Some(Value::Sequence(vec![
+                Value::String("deploy".to_string()),
+                Value::String("ready".to_string())
+            ]))
+        );
+        assert!(own.is_become);
+        assert_eq!(own.become_user, "app");
+        assert_eq!(own.tags, vec!["web".to_string()]);
+        assert_eq!(own.ignore_errors, Some(true));
+        assert_eq!(
+            own.vars.get(Value::String("port".to_string())),
+            Some(&Value::Number(8080.into()))
+        );
+
+        let inner = &block.always[0].block.as_ref().unwrap().block[0];
+        assert_eq!(inner.when, Some(Value::String("deploy".to_string())));
+        assert_eq!(inner.become_user, "root");
+        // A task can still turn off ignore_errors set on its block
+        assert_eq!(inner.ignore_errors, Some(false));
+        assert_eq!(
+            inner.vars.get(Value::String("port".to_string())),
+            Some(&Value::Number(80.into()))
+        );
+    }
+}
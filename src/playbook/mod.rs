@@ -1,23 +1,265 @@
+mod block;
 pub mod filters;
 mod handlers;
+mod options;
+mod output;
 mod parser;
 mod play;
+mod role;
+mod serial;
+mod source;
+mod stats;
+mod strategy;
+mod tags;
 mod task;
 mod templar;
+mod vars;
 
-use crate::inventory::Inventory;
+use crate::inventory::{Host, Inventory};
+use crate::ssh::pool::ConnectionPool;
 use anyhow::Result;
 use log::{debug, error, info};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use std::time::Instant;
 
+pub use block::Block;
 pub use handlers::Handler;
+pub use options::PlaybookOptions;
 pub use play::Play;
+pub use serial::SerialBatch;
+pub use stats::{HostStats, PlaybookStats};
 pub use task::{Task, TaskResult};
+pub use templar::render_string;
+pub use vars::parse_extra_vars;
 
-pub fn execute(playbook_file: &str, inventory: &Inventory) -> Result<()> {
+pub fn execute(playbook_file: &str, inventory: &Inventory) -> Result<PlaybookStats> {
+    execute_with_options(playbook_file, inventory, &PlaybookOptions::default())
+}
+
+pub fn execute_with_options(
+    playbook_file: &str,
+    inventory: &Inventory,
+    options: &PlaybookOptions,
+) -> Result<PlaybookStats> {
     info!("Loading playbook from file: {}", playbook_file);
 
     let playbook = parser::parse_playbook(playbook_file)?;
     info!("Playbook contains {} plays", playbook.plays.len());
+    let inventory = &*playbook_inventory(playbook_file, inventory)?;
+
+    // SSH sessions are reused by every play, then closed whether the run succeeded or not
+    let start_time = Instant::now();
+    let mut stats = PlaybookStats::default();
+    let result = run_plays(&playbook, inventory, options, &mut stats);
+    ConnectionPool::global().close_all();
+
+    // One recap covers every play of the run
+    stats.print_recap();
+    println!(
+        "\nPlaybook execution completed in {:.2}s",
+        start_time.elapsed().as_secs_f64()
+    );
+
+    result.map(|()| stats)
+}
+
+/// What `list` prints about each play of a playbook
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Listing {
+    /// The hosts the play runs on, after `--limit` (`--list-hosts`)
+    pub hosts: bool,
+    /// The tasks `--tags`/`--skip-tags` select, with their tags (`--list-tasks`)
+    pub tasks: bool,
+    /// The tags of the selected tasks (`--list-tags`)
+    pub tags: bool,
+}
+
+/// Print the hosts, tasks or tags of every play instead of running the playbook
+pub fn list(
+    playbook_file: &str,
+    inventory: &Inventory,
+    options: &PlaybookOptions,
+    listing: Listing,
+) -> Result<()> {
+    let playbook = parser::parse_playbook(playbook_file)?;
+    // Only the host listing needs the inventory
+    let limit_hosts = if listing.hosts {
+        limit_host_names(inventory, options)?
+    } else {
+        None
+    };
+
+    println!("\nplaybook: {}", playbook_file);
+    for (index, play) in playbook.plays.iter().enumerate() {
+        println!(
+            "\n  play #{} ({}): {}\tTAGS: [{}]",
+            index + 1,
+            play.hosts,
+            play.name,
+            play.tags.join(", ")
+        );
+
+        if listing.hosts {
+            let mut hosts: Vec<String> = play_hosts(play, inventory, limit_hosts.as_ref())
+                .into_iter()
+                .map(|host| host.name)
+                .collect();
+            hosts.sort();
+            println!("    pattern: ['{}']", play.hosts);
+            println!("    hosts ({}):", hosts.len());
+            for host in hosts {
+                println!("      {}", host);
+            }
+        }
+
+        let tasks = selected_tasks(&play.tasks, options);
+        if listing.tasks {
+            println!("    tasks:");
+            for task in &tasks {
+                println!("      {}\tTAGS: [{}]", task.name, task.tags.join(", "));
+            }
+        }
+
+        if listing.tags {
+            let task_tags: BTreeSet<&String> =
+                tasks.iter().flat_map(|task| task.tags.iter()).collect();
+            println!(
+                "      TASK TAGS: [{}]",
+                task_tags
+                    .into_iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the variables a host starts each of its plays with and where each value comes from,
+/// with the lower precedence values it overrides (`--debug-vars`)
+pub fn debug_vars(
+    playbook_file: &str,
+    inventory: &Inventory,
+    options: &PlaybookOptions,
+    host_name: &str,
+) -> Result<()> {
+    let playbook = parser::parse_playbook(playbook_file)?;
+    let inventory = &*playbook_inventory(playbook_file, inventory)?;
+    let limit_hosts = limit_host_names(inventory, options)?;
+
+    println!("\nplaybook: {}", playbook_file);
+    for (index, play) in playbook.plays.iter().enumerate() {
+        let hosts = play_hosts(play, inventory, limit_hosts.as_ref());
+        let Some(host) = hosts.iter().find(|host| host.name == host_name) else {
+            continue;
+        };
+
+        println!(
+            "\n  play #{} ({}): {}\n    host: {}",
+            index + 1,
+            play.hosts,
+            play.name,
+            host.name
+        );
+        let host_vars = play.host_vars(host, &options.extra_vars);
+        for (name, origins) in host_vars.origins() {
+            let (source, value) = origins[0];
+            println!("      {} = {}  [{}]", name, display_value(value), source);
+            for (source, value) in &origins[1..] {
+                println!("          overrides {}  [{}]", display_value(value), source);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A variable value on one line
+fn display_value(value: &serde_yaml::Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value))
+}
+
+/// Parse and validate a playbook without running it (`--syntax-check`)
+pub fn syntax_check(playbook_file: &str) -> Result<()> {
+    parser::parse_playbook(playbook_file)?;
+    println!("\nplaybook: {}", playbook_file);
+    Ok(())
+}
+
+/// The inventory with the `group_vars` and `host_vars` next to the playbook, which override
+/// the ones next to the inventory
+fn playbook_inventory<'a>(
+    playbook_file: &str,
+    inventory: &'a Inventory,
+) -> Result<Cow<'a, Inventory>> {
+    let dir = match Path::new(playbook_file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.join("group_vars").is_dir() && !dir.join("host_vars").is_dir() {
+        return Ok(Cow::Borrowed(inventory));
+    }
+
+    let mut inventory = inventory.clone();
+    inventory.load_vars_dirs(dir)?;
+    Ok(Cow::Owned(inventory))
+}
+
+/// The hosts allowed by `--limit`, if set
+fn limit_host_names(
+    inventory: &Inventory,
+    options: &PlaybookOptions,
+) -> Result<Option<HashSet<String>>> {
+    match &options.limit {
+        Some(limit) => Ok(Some(inventory.limit_host_names(limit)?)),
+        None => Ok(None),
+    }
+}
+
+/// The hosts of the inventory matching the play's pattern and the limit
+fn play_hosts(
+    play: &Play,
+    inventory: &Inventory,
+    limit_hosts: Option<&HashSet<String>>,
+) -> Vec<Host> {
+    let mut hosts = inventory.filter_hosts(&play.hosts);
+    if let Some(allowed) = limit_hosts {
+        hosts.retain(|host| allowed.contains(&host.name));
+    }
+    hosts
+}
+
+/// The tasks `--tags`/`--skip-tags` select, with the tasks of blocks in place of the blocks
+fn selected_tasks<'a>(tasks: &'a [Task], options: &PlaybookOptions) -> Vec<&'a Task> {
+    let mut selected = Vec::new();
+    for task in tasks {
+        match &task.block {
+            Some(block) => {
+                for section in [&block.block, &block.rescue, &block.always] {
+                    selected.extend(selected_tasks(section, options));
+                }
+            }
+            None if options.selects_tags(&task.tags) => selected.push(task),
+            None => {}
+        }
+    }
+    selected
+}
+
+fn run_plays(
+    playbook: &parser::Playbook,
+    inventory: &Inventory,
+    options: &PlaybookOptions,
+    stats: &mut PlaybookStats,
+) -> Result<()> {
+    let limit_hosts = limit_host_names(inventory, options)?;
+
+    // Hosts that failed in a play don't run the following plays
+    let mut failed_hosts = HashSet::new();
 
     for (index, play) in playbook.plays.iter().enumerate() {
         info!(
@@ -27,23 +269,37 @@ pub fn execute(playbook_file: &str, inventory: &Inventory) -> Result<()> {
             playbook.plays.len()
         );
 
-        let hosts = inventory.filter_hosts(&play.hosts);
+        let mut hosts = play_hosts(play, inventory, limit_hosts.as_ref());
         if hosts.is_empty() {
             error!(
-                "No hosts matched for play '{}' with pattern: {}",
-                play.name, play.hosts
+                "No hosts matched for play '{}' with pattern: {}{}",
+                play.name,
+                play.hosts,
+                options
+                    .limit
+                    .as_ref()
+                    .map(|limit| format!(" (limit: {})", limit))
+                    .unwrap_or_default()
             );
             continue;
         }
 
-        debug!("Play '{}' matched {} hosts", play.name, hosts.len());
-        let play_result = play.execute(&hosts);
+        hosts.retain(|host| !failed_hosts.contains(&host.name));
+        if hosts.is_empty() {
+            error!("No hosts left for play '{}'", play.name);
+            continue;
+        }
 
-        if let Err(e) = play_result {
-            error!("Play '{}' failed: {}", play.name, e);
-            // Continue with next play unless fail_fast is enabled
-            if playbook.fail_fast {
-                return Err(e);
+        debug!("Play '{}' matched {} hosts", play.name, hosts.len());
+        match play.run(&hosts, options, stats) {
+            Ok(play_failed_hosts) => failed_hosts.extend(play_failed_hosts),
+            Err(e) => {
+                error!("Play '{}' failed: {}", play.name, e);
+                failed_hosts.extend(hosts.iter().map(|host| host.name.clone()));
+                // Continue with next play unless fail_fast is enabled
+                if playbook.fail_fast {
+                    return Err(e);
+                }
             }
         }
     }
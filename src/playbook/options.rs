@@ -0,0 +1,28 @@
+use serde_yaml::Mapping;
+
+/// Number of hosts a task runs on in parallel when neither `--forks` nor the play sets it
+pub const DEFAULT_FORKS: usize = 5;
+
+/// Run-wide options for a playbook execution, usually built from the command line
+#[derive(Debug, Clone, Default)]
+pub struct PlaybookOptions {
+    /// Predict changes without modifying the managed hosts (`--check`)
+    pub check_mode: bool,
+    /// Restrict every play to the hosts matching this pattern (`--limit`)
+    pub limit: Option<String>,
+    /// Maximum number of hosts a task runs on concurrently (`-f/--forks`)
+    pub forks: Option<usize>,
+    /// Only run the tasks with one of these tags (`--tags`), all tasks when empty
+    pub tags: Vec<String>,
+    /// Don't run the tasks with one of these tags (`--skip-tags`)
+    pub skip_tags: Vec<String>,
+    /// Variables set with `-e/--extra-vars`, overriding every other variable
+    pub extra_vars: Mapping,
+}
+
+impl PlaybookOptions {
+    /// Whether a task with these tags is selected by `--tags` and `--skip-tags`
+    pub fn selects_tags(&self, tags: &[String]) -> bool {
+        crate::playbook::tags::selects(tags, &self.tags, &self.skip_tags)
+    }
+}
@@ -0,0 +1,67 @@
+use std::cell::RefCell;
+use std::io::Write;
+
+thread_local! {
+    // Lines printed while a host is being processed on a worker thread
+    static HOST_BUFFER: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Print a line of task output, buffering it when running inside `capture`
+pub(crate) fn emit(line: String) {
+    HOST_BUFFER.with(|buffer| match buffer.borrow_mut().as_mut() {
+        Some(lines) => lines.push(line),
+        None => println!("{}", line),
+    });
+}
+
+/// Print lines as one block that the output of other threads cannot split up,
+/// buffering them when running inside `capture`
+pub(crate) fn emit_block(lines: impl IntoIterator<Item = String>) {
+    HOST_BUFFER.with(|buffer| match buffer.borrow_mut().as_mut() {
+        Some(buffered) => buffered.extend(lines),
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            for line in lines {
+                let _ = writeln!(stdout, "{}", line);
+            }
+        }
+    });
+}
+
+/// Run `f` and collect everything it emits instead of printing it immediately,
+/// so output from hosts running in parallel can be printed grouped per host
+pub(crate) fn capture<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    HOST_BUFFER.with(|buffer| *buffer.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let lines = HOST_BUFFER
+        .with(|buffer| buffer.borrow_mut().take())
+        .unwrap_or_default();
+    (result, lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_collects_emitted_lines() {
+        let (value, lines) = capture(|| {
+            emit("first".to_string());
+            emit("second".to_string());
+            42
+        });
+
+        assert_eq!(value, 42);
+        assert_eq!(lines, vec!["first", "second"]);
+
+        // Outside of capture nothing is buffered any more
+        let (_, lines) = capture(|| ());
+        assert!(lines.is_empty());
+
+        let (_, lines) = capture(|| {
+            emit("first".to_string());
+            emit_block(["second".to_string(), "third".to_string()]);
+        });
+        assert_eq!(lines, vec!["first", "second", "third"]);
+    }
+}
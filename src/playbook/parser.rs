@@ -1,11 +1,18 @@
 use anyhow::{Context, Result};
 use log::{debug, warn};
 use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
-use crate::playbook::{Handler, Play, Task};
+use crate::playbook::role;
+use crate::playbook::serial;
+use crate::playbook::source::Source;
+use crate::playbook::strategy;
+use crate::playbook::task::INCLUDE_DIR_ARG;
+use crate::playbook::{Block, Handler, Play, Task};
 
 /// The main Playbook structure
 #[derive(Debug)]
@@ -14,11 +21,49 @@ pub struct Playbook {
     pub fail_fast: bool,
 }
 
+/// State collected while parsing a play: where roles are looked up and what they add to the play
+pub(super) struct PlayContext {
+    pub base_dir: PathBuf,
+    pub file_dir: PathBuf, // Directory of the file being parsed, for relative imports
+    pub handlers: Vec<Handler>,
+    pub role_defaults: Mapping,
+    pub loaded_roles: HashSet<String>,
+    pub roles_with_handlers: HashSet<PathBuf>,
+    pub include_stack: Vec<PathBuf>, // Roles and task files being loaded, to detect cycles
+}
+
+impl PlayContext {
+    fn new(base_dir: &Path) -> Self {
+        PlayContext {
+            base_dir: base_dir.to_path_buf(),
+            file_dir: base_dir.to_path_buf(),
+            handlers: Vec::new(),
+            role_defaults: Mapping::new(),
+            loaded_roles: HashSet::new(),
+            roles_with_handlers: HashSet::new(),
+            include_stack: Vec::new(),
+        }
+    }
+}
+
 /// Parse an Ansible playbook YAML file
 pub fn parse_playbook(playbook_path: &str) -> Result<Playbook> {
+    parse_playbook_file(Path::new(playbook_path), &mut Vec::new())
+}
+
+/// Parse a playbook file; `importing` holds the playbooks currently importing it
+fn parse_playbook_file(path: &Path, importing: &mut Vec<PathBuf>) -> Result<Playbook> {
+    let playbook_path = path.display().to_string();
     debug!("Parsing playbook file: {}", playbook_path);
 
-    let path = Path::new(playbook_path);
+    let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if importing.contains(&canonical_path) {
+        return Err(anyhow::anyhow!(
+            "Playbook {} imports itself through import_playbook",
+            playbook_path
+        ));
+    }
+
     let mut file =
         File::open(path).context(format!("Failed to open playbook file: {}", playbook_path))?;
 
@@ -26,11 +71,18 @@ pub fn parse_playbook(playbook_path: &str) -> Result<Playbook> {
     file.read_to_string(&mut content)
         .context("Failed to read playbook file content")?;
 
-    let yaml_docs: Vec<Value> =
-        serde_yaml::from_str(&content).context("Failed to parse YAML content")?;
+    let yaml_docs: Vec<Value> = serde_yaml::from_str(&content)
+        .context(format!("Failed to parse YAML content of {}", playbook_path))?;
 
     debug!("Parsed {} YAML documents from playbook", yaml_docs.len());
 
+    // Roles are looked up relative to the playbook
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    // Errors point at the line of the play or task they come from
+    let source = Source::new(&playbook_path, &content);
+    let doc_lines = source.entries(source.all());
+
     let mut plays = Vec::new();
     let mut fail_fast = false;
 
@@ -45,12 +97,27 @@ pub fn parse_playbook(playbook_path: &str) -> Result<Playbook> {
 
                 for (play_index, play_value) in items.into_iter().enumerate() {
                     match play_value {
+                        Value::Mapping(play_map)
+                            if play_map
+                                .contains_key(Value::String("import_playbook".to_string())) =>
+                        {
+                            importing.push(canonical_path.clone());
+                            let imported = import_playbook(&play_map, base_dir, importing);
+                            importing.pop();
+                            plays.extend(imported.context(format!(
+                                "Failed to import playbook at index {} in document {}",
+                                play_index, doc_index
+                            ))?);
+                        }
                         Value::Mapping(play_map) => {
                             debug!("Processing play {} in document {}", play_index, doc_index);
-                            let play = parse_play(play_map).context(format!(
-                                "Failed to parse play {} in document {}",
-                                play_index, doc_index
-                            ))?;
+                            let play =
+                                parse_play(play_map, base_dir, &source, 0..0).context(format!(
+                                    "Failed to parse play {} in document {} ({})",
+                                    play_index,
+                                    doc_index,
+                                    source.locate(&doc_lines, doc_index)
+                                ))?;
                             plays.push(play);
                         }
                         _ => {
@@ -62,11 +129,26 @@ pub fn parse_playbook(playbook_path: &str) -> Result<Playbook> {
                     }
                 }
             }
+            Value::Mapping(doc_map)
+                if doc_map.contains_key(Value::String("import_playbook".to_string())) =>
+            {
+                importing.push(canonical_path.clone());
+                let imported = import_playbook(&doc_map, base_dir, importing);
+                importing.pop();
+                plays.extend(imported.context(format!(
+                    "Failed to import playbook in document {}",
+                    doc_index
+                ))?);
+            }
             Value::Mapping(doc_map) => {
                 debug!("Processing document {} as a single play", doc_index);
                 let doc_map_clone = doc_map.clone();
-                let play = parse_play(doc_map)
-                    .context(format!("Failed to parse play in document {}", doc_index))?;
+                let lines = doc_lines.get(doc_index).cloned().unwrap_or(0..0);
+                let play = parse_play(doc_map, base_dir, &source, lines).context(format!(
+                    "Failed to parse play in document {} ({})",
+                    doc_index,
+                    source.locate(&doc_lines, doc_index)
+                ))?;
                 plays.push(play);
 
                 if let Some(Value::Bool(fast_fail)) =
@@ -86,8 +168,28 @@ pub fn parse_playbook(playbook_path: &str) -> Result<Playbook> {
     Ok(Playbook { plays, fail_fast })
 }
 
-/// Parse an individual play from a YAML mapping
-fn parse_play(play_map: Mapping) -> Result<Play> {
+/// Load the plays of an `import_playbook` entry, relative to the importing playbook
+fn import_playbook(
+    entry: &Mapping,
+    base_dir: &Path,
+    importing: &mut Vec<PathBuf>,
+) -> Result<Vec<Play>> {
+    let file = match entry.get(Value::String("import_playbook".to_string())) {
+        Some(Value::String(file)) => file,
+        _ => return Err(anyhow::anyhow!("import_playbook requires a file name")),
+    };
+
+    debug!("Importing playbook {}", file);
+    Ok(parse_playbook_file(&base_dir.join(file), importing)?.plays)
+}
+
+/// Parse an individual play from a YAML mapping, written on the `lines` of `source`
+fn parse_play(
+    play_map: Mapping,
+    base_dir: &Path,
+    source: &Source,
+    lines: Range<usize>,
+) -> Result<Play> {
     debug!("Parsing play definition");
 
     // Play name is required
@@ -104,14 +206,32 @@ fn parse_play(play_map: Mapping) -> Result<Play> {
         None => return Err(anyhow::anyhow!("Play requires a hosts field")),
     };
 
-    // Process tasks
+    let mut ctx = PlayContext::new(base_dir);
+
+    // Roles run before the play's own tasks
     let mut tasks = Vec::new();
+    if let Some(Value::Sequence(role_seq)) = play_map.get(Value::String("roles".to_string())) {
+        for (role_index, role_entry) in role_seq.iter().enumerate() {
+            if let Some(task) = parse_role_entry(role_entry, &mut ctx)
+                .context(format!("Failed to load role at index {}", role_index))?
+            {
+                tasks.push(task);
+            }
+        }
+    }
+
+    // Process tasks
     if let Some(Value::Sequence(task_seq)) = play_map.get(&Value::String("tasks".to_string())) {
+        let task_lines = source.entries(source.key(lines.clone(), "tasks"));
         for (task_index, task_value) in task_seq.iter().enumerate() {
             match task_value {
                 Value::Mapping(task_map) => {
-                    let task = parse_task(task_map.clone(), task_index)
-                        .context(format!("Failed to parse task at index {}", task_index))?;
+                    let task =
+                        parse_task(task_map.clone(), task_index, &mut ctx).context(format!(
+                            "Failed to parse task at index {} ({})",
+                            task_index,
+                            source.locate(&task_lines, task_index)
+                        ))?;
                     tasks.push(task);
                 }
                 _ => {
@@ -125,12 +245,16 @@ fn parse_play(play_map: Mapping) -> Result<Play> {
     let mut handlers = Vec::new();
     if let Some(Value::Sequence(handler_seq)) = play_map.get(&Value::String("handlers".to_string()))
     {
+        let handler_lines = source.entries(source.key(lines, "handlers"));
         for (handler_index, handler_value) in handler_seq.iter().enumerate() {
             match handler_value {
                 Value::Mapping(handler_map) => {
-                    let handler = parse_handler(handler_map.clone(), handler_index).context(
-                        format!("Failed to parse handler at index {}", handler_index),
-                    )?;
+                    let handler = parse_handler(handler_map.clone(), handler_index, &mut ctx)
+                        .context(format!(
+                            "Failed to parse handler at index {} ({})",
+                            handler_index,
+                            source.locate(&handler_lines, handler_index)
+                        ))?;
                     handlers.push(handler);
                 }
                 _ => {
@@ -140,12 +264,33 @@ fn parse_play(play_map: Mapping) -> Result<Play> {
         }
     }
 
+    // Role handlers come first, like the role tasks
+    let mut handlers: Vec<Handler> = ctx.handlers.drain(..).chain(handlers).collect();
+
     // Variables are optional
     let mut vars = Mapping::new();
     if let Some(Value::Mapping(var_map)) = play_map.get(&Value::String("vars".to_string())) {
         vars = var_map.clone();
     }
 
+    // Variable files are read relative to the playbook
+    let vars_files = match play_map.get(Value::String("vars_files".to_string())) {
+        Some(Value::Sequence(files)) => files.clone(),
+        Some(file @ Value::String(_)) => vec![file.clone()],
+        Some(_) => {
+            return Err(anyhow::anyhow!(
+                "Play '{}' has an invalid 'vars_files' value, expected a list of files",
+                name
+            ))
+        }
+        None => Vec::new(),
+    };
+    let vars_files = vars_files
+        .iter()
+        .map(|file| load_vars_file(file, base_dir))
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("Play '{}' has an invalid 'vars_files' entry", name))?;
+
     // Check for become (privilege escalation)
     let mut is_become = false;
     let mut become_user = "root".to_string();
@@ -170,6 +315,92 @@ fn parse_play(play_map: Mapping) -> Result<Play> {
         tags.push(tag.clone());
     }
 
+    // Play tags apply to everything in the play
+    inherit_play_tags(&mut tasks, &tags);
+    for handler in &mut handlers {
+        inherit_play_tags(std::slice::from_mut(&mut handler.task), &tags);
+    }
+
+    // Play-level check mode override
+    let check_mode = play_map
+        .get(Value::String("check_mode".to_string()))
+        .and_then(parse_bool_value);
+
+    // Fact gathering runs before the tasks unless explicitly disabled
+    let gather_facts = play_map
+        .get(Value::String("gather_facts".to_string()))
+        .and_then(parse_bool_value)
+        .unwrap_or(true);
+    let gather_subset = play_map
+        .get(Value::String("gather_subset".to_string()))
+        .cloned();
+
+    // Play-level number of parallel hosts
+    let forks = match play_map.get(Value::String("forks".to_string())) {
+        Some(value) => {
+            let forks = match value {
+                Value::Number(n) => n.as_u64(),
+                Value::String(s) => s.trim().parse::<u64>().ok(),
+                _ => None,
+            };
+            match forks {
+                Some(n) if n > 0 => Some(n as usize),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Play '{}' has an invalid 'forks' value, expected a positive integer",
+                        name
+                    ))
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Failure handling across hosts
+    let any_errors_fatal = play_map
+        .get(Value::String("any_errors_fatal".to_string()))
+        .and_then(parse_bool_value)
+        .unwrap_or(false);
+    let max_fail_percentage = match play_map.get(Value::String("max_fail_percentage".to_string())) {
+        Some(value) => {
+            let percentage = match value {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().trim_end_matches('%').parse::<f64>().ok(),
+                _ => None,
+            };
+            match percentage {
+                Some(p) if (0.0..=100.0).contains(&p) => Some(p),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Play '{}' has an invalid 'max_fail_percentage' value, expected a number between 0 and 100",
+                        name
+                    ))
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Rolling update batches
+    let serial = match play_map.get(Value::String("serial".to_string())) {
+        Some(value) => serial::parse_serial(value)
+            .with_context(|| format!("Play '{}' has an invalid 'serial' value", name))?,
+        None => Vec::new(),
+    };
+
+    // How the hosts go through the tasks
+    let strategy = match play_map.get(Value::String("strategy".to_string())) {
+        Some(Value::String(name)) if strategy::find(name).is_some() => name.clone(),
+        Some(value) => {
+            return Err(anyhow::anyhow!(
+                "Play '{}' uses an unknown strategy {:?}",
+                name,
+                value
+            ))
+        }
+        None => strategy::DEFAULT_STRATEGY.to_string(),
+    };
+
     debug!(
         "Finished parsing play '{}' with {} tasks and {} handlers",
         name,
@@ -186,18 +417,109 @@ fn parse_play(play_map: Mapping) -> Result<Play> {
         is_become,
         become_user,
         tags,
+        check_mode,
+        forks,
+        gather_facts,
+        gather_subset,
+        role_defaults: ctx.role_defaults,
+        vars_files,
+        any_errors_fatal,
+        max_fail_percentage,
+        serial,
+        strategy,
     })
 }
 
+/// Read a file of `vars_files`, which must hold a mapping of variables
+fn load_vars_file(file: &Value, base_dir: &Path) -> Result<(String, Mapping)> {
+    let file = match file {
+        Value::String(file) if file.contains("{{") => {
+            return Err(anyhow::anyhow!(
+                "vars_files entry '{}' is read when the playbook is loaded and cannot be templated",
+                file
+            ))
+        }
+        Value::String(file) => file,
+        _ => return Err(anyhow::anyhow!("vars_files entries must be file names")),
+    };
+
+    let path = base_dir.join(file);
+    let content = std::fs::read_to_string(&path)
+        .context(format!("Failed to read vars file: {}", path.display()))?;
+    match serde_yaml::from_str(&content)
+        .context(format!("Failed to parse vars file: {}", path.display()))?
+    {
+        Value::Mapping(vars) => Ok((file.clone(), vars)),
+        Value::Null => Ok((file.clone(), Mapping::new())),
+        _ => Err(anyhow::anyhow!(
+            "Vars file {} must contain a mapping of variables",
+            path.display()
+        )),
+    }
+}
+
+/// Add the play's tags to tasks, including the ones nested in blocks
+fn inherit_play_tags(tasks: &mut [Task], tags: &[String]) {
+    for task in tasks {
+        for tag in tags {
+            if !task.tags.contains(tag) {
+                task.tags.push(tag.clone());
+            }
+        }
+        if let Some(block) = task.block.as_mut() {
+            inherit_play_tags(&mut block.block, tags);
+            inherit_play_tags(&mut block.rescue, tags);
+            inherit_play_tags(&mut block.always, tags);
+        }
+    }
+}
+
+/// Parse an entry of a play's `roles:` list, or a role dependency. Returns None when the
+/// same role with the same parameters was already added to the play.
+pub(super) fn parse_role_entry(entry: &Value, ctx: &mut PlayContext) -> Result<Option<Task>> {
+    let (name, task_map) = role::role_entry_task(entry)?;
+
+    let role_path = role::find_role(&name, &ctx.base_dir)?;
+    if ctx.include_stack.contains(&role_path) {
+        return Err(anyhow::anyhow!(
+            "Role '{}' depends on itself through its dependencies",
+            name
+        ));
+    }
+
+    let params = serde_yaml::to_string(&task_map.get(Value::String("import_role".to_string())))?;
+    let role_key = format!("{}\n{}", role_path.display(), params);
+    if !ctx.loaded_roles.insert(role_key) {
+        debug!("Role '{}' already added to the play, skipping", name);
+        return Ok(None);
+    }
+
+    parse_task(task_map, 0, ctx).map(Some)
+}
+
 /// Parse a task from a YAML mapping
-fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
+pub(super) fn parse_task(task_map: Mapping, index: usize, ctx: &mut PlayContext) -> Result<Task> {
     debug!("Parsing task definition at index {}", index);
 
-    // Name is required for clarity
+    let mut block = parse_block(&task_map, ctx)?;
+    let include = [
+        "include_role",
+        "import_role",
+        "include_tasks",
+        "import_tasks",
+    ]
+    .into_iter()
+    .find(|key| task_map.contains_key(Value::String(key.to_string())));
+
+    // Name is required for clarity, blocks and includes may leave it out
     let name = match task_map.get(&Value::String("name".to_string())) {
         Some(Value::String(name)) => name.clone(),
         Some(_) => return Err(anyhow::anyhow!("Task name must be a string")),
-        None => return Err(anyhow::anyhow!("Task requires a name field")),
+        None => match include {
+            Some(key) => key.to_string(),
+            None if block.is_some() => "block".to_string(),
+            None => return Err(anyhow::anyhow!("Task requires a name field")),
+        },
     };
 
     // Find the module and arguments
@@ -218,6 +540,11 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
                 "become_user",
                 "register",
                 "when",
+                "changed_when",
+                "failed_when",
+                "until",
+                "retries",
+                "delay",
                 "tags",
                 "notify",
                 "ignore_errors",
@@ -225,6 +552,10 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
                 "with_items",
                 "loop",
                 "loop_control",
+                "check_mode",
+                "block",
+                "rescue",
+                "always",
             ]
             .contains(&key_str.as_str())
             {
@@ -252,6 +583,9 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
                         );
                     }
                 }
+                Value::Null => {
+                    // Module used without arguments, e.g. "setup:"
+                }
                 Value::Mapping(map_val) => {
                     // Value is a map. Use this map directly as the arguments.
                     // This handles both complex args (e.g., file: { path: ... })
@@ -273,7 +607,79 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
         }
     }
 
-    if module.is_empty() {
+    // Task-level variables
+    let mut vars = Mapping::new();
+    if let Some(Value::Mapping(var_map)) = task_map.get(Value::String("vars".to_string())) {
+        vars = var_map.clone();
+    }
+
+    let mut role_vars = Mapping::new();
+    let mut role_params = Mapping::new();
+    let has_loop = task_map.contains_key(Value::String("loop".to_string()))
+        || task_map.contains_key(Value::String("with_items".to_string()));
+    if module == "include_role" {
+        if block.is_some() {
+            return Err(anyhow::anyhow!(
+                "Task '{}' cannot combine '{}' with 'block'",
+                name,
+                module
+            ));
+        }
+        // Loaded at run time, so the role name can be templated and looped over.
+        // Roles are looked up next to the playbook.
+        args.insert(
+            Value::String(INCLUDE_DIR_ARG.to_string()),
+            Value::String(ctx.base_dir.to_string_lossy().to_string()),
+        );
+    } else if module == "import_role" {
+        if block.is_some() {
+            return Err(anyhow::anyhow!(
+                "Task '{}' cannot combine '{}' with 'block'",
+                name,
+                module
+            ));
+        }
+        if has_loop {
+            return Err(anyhow::anyhow!("import_role '{}' cannot use a loop", name));
+        }
+
+        // Roles are loaded with the playbook and run like a block of their tasks
+        if let Some(Value::Mapping(params)) = args.remove(role::ROLE_PARAMS_ARG) {
+            role_params = params;
+        }
+        let (role_block, vars) = role::load_role(&args, ctx)?;
+        role_vars = vars;
+        block = Some(role_block);
+    } else if module == "import_tasks" {
+        if block.is_some() || has_loop {
+            return Err(anyhow::anyhow!(
+                "import_tasks '{}' cannot be combined with 'block' or a loop, use include_tasks",
+                name
+            ));
+        }
+        block = Some(Block {
+            block: import_tasks(&args, ctx)?,
+            ..Default::default()
+        });
+    } else if module == "include_tasks" {
+        // Included at run time, relative to the file the task is written in
+        args.insert(
+            Value::String(INCLUDE_DIR_ARG.to_string()),
+            Value::String(ctx.file_dir.to_string_lossy().to_string()),
+        );
+    } else if block.is_some() {
+        if !module.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Block '{}' cannot also run module '{}'",
+                name,
+                module
+            ));
+        }
+        if has_loop {
+            return Err(anyhow::anyhow!("Block '{}' cannot use a loop", name));
+        }
+        module = "block".to_string();
+    } else if module.is_empty() {
         return Err(anyhow::anyhow!("Task doesn't specify a module to execute"));
     }
 
@@ -301,6 +707,19 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
         when = Some(when_value.clone());
     }
 
+    // Result status overrides
+    let changed_when = task_map
+        .get(Value::String("changed_when".to_string()))
+        .cloned();
+    let failed_when = task_map
+        .get(Value::String("failed_when".to_string()))
+        .cloned();
+
+    // Retry loop
+    let until = task_map.get(Value::String("until".to_string())).cloned();
+    let retries = parse_count(&task_map, "retries")?;
+    let delay = parse_count(&task_map, "delay")?;
+
     // Check for notification handlers
     let mut notify = Vec::new();
     if let Some(Value::Sequence(notify_seq)) = task_map.get(&Value::String("notify".to_string())) {
@@ -315,17 +734,14 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
     }
 
     // Check for ignore_errors
-    let mut ignore_errors = false;
-    if let Some(value) = task_map.get(&Value::String("ignore_errors".to_string())) {
-        ignore_errors = match value {
-            Value::Bool(b) => *b,
-            Value::String(s) => match s.to_lowercase().as_str() {
-                "yes" | "true" => true,
-                _ => false, // Default to false for unrecognized strings or "no"
-            },
-            _ => false, // Default to false for other types
-        };
-    }
+    let ignore_errors = task_map
+        .get(Value::String("ignore_errors".to_string()))
+        .and_then(parse_bool_value);
+
+    // Task-level check mode override (e.g. `check_mode: false` to always run)
+    let check_mode = task_map
+        .get(Value::String("check_mode".to_string()))
+        .and_then(parse_bool_value);
 
     // Check for tags
     let mut tags = Vec::new();
@@ -361,7 +777,7 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
 
     debug!("Finished parsing task '{}' with module '{}'", name, module);
 
-    Ok(Task {
+    let mut task = Task {
         name,
         module,
         args,
@@ -369,21 +785,241 @@ fn parse_task(task_map: Mapping, index: usize) -> Result<Task> {
         become_user,
         register,
         when,
+        changed_when,
+        failed_when,
+        until,
+        retries,
+        delay,
         notify,
         ignore_errors,
         tags,
         loop_items,
         loop_var_name,
         index_var_name,
-    })
+        check_mode,
+        vars,
+        role_vars,
+        role_params,
+        role_defaults: Mapping::new(),
+        block: None,
+    };
+
+    if let Some(mut block) = block {
+        block.inherit_from(&task);
+        task.block = Some(block);
+    }
+
+    Ok(task)
+}
+
+/// Load the tasks of an `import_tasks` file, relative to the file containing the import
+fn import_tasks(args: &Mapping, ctx: &mut PlayContext) -> Result<Vec<Task>> {
+    let file = match args
+        .get(Value::String("_raw_params".to_string()))
+        .or_else(|| args.get(Value::String("file".to_string())))
+    {
+        Some(Value::String(file)) => file,
+        _ => return Err(anyhow::anyhow!("import_tasks requires a file name")),
+    };
+    if file.contains("{{") {
+        return Err(anyhow::anyhow!(
+            "import_tasks file '{}' is resolved when the playbook is loaded and cannot be templated, use include_tasks",
+            file
+        ));
+    }
+
+    let path = ctx.file_dir.join(file);
+    let canonical_path = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if ctx.include_stack.contains(&canonical_path) {
+        return Err(anyhow::anyhow!(
+            "Task file {} imports itself",
+            path.display()
+        ));
+    }
+
+    debug!("Importing tasks from {}", path.display());
+    ctx.include_stack.push(canonical_path);
+    let tasks = read_task_file(&path).and_then(|task_maps| {
+        parse_tasks_in(path.parent().unwrap_or(Path::new(".")), task_maps, ctx)
+    });
+    ctx.include_stack.pop();
+    tasks.context(format!("Failed to import tasks from {}", path.display()))
+}
+
+/// Parse the tasks of an `include_tasks` file when it is included at run time, along with the
+/// handlers of the roles it imports
+pub(super) fn parse_included_tasks(path: &Path) -> Result<(Vec<Task>, Vec<Handler>)> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut ctx = PlayContext::new(dir);
+    let tasks = parse_tasks_in(dir, read_task_file(path)?, &mut ctx)?;
+    Ok((tasks, ctx.handlers))
+}
+
+/// Load the role found at `role_path` when an `include_role` task includes it at run time:
+/// its tasks, handlers, variables and defaults
+pub(super) fn parse_included_role(
+    role_path: &Path,
+    args: &Mapping,
+) -> Result<(Vec<Task>, Vec<Handler>, Mapping, Mapping)> {
+    let name = role_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let base_dir = match args.get(Value::String(INCLUDE_DIR_ARG.to_string())) {
+        Some(Value::String(dir)) => Path::new(dir).to_path_buf(),
+        _ => PathBuf::new(),
+    };
+    let mut ctx = PlayContext::new(&base_dir);
+    let (block, vars) = role::load_role_at(&name, role_path, args, &mut ctx)?;
+    Ok((block.block, ctx.handlers, vars, ctx.role_defaults))
+}
+
+/// Read a YAML file holding a list of tasks
+fn read_task_file(path: &Path) -> Result<Vec<(usize, Mapping)>> {
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read task file: {}", path.display()))?;
+    let value: Value = serde_yaml::from_str(&content)
+        .context(format!("Failed to parse task file: {}", path.display()))?;
+    Ok(task_maps(Some(value)))
+}
+
+/// The task mappings of a task list, skipping entries that are not mappings
+pub(super) fn task_maps(value: Option<Value>) -> Vec<(usize, Mapping)> {
+    match value {
+        Some(Value::Sequence(items)) => items
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, item)| match item {
+                Value::Mapping(task_map) => Some((index, task_map)),
+                _ => {
+                    warn!("Skipping non-mapping task at index {}", index);
+                    None
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parse tasks that come from a file in `file_dir`, so their own imports are relative to it
+pub(super) fn parse_tasks_in(
+    file_dir: &Path,
+    task_maps: Vec<(usize, Mapping)>,
+    ctx: &mut PlayContext,
+) -> Result<Vec<Task>> {
+    let previous_dir = std::mem::replace(&mut ctx.file_dir, file_dir.to_path_buf());
+    let tasks = task_maps
+        .into_iter()
+        .map(|(index, task_map)| {
+            parse_task(task_map, index, ctx)
+                .context(format!("Failed to parse task at index {}", index))
+        })
+        .collect();
+    ctx.file_dir = previous_dir;
+    tasks
+}
+
+/// Parse the `block`, `rescue` and `always` sections of a task, if it is a block
+fn parse_block(task_map: &Mapping, ctx: &mut PlayContext) -> Result<Option<Block>> {
+    if !task_map.contains_key(Value::String("block".to_string())) {
+        for section in ["rescue", "always"] {
+            if task_map.contains_key(Value::String(section.to_string())) {
+                return Err(anyhow::anyhow!(
+                    "'{}' can only be used together with 'block'",
+                    section
+                ));
+            }
+        }
+        return Ok(None);
+    }
+
+    Ok(Some(Block {
+        block: parse_block_section(task_map, "block", ctx)?,
+        rescue: parse_block_section(task_map, "rescue", ctx)?,
+        always: parse_block_section(task_map, "always", ctx)?,
+    }))
+}
+
+fn parse_block_section(
+    task_map: &Mapping,
+    section: &str,
+    ctx: &mut PlayContext,
+) -> Result<Vec<Task>> {
+    let task_seq = match task_map.get(Value::String(section.to_string())) {
+        Some(Value::Sequence(task_seq)) => task_seq,
+        Some(Value::Null) | None => return Ok(Vec::new()),
+        Some(_) => {
+            return Err(anyhow::anyhow!(
+                "Block section '{}' must be a list of tasks",
+                section
+            ))
+        }
+    };
+
+    let mut tasks = Vec::new();
+    for (task_index, task_value) in task_seq.iter().enumerate() {
+        match task_value {
+            Value::Mapping(child_map) => {
+                let task = parse_task(child_map.clone(), task_index, ctx).context(format!(
+                    "Failed to parse {} task at index {}",
+                    section, task_index
+                ))?;
+                tasks.push(task);
+            }
+            _ => {
+                warn!(
+                    "Skipping non-mapping {} task at index {}",
+                    section, task_index
+                );
+            }
+        }
+    }
+    Ok(tasks)
+}
+
+/// Interpret a YAML boolean, accepting the Ansible-style "yes"/"no" strings
+fn parse_bool_value(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) => match s.to_lowercase().as_str() {
+            "yes" | "true" => Some(true),
+            "no" | "false" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Parse a non-negative integer keyword such as `retries`, None when it is not set
+fn parse_count(task_map: &Mapping, key: &str) -> Result<Option<u64>> {
+    match task_map.get(Value::String(key.to_string())) {
+        None => Ok(None),
+        Some(Value::Number(n)) if n.as_u64().is_some() => Ok(n.as_u64()),
+        Some(Value::String(s)) if s.trim().parse::<u64>().is_ok() => Ok(s.trim().parse().ok()),
+        Some(other) => Err(anyhow::anyhow!(
+            "'{}' must be a non-negative integer, got {:?}",
+            key,
+            other
+        )),
+    }
 }
 
 /// Parse a handler from a YAML mapping (similar to a task)
-fn parse_handler(handler_map: Mapping, index: usize) -> Result<Handler> {
+pub(super) fn parse_handler(
+    handler_map: Mapping,
+    index: usize,
+    ctx: &mut PlayContext,
+) -> Result<Handler> {
     debug!("Parsing handler definition at index {}", index);
 
     // A handler is essentially a task that is triggered by notifications
-    let task = parse_task(handler_map, index)?;
+    let task = parse_task(handler_map, index, ctx)?;
+    if task.block.is_some() || task.module == "include_tasks" || task.module == "include_role" {
+        return Err(anyhow::anyhow!(
+            "Handler '{}' cannot be a block or an include",
+            task.name
+        ));
+    }
 
     Ok(Handler { task })
 }
@@ -461,7 +1097,7 @@ mod tests {
         assert_eq!(task.register, Some("shell_result".to_string()));
         assert!(task.when.is_some());
         assert_eq!(task.notify, vec!["Restart service".to_string()]);
-        assert_eq!(task.ignore_errors, true);
+        assert_eq!(task.ignore_errors, Some(true));
         assert!(task.loop_items.is_some());
         if let Some(Value::Sequence(items)) = &task.loop_items {
             assert_eq!(items.len(), 2);
@@ -607,6 +1243,540 @@ mod tests {
         assert_eq!(task.index_var_name, Some("idx".to_string()));
     }
 
+    #[test]
+    fn test_parse_check_mode() {
+        let content = r#"
+---
+- name: Check Mode Play
+  hosts: all
+  check_mode: yes
+  tasks:
+    - name: Always run
+      command: echo hello
+      check_mode: false
+    - name: Inherit
+      command: echo hello
+"#;
+        let temp_file = create_temp_playbook(content);
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        let play = &playbook.plays[0];
+
+        assert_eq!(play.check_mode, Some(true));
+        assert_eq!(play.tasks[0].check_mode, Some(false));
+        assert_eq!(play.tasks[0].module, "command");
+        assert_eq!(play.tasks[1].check_mode, None);
+    }
+
+    #[test]
+    fn test_parse_retry_loop() {
+        let content = r#"
+---
+- name: Retry Play
+  hosts: all
+  tasks:
+    - name: Wait for service
+      command: systemctl is-active app
+      register: app_status
+      until: app_status.rc == 0
+      retries: 10
+      delay: "2"
+      changed_when: false
+      failed_when:
+        - app_status.rc != 0
+        - app_status.rc != 3
+    - name: Bad retries
+      command: echo hello
+      retries: many
+"#;
+        let temp_file = create_temp_playbook(content);
+        let err = parse_playbook(temp_file.path().to_str().unwrap()).unwrap_err();
+        assert!(format!("{:#}", err).contains("'retries' must be a non-negative integer"));
+
+        let content = content.split("    - name: Bad retries").next().unwrap();
+        let temp_file = create_temp_playbook(content);
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        let task = &playbook.plays[0].tasks[0];
+
+        assert_eq!(task.module, "command");
+        assert_eq!(
+            task.until,
+            Some(Value::String("app_status.rc == 0".to_string()))
+        );
+        assert_eq!(task.retries, Some(10));
+        assert_eq!(task.delay, Some(2));
+        assert_eq!(task.changed_when, Some(Value::Bool(false)));
+        assert!(matches!(&task.failed_when, Some(Value::Sequence(c)) if c.len() == 2));
+    }
+
+    #[test]
+    fn test_parse_play_forks() {
+        let content = r#"
+---
+- name: Wide Play
+  hosts: all
+  forks: 20
+  tasks: []
+- name: Default Play
+  hosts: all
+  tasks: []
+"#;
+        let temp_file = create_temp_playbook(content);
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(playbook.plays[0].forks, Some(20));
+        assert_eq!(playbook.plays[1].forks, None);
+
+        let content = r#"
+---
+- name: Broken Play
+  hosts: all
+  forks: 0
+  tasks: []
+"#;
+        let temp_file = create_temp_playbook(content);
+        assert!(parse_playbook(temp_file.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_failure_limits() {
+        let content = r#"
+---
+- name: Strict Play
+  hosts: all
+  any_errors_fatal: true
+  max_fail_percentage: "25%"
+  serial: [1, "30%"]
+  strategy: free
+  tasks: []
+- name: Default Play
+  hosts: all
+  tasks: []
+"#;
+        let temp_file = create_temp_playbook(content);
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(playbook.plays[0].any_errors_fatal);
+        assert_eq!(playbook.plays[0].max_fail_percentage, Some(25.0));
+        assert!(!playbook.plays[1].any_errors_fatal);
+        assert_eq!(playbook.plays[1].max_fail_percentage, None);
+        assert_eq!(
+            playbook.plays[0].serial,
+            vec![
+                crate::playbook::SerialBatch::Hosts(1),
+                crate::playbook::SerialBatch::Percent(30.0)
+            ]
+        );
+        assert!(playbook.plays[1].serial.is_empty());
+        assert_eq!(playbook.plays[0].strategy, "free");
+        assert_eq!(playbook.plays[1].strategy, "linear");
+
+        let content = r#"
+---
+- name: Broken Play
+  hosts: all
+  max_fail_percentage: 150
+  tasks: []
+"#;
+        let temp_file = create_temp_playbook(content);
+        assert!(parse_playbook(temp_file.path().to_str().unwrap()).is_err());
+
+        let content = content.replace("max_fail_percentage: 150", "strategy: random");
+        let temp_file = create_temp_playbook(&content);
+        assert!(parse_playbook(temp_file.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_play_tags_are_inherited() {
+        let content = r#"
+---
+- name: Tagged Play
+  hosts: all
+  tags: [web]
+  tasks:
+    - name: Configure
+      command: echo configure
+      tags: config
+    - block:
+        - name: Nested
+          command: echo nested
+  handlers:
+    - name: Restart
+      command: echo restart
+"#;
+        let temp_file = create_temp_playbook(content);
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        let play = &playbook.plays[0];
+
+        assert_eq!(play.tags, vec!["web".to_string()]);
+        assert_eq!(
+            play.tasks[0].tags,
+            vec!["config".to_string(), "web".to_string()]
+        );
+        let nested = &play.tasks[1].block.as_ref().unwrap().block[0];
+        assert_eq!(nested.tags, vec!["web".to_string()]);
+        assert_eq!(play.handlers[0].task.tags, vec!["web".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_gather_facts() {
+        let content = r#"
+---
+- name: Facts Play
+  hosts: all
+  gather_subset:
+    - "!hardware"
+  tasks:
+    - name: Refresh facts
+      setup:
+- name: No Facts Play
+  hosts: all
+  gather_facts: no
+  tasks: []
+"#;
+        let temp_file = create_temp_playbook(content);
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+
+        let play = &playbook.plays[0];
+        assert!(play.gather_facts);
+        assert_eq!(
+            play.gather_subset,
+            Some(Value::Sequence(vec![Value::String(
+                "!hardware".to_string()
+            )]))
+        );
+        assert_eq!(play.tasks[0].module, "setup");
+        assert!(play.tasks[0].args.is_empty());
+
+        assert!(!playbook.plays[1].gather_facts);
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let content = r#"
+---
+- name: Block Play
+  hosts: all
+  tasks:
+    - name: Install app
+      when: deploy
+      become: true
+      tags: app
+      vars:
+        release: "1.0"
+      block:
+        - name: Fetch
+          command: echo fetch
+          when: fetch_enabled
+      rescue:
+        - name: Report
+          debug:
+            msg: "{{ ansible_failed_task.name }} failed"
+      always:
+        - block:
+            - name: Cleanup
+              command: echo cleanup
+"#;
+        let temp_file = create_temp_playbook(content);
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        let task = &playbook.plays[0].tasks[0];
+
+        assert_eq!(task.module, "block");
+        let block = task.block.as_ref().unwrap();
+        assert_eq!(block.block.len(), 1);
+        assert_eq!(block.rescue.len(), 1);
+        assert_eq!(block.always.len(), 1);
+
+        let fetch = &block.block[0];
+        assert_eq!(
+            fetch.when,
+            Some(Value::Sequence(vec![
+                Value::String("deploy".to_string()),
+                Value::String("fetch_enabled".to_string())
+            ]))
+        );
+        assert!(fetch.is_become);
+        assert_eq!(fetch.tags, vec!["app".to_string()]);
+        assert!(fetch
+            .vars
+            .contains_key(&Value::String("release".to_string())));
+
+        let unnamed = &block.always[0];
+        assert_eq!(unnamed.name, "block");
+        let cleanup = &unnamed.block.as_ref().unwrap().block[0];
+        assert_eq!(cleanup.when, Some(Value::String("deploy".to_string())));
+        assert!(cleanup.is_become);
+    }
+
+    #[test]
+    fn test_parse_invalid_block() {
+        for content in [
+            "- name: P\n  hosts: all\n  tasks:\n    - name: T\n      command: echo\n      block: []\n",
+            "- name: P\n  hosts: all\n  tasks:\n    - name: T\n      rescue: []\n",
+            "- name: P\n  hosts: all\n  tasks:\n    - name: T\n      loop: [1]\n      block: []\n",
+        ] {
+            let temp_file = create_temp_playbook(content);
+            assert!(parse_playbook(temp_file.path().to_str().unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_report_lines() {
+        let temp_file = create_temp_playbook(
+            "- name: P\n  hosts: all\n  tasks:\n    - name: ok\n      ping:\n\n    - name: T\n      rescue: []\n",
+        );
+        let path = temp_file.path().to_str().unwrap();
+        let error = format!("{:#}", parse_playbook(path).unwrap_err());
+        assert!(error.contains(&format!("({}:1)", path)), "{}", error);
+        assert!(error.contains(&format!("({}:7)", path)), "{}", error);
+
+        let temp_file = create_temp_playbook("- name: P\n  hosts: [\n");
+        let error = format!(
+            "{:#}",
+            parse_playbook(temp_file.path().to_str().unwrap()).unwrap_err()
+        );
+        assert!(error.contains("line 3"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_playbook_without_block_entries() {
+        // Flow-style and nested lists have no `- ` entry lines to locate
+        let temp_file =
+            create_temp_playbook("\n[{name: P, hosts: all, tasks: [{name: t, command: echo}]}]\n");
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(playbook.plays[0].tasks.len(), 1);
+
+        let temp_file = create_temp_playbook("\n- - name: P\n    hosts: all\n    tasks: []\n");
+        assert!(parse_playbook(temp_file.path().to_str().unwrap()).is_ok());
+    }
+
+    fn write_file(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_parse_roles() {
+        let dir = tempfile::tempdir().unwrap();
+        let roles = dir.path().join("roles");
+        write_file(
+            &roles.join("common/tasks/main.yml"),
+            "- name: Base packages\n  command: echo base\n",
+        );
+        write_file(
+            &roles.join("web/meta/main.yml"),
+            "dependencies:\n  - common\n",
+        );
+        write_file(&roles.join("web/defaults/main.yml"), "http_port: 80\n");
+        write_file(&roles.join("web/vars/main.yml"), "web_user: www-data\n");
+        write_file(
+            &roles.join("web/tasks/main.yml"),
+            "- name: Install\n  command: echo install\n  notify: Restart web\n",
+        );
+        write_file(
+            &roles.join("web/handlers/main.yml"),
+            "- name: Restart web\n  command: echo restart\n",
+        );
+        write_file(
+            &roles.join("db/meta/main.yml"),
+            "dependencies:\n  - role: common\n",
+        );
+        write_file(
+            &roles.join("db/tasks/main.yml"),
+            "- name: Configure\n  command: echo {{ db_port }}\n",
+        );
+
+        let playbook_path = dir.path().join("site.yml");
+        write_file(
+            &playbook_path,
+            r#"
+- name: Role Play
+  hosts: all
+  roles:
+    - web
+    - role: db
+      db_port: 5433
+      when: with_db
+  tasks:
+    - name: Reuse web
+      import_role:
+        name: web
+"#,
+        );
+
+        let playbook = parse_playbook(playbook_path.to_str().unwrap()).unwrap();
+        let play = &playbook.plays[0];
+        assert_eq!(play.tasks.len(), 3);
+
+        // The shared dependency runs first, and only once
+        let web = &play.tasks[0];
+        assert_eq!(web.module, "import_role");
+        let web_tasks = &web.block.as_ref().unwrap().block;
+        assert_eq!(web_tasks[0].module, "import_role");
+        assert_eq!(
+            web_tasks[0].block.as_ref().unwrap().block[0].name,
+            "common : Base packages"
+        );
+        assert_eq!(web_tasks[1].name, "web : Install");
+        assert_eq!(
+            web_tasks[1].role_vars.get("web_user"),
+            Some(&Value::String("www-data".to_string()))
+        );
+        assert!(web_tasks[1].role_vars.contains_key("role_path"));
+
+        let db = &play.tasks[1];
+        let db_tasks = &db.block.as_ref().unwrap().block;
+        assert_eq!(db_tasks.len(), 1);
+        assert_eq!(db_tasks[0].name, "db : Configure");
+        assert_eq!(
+            db_tasks[0].role_params.get("db_port"),
+            Some(&Value::Number(5433.into()))
+        );
+        assert_eq!(db_tasks[0].when, Some(Value::String("with_db".to_string())));
+
+        // Explicit imports run the role again, its dependency already ran
+        assert_eq!(play.tasks[2].name, "Reuse web");
+        let reused = &play.tasks[2].block.as_ref().unwrap().block;
+        assert_eq!(reused.len(), 1);
+        assert_eq!(reused[0].name, "web : Install");
+
+        assert_eq!(
+            play.role_defaults.get("http_port"),
+            Some(&Value::Number(80.into()))
+        );
+        assert_eq!(play.handlers.len(), 1);
+        assert_eq!(play.handlers[0].task.name, "Restart web");
+    }
+
+    #[test]
+    fn test_parse_role_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(
+            &dir.path().join("roles/loop/meta/main.yml"),
+            "dependencies:\n  - loop\n",
+        );
+        let playbook_path = dir.path().join("site.yml");
+
+        write_file(
+            &playbook_path,
+            "- name: P\n  hosts: all\n  roles:\n    - loop\n",
+        );
+        let err = parse_playbook(playbook_path.to_str().unwrap()).unwrap_err();
+        assert!(format!("{:?}", err).contains("depends on itself"));
+
+        write_file(
+            &playbook_path,
+            "- name: P\n  hosts: all\n  roles:\n    - missing\n",
+        );
+        let err = parse_playbook(playbook_path.to_str().unwrap()).unwrap_err();
+        assert!(format!("{:?}", err).contains("Role 'missing' not found"));
+
+        write_file(
+            &playbook_path,
+            "- name: P\n  hosts: all\n  tasks:\n    - include_role:\n        name: web\n      block: []\n",
+        );
+        let err = parse_playbook(playbook_path.to_str().unwrap()).unwrap_err();
+        assert!(format!("{:?}", err).contains("cannot combine 'include_role' with 'block'"));
+    }
+
+    #[test]
+    fn test_parse_include_role() {
+        let temp_file = create_temp_playbook(
+            "- name: P\n  hosts: all\n  tasks:\n    - include_role:\n        name: \"{{ item }}\"\n      loop: [web, db]\n",
+        );
+        let playbook = parse_playbook(temp_file.path().to_str().unwrap()).unwrap();
+        let include = &playbook.plays[0].tasks[0];
+
+        // The role is only looked up when the task runs
+        assert_eq!(include.module, "include_role");
+        assert_eq!(include.name, "include_role");
+        assert!(include.block.is_none());
+        assert!(include.loop_items.is_some());
+        assert_eq!(
+            include.args.get(INCLUDE_DIR_ARG),
+            Some(&Value::String(
+                temp_file
+                    .path()
+                    .parent()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(
+            &dir.path().join("plays/web.yml"),
+            "- name: Web\n  hosts: web\n  tasks:\n    - import_tasks: tasks/setup.yml\n      tags: setup\n",
+        );
+        // Imports are resolved relative to the file that contains them
+        write_file(
+            &dir.path().join("plays/tasks/setup.yml"),
+            "- name: Prepare\n  command: echo prepare\n- import_tasks: more/extra.yml\n",
+        );
+        write_file(
+            &dir.path().join("plays/tasks/more/extra.yml"),
+            "- name: Extra\n  command: echo extra\n",
+        );
+        let playbook_path = dir.path().join("site.yml");
+        write_file(
+            &playbook_path,
+            r#"
+- name: First
+  hosts: all
+  tasks:
+    - name: Dynamic
+      include_tasks: "{{ kind }}.yml"
+- import_playbook: plays/web.yml
+"#,
+        );
+
+        let playbook = parse_playbook(playbook_path.to_str().unwrap()).unwrap();
+        assert_eq!(playbook.plays.len(), 2);
+
+        let include = &playbook.plays[0].tasks[0];
+        assert_eq!(include.module, "include_tasks");
+        assert!(include.block.is_none());
+        assert_eq!(
+            include.args.get(INCLUDE_DIR_ARG),
+            Some(&Value::String(dir.path().to_string_lossy().to_string()))
+        );
+
+        let web = &playbook.plays[1];
+        assert_eq!(web.name, "Web");
+        let imported = &web.tasks[0].block.as_ref().unwrap().block;
+        assert_eq!(imported[0].name, "Prepare");
+        assert_eq!(imported[0].tags, vec!["setup".to_string()]);
+        let nested = &imported[1].block.as_ref().unwrap().block;
+        assert_eq!(nested[0].name, "Extra");
+        assert_eq!(nested[0].tags, vec!["setup".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_import_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let playbook_path = dir.path().join("site.yml");
+
+        write_file(&playbook_path, "- import_playbook: site.yml\n");
+        let err = parse_playbook(playbook_path.to_str().unwrap()).unwrap_err();
+        assert!(format!("{:?}", err).contains("imports itself"));
+
+        write_file(&dir.path().join("loop.yml"), "- import_tasks: loop.yml\n");
+        write_file(
+            &playbook_path,
+            "- name: P\n  hosts: all\n  tasks:\n    - import_tasks: loop.yml\n",
+        );
+        let err = parse_playbook(playbook_path.to_str().unwrap()).unwrap_err();
+        assert!(format!("{:?}", err).contains("imports itself"));
+
+        write_file(
+            &playbook_path,
+            "- name: P\n  hosts: all\n  tasks:\n    - import_tasks: \"{{ name }}.yml\"\n",
+        );
+        let err = parse_playbook(playbook_path.to_str().unwrap()).unwrap_err();
+        assert!(format!("{:?}", err).contains("use include_tasks"));
+    }
+
     #[test]
     fn test_parse_invalid_yaml() {
         let content = "invalid: yaml: : syntax";
@@ -1,12 +1,23 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
 use log::{debug, error, info};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde_yaml::{Mapping, Value};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::Instant;
 
 use crate::inventory::Host;
-use crate::playbook::{Handler, Task, TaskResult};
+use crate::playbook::options::DEFAULT_FORKS;
+use crate::playbook::output;
+use crate::playbook::parser;
+use crate::playbook::serial::{self, SerialBatch};
+use crate::playbook::strategy::{self, Strategy};
+use crate::playbook::task::IncludedFile;
+use crate::playbook::vars::{HostVars, VarSource};
+use crate::playbook::{Block, Handler, PlaybookOptions, PlaybookStats, Task, TaskResult};
 
 /// Play structure representing a set of tasks to run on hosts
 #[derive(Debug, Clone)]
@@ -18,211 +29,214 @@ pub struct Play {
     pub vars: Mapping,
     pub is_become: bool, // renamed from 'become' to avoid Rust keyword
     pub become_user: String,
-    #[allow(dead_code)]
-    pub tags: Vec<String>, // Keep this for future use
+    pub tags: Vec<String>, // Also applied to every task and handler of the play
+    pub check_mode: Option<bool>, // Play-level override of the --check flag
+    pub forks: Option<usize>, // Play-level override of --forks
+    pub gather_facts: bool,
+    pub gather_subset: Option<Value>,
+    pub role_defaults: Mapping, // Defaults of the roles used by the play, lowest precedence
+    pub vars_files: Vec<(String, Mapping)>, // Variables of each file in `vars_files`
+    pub any_errors_fatal: bool, // Stop the play on every host as soon as one host fails
+    pub max_fail_percentage: Option<f64>, // Stop the play once more hosts than this have failed
+    pub serial: Vec<SerialBatch>, // Batches of hosts running the play in turn, all at once if empty
+    pub strategy: String,       // How hosts go through the tasks: "linear" or "free"
 }
 
 impl Play {
-    pub fn execute(&self, hosts: &[Host]) -> Result<()> {
+    pub fn execute(&self, hosts: &[Host]) -> Result<HashSet<String>> {
+        self.execute_with_options(hosts, &PlaybookOptions::default())
+    }
+
+    /// Run the play on its own and print its recap, see `run`
+    pub fn execute_with_options(
+        &self,
+        hosts: &[Host],
+        options: &PlaybookOptions,
+    ) -> Result<HashSet<String>> {
+        let mut stats = PlaybookStats::default();
+        let result = self.run(hosts, options, &mut stats);
+        stats.print_recap();
+        result
+    }
+
+    /// Run the play, counting every task result in `stats`, and return the names of the hosts
+    /// that failed. A failed host stops running the play while the others go on; the play
+    /// itself fails when no host is left or when `any_errors_fatal`/`max_fail_percentage` stop it.
+    pub(super) fn run(
+        &self,
+        hosts: &[Host],
+        options: &PlaybookOptions,
+        stats: &mut PlaybookStats,
+    ) -> Result<HashSet<String>> {
         let start_time = Instant::now();
         info!("PLAY [{}] on {} hosts", self.name, hosts.len());
         println!("\n{}", format!("PLAY [{}]", self.name).bold());
 
-        // Track task results for the play recap
-        let mut _ok_hosts = 0;
-        let mut failed_hosts = HashSet::new();
-        let mut changed_hosts = HashSet::new();
-        let mut _skipped_hosts = 0;
-
-        // Track which handlers have been notified
-        let mut notified_handlers: HashSet<String> = HashSet::new();
-
-        // Execute all tasks in order
-        for (task_index, task) in self.tasks.iter().enumerate() {
-            debug!(
-                "Executing task {} of {}: {}",
-                task_index + 1,
-                self.tasks.len(),
-                task.name
-            );
-
-            // 更接近ansible风格的任务标题
+        let play_check_mode = self.check_mode.unwrap_or(options.check_mode);
+        if play_check_mode {
+            stats.mark_check_mode();
             println!(
-                "\nTASK [{}] {}",
-                task.name,
-                "*".repeat(80 - task.name.len() - 8).dimmed()
+                "{}",
+                "Running in check mode: no changes will be made".dimmed()
             );
+        }
 
-            let mut task_results = Vec::new();
-
-            // Create a task with play's become settings if task doesn't override
-            let mut effective_task = task.clone();
-            if !effective_task.is_become && self.is_become {
-                effective_task.is_become = self.is_become;
-                effective_task.become_user = self.become_user.clone();
-            }
-
-            for host in hosts {
-                // Prepare host-specific variables by merging play vars with host vars
-                let mut host_vars: HashMap<String, Value> = HashMap::new();
-
-                // First add play variables
-                for (key, value) in &self.vars {
-                    if let Value::String(k) = key {
-                        host_vars.insert(k.clone(), value.clone());
-                    }
-                }
-
-                // Then add host variables (they take precedence over play vars)
-                // Add host's own variables
-                for (key, value) in &host.variables {
-                    host_vars.insert(key.clone(), Value::String(value.clone()));
-                }
+        let strategy = strategy::find(&self.strategy)
+            .ok_or_else(|| anyhow::anyhow!("Unknown strategy '{}'", self.strategy))?;
+        debug!(
+            "Using {} strategy for play '{}'",
+            strategy.name(),
+            self.name
+        );
 
-                // Add host's inherited variables (from groups)
-                for (key, value) in &host.inherited_variables {
-                    // Only add if not already set by host or play variables
-                    if !host_vars.contains_key(key) {
-                        host_vars.insert(key.clone(), Value::String(value.clone()));
-                    }
-                }
-
-                // Add standard ansible facts for the host
-                host_vars.insert(
-                    "ansible_hostname".to_string(),
-                    Value::String(host.hostname.clone()),
-                );
-                host_vars.insert(
-                    "inventory_hostname".to_string(),
-                    Value::String(host.name.clone()),
-                );
-                host_vars.insert(
-                    "ansible_host".to_string(),
-                    Value::String(host.hostname.clone()),
-                );
-                host_vars.insert("ansible_port".to_string(), Value::Number(host.port.into()));
+        // Run each task on up to `forks` hosts at a time
+        let forks = self
+            .forks
+            .or(options.forks)
+            .unwrap_or(DEFAULT_FORKS)
+            .clamp(1, hosts.len().max(1));
+        debug!("Using {} forks for play '{}'", forks, self.name);
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(forks)
+            .build()
+            .context("Failed to create worker pool for hosts")?;
 
+        for host in hosts {
+            stats.add_host(&host.name);
+        }
+        let mut state = PlayState {
+            pool: &pool,
+            options,
+            check_mode: play_check_mode,
+            // Per-host variables, kept for the whole play so registered results reach later tasks
+            hosts_vars: hosts
+                .iter()
+                .map(|host| self.host_vars(host, &options.extra_vars))
+                .collect(),
+            stats,
+            notified_handlers: HashSet::new(),
+            included_handlers: Vec::new(),
+            batch_size: hosts.len(),
+            abort: None,
+            shared_failures: None,
+        };
+
+        // With `serial`, each batch of hosts runs the whole play before the next one starts
+        let batches = serial::batches(&self.serial, hosts.len());
+        let mut done_hosts = Vec::new();
+        let mut live_hosts = Vec::new();
+        let mut all_failed_batch = None;
+        for (batch_index, batch) in batches.iter().enumerate() {
+            if batch_index > 0 {
+                println!("\n{}", format!("PLAY [{}]", self.name).bold());
+            }
+            if batches.len() > 1 {
                 debug!(
-                    "Host {} has {} variables available",
-                    host.name,
-                    host_vars.len()
+                    "Running batch {}/{} of play '{}' on {} hosts",
+                    batch_index + 1,
+                    batches.len(),
+                    self.name,
+                    batch.len()
                 );
+            }
 
-                // Execute the task on this host with merged variables
-                match effective_task.execute(host, &host_vars) {
-                    Ok(result) => {
-                        // Update host status for recap
-                        if result.failed {
-                            failed_hosts.insert(host.name.clone());
-                        } else if result.skipped {
-                            _skipped_hosts += 1;
-                        } else {
-                            _ok_hosts += 1;
-                            if result.changed {
-                                changed_hosts.insert(host.name.clone());
-                            }
-                        }
-
-                        // Store the result for registered variables
-                        if let Some(register_var) = &effective_task.register {
-                            // Convert TaskResult to a Value for storage
-                            let mut result_value = Mapping::new();
-                            result_value.insert(
-                                Value::String("changed".to_string()),
-                                Value::Bool(result.changed),
-                            );
-                            result_value.insert(
-                                Value::String("failed".to_string()),
-                                Value::Bool(result.failed),
-                            );
-                            result_value.insert(
-                                Value::String("skipped".to_string()),
-                                Value::Bool(result.skipped),
-                            );
-
-                            if !result.msg.is_empty() {
-                                result_value.insert(
-                                    Value::String("msg".to_string()),
-                                    Value::String(result.msg.clone()),
-                                );
-                            }
+            let batch_live_hosts = self.run_batch(strategy, &mut state, hosts, batch);
+            done_hosts.extend(batch.iter().copied());
+            live_hosts.extend(batch_live_hosts.iter().copied());
+            if batch_live_hosts.is_empty() || state.abort.is_some() {
+                println!("\n{}", "NO MORE HOSTS LEFT".red().bold());
+                if batch_live_hosts.is_empty() {
+                    all_failed_batch = Some(batch_index);
+                }
+                break;
+            }
+        }
 
-                            // Add any module-specific values
-                            for (k, v) in &result.values {
-                                result_value.insert(Value::String(k.clone()), v.clone());
-                            }
+        let elapsed = start_time.elapsed();
+        let execution_time = format!("{:.2}s", elapsed.as_secs_f64());
 
-                            // Store in host variables for next tasks (registered variables are host-specific)
-                            host_vars.insert(register_var.clone(), Value::Mapping(result_value));
-                        }
+        info!("Play '{}' completed in {}", self.name, execution_time);
 
-                        // Check for handler notifications
-                        if result.changed && !effective_task.notify.is_empty() {
-                            for handler_name in &effective_task.notify {
-                                debug!(
-                                    "Handler '{}' notified by task '{}'",
-                                    handler_name, effective_task.name
-                                );
-                                notified_handlers.insert(handler_name.clone());
-                            }
-                        }
+        println!("\nPlay execution completed in {}", execution_time);
 
-                        task_results.push(result);
-                    }
-                    Err(e) => {
-                        error!("Task execution failed on host {}: {}", host.name, e);
-
-                        // Create a failed result
-                        let mut result = TaskResult::new(&host.name);
-                        result.failed = true;
-                        result.msg = format!("Task execution error: {}", e);
-
-                        // Explicitly print the failure result to console
-                        let error_time = "0.00s"; // Execution time is not available here
-                        crate::playbook::task::print_task_result(
-                            &host.name,
-                            &effective_task.name,
-                            &result,
-                            error_time,
-                        );
-
-                        failed_hosts.insert(host.name.clone());
-
-                        task_results.push(result);
-
-                        // Skip further tasks for this host if there's an unhandled error
-                        if !effective_task.ignore_errors {
-                            // In a more complete implementation, we would track failed hosts
-                            // and skip them in subsequent tasks
-                        }
-                    }
-                }
+        if let Some(reason) = state.abort {
+            return Err(anyhow::anyhow!(reason));
+        }
+        match all_failed_batch {
+            Some(_) if batches.len() == 1 => {
+                return Err(anyhow::anyhow!("All hosts of play '{}' failed", self.name))
             }
-
-            // Check if the play should fail based on task results
-            let failed_count = task_results.iter().filter(|r| r.failed).count();
-            if failed_count > 0 && !effective_task.ignore_errors {
+            Some(batch_index) => {
                 return Err(anyhow::anyhow!(
-                    "Task '{}' failed on {} hosts",
-                    effective_task.name,
-                    failed_count
-                ));
+                    "All hosts of batch {}/{} of play '{}' failed",
+                    batch_index + 1,
+                    batches.len(),
+                    self.name
+                ))
+            }
+            None => {}
+        }
+
+        Ok(done_hosts
+            .into_iter()
+            .filter(|index| !live_hosts.contains(index))
+            .map(|index| hosts[index].name.clone())
+            .collect())
+    }
+
+    /// Run the tasks and then the notified handlers of the play on a batch of hosts,
+    /// returning the hosts that are still running at the end
+    fn run_batch(
+        &self,
+        strategy: &dyn Strategy,
+        state: &mut PlayState,
+        hosts: &[Host],
+        batch: &[usize],
+    ) -> Vec<usize> {
+        state.batch_size = batch.len();
+        state.notified_handlers.clear();
+        state.included_handlers.clear();
+
+        // Facts are gathered like a regular first task so they reach every later one
+        let fact_task = self.gather_facts.then(|| self.fact_gathering_task());
+        let mut live_hosts = batch.to_vec();
+
+        for tasks in [fact_task.as_slice(), self.tasks.as_slice()] {
+            let failures = strategy.run_tasks(self, state, hosts, tasks, &live_hosts);
+            live_hosts.retain(|index| !failures.iter().any(|f| f.host == *index));
+            if state.abort.is_some() {
+                live_hosts.clear();
+            }
+            if live_hosts.is_empty() {
+                return live_hosts;
             }
         }
 
-        // Run handlers that were notified
-        if !notified_handlers.is_empty() {
+        // Run handlers that were notified, on the hosts still running the play
+        if !state.notified_handlers.is_empty() {
             info!("Running notified handlers");
             println!("\n{}", "RUNNING HANDLERS".bold());
             println!(
                 "{}\n",
-                format!("{} handlers to run", notified_handlers.len()).dimmed()
+                format!("{} handlers to run", state.notified_handlers.len()).dimmed()
             );
 
-            for handler in &self.handlers {
+            // Handlers of roles included at run time come after the play's own, which win
+            // when the same role is also imported
+            let included_handlers = std::mem::take(&mut state.included_handlers);
+            let handlers = self
+                .handlers
+                .iter()
+                .chain(included_handlers.iter().filter(|handler| {
+                    !self
+                        .handlers
+                        .iter()
+                        .any(|own| own.task.name == handler.task.name)
+                }));
+            for handler in handlers {
                 let handler_name = &handler.task.name;
 
-                if notified_handlers.contains(handler_name) {
+                if state.notified_handlers.contains(handler_name) {
                     debug!("Running notified handler: {}", handler_name);
 
                     // Create a handler with play's become settings if it doesn't override
@@ -231,87 +245,613 @@ impl Play {
                         effective_handler.is_become = self.is_become;
                         effective_handler.become_user = self.become_user.clone();
                     }
+                    effective_handler.check_mode =
+                        Some(effective_handler.check_mode.unwrap_or(state.check_mode));
+
+                    // A host whose handler fails doesn't run the next handlers
+                    let vars: Vec<_> = live_hosts
+                        .iter()
+                        .map(|&index| {
+                            state.hosts_vars[index].merged_with(&effective_handler.var_layers())
+                        })
+                        .collect();
+                    effective_handler.vars.clear();
+                    let targets: Vec<_> = live_hosts
+                        .iter()
+                        .map(|&index| &hosts[index])
+                        .zip(&vars)
+                        .collect();
+                    let results =
+                        run_on_hosts(state.pool, &effective_handler, &targets, Vec::new());
+                    let mut failed = Vec::new();
+                    for (&index, result) in live_hosts.iter().zip(results) {
+                        state.stats.record(&hosts[index].name, &result);
+                        if result.failed || result.unreachable {
+                            failed.push(index);
+                        }
+                    }
+                    live_hosts.retain(|index| !failed.contains(index));
+                }
+            }
+        }
 
-                    for host in hosts {
-                        // Prepare host-specific variables for handlers too
-                        let mut handler_vars: HashMap<String, Value> = HashMap::new();
+        live_hosts
+    }
 
-                        // Add play variables
-                        for (key, value) in &self.vars {
-                            if let Value::String(k) = key {
-                                handler_vars.insert(k.clone(), value.clone());
-                            }
-                        }
+    /// Run a list of tasks on the given hosts (indexes into `hosts`).
+    /// A host that fails a task skips the rest of the list. The play's own task lists
+    /// (`play_level`) also stop on every host when the play's failure limits are hit.
+    pub(super) fn run_tasks(
+        &self,
+        state: &mut PlayState,
+        hosts: &[Host],
+        tasks: &[Task],
+        active: &[usize],
+        play_level: bool,
+    ) -> Vec<HostFailure> {
+        let mut active = active.to_vec();
+        let mut failures = Vec::new();
+
+        for task in tasks {
+            if active.is_empty() || state.shared_failures.is_some_and(|shared| shared.aborted()) {
+                break;
+            }
+            // Block tasks are selected one by one, with the block's tags inherited
+            if task.block.is_none() && !state.options.selects_tags(&task.tags) {
+                debug!("Skipping task '{}' not selected by tags", task.name);
+                continue;
+            }
 
-                        // Add host variables
-                        for (key, value) in &host.variables {
-                            handler_vars.insert(key.clone(), Value::String(value.clone()));
-                        }
+            let task_failures = match &task.block {
+                Some(block) => self.run_block(state, hosts, block, &active),
+                None if task.module == "include_tasks" || task.module == "include_role" => {
+                    self.run_include(state, hosts, task, &active)
+                }
+                None => self.run_task(state, hosts, task, &active),
+            };
+            if task_failures.is_empty() {
+                continue;
+            }
 
-                        // Add host's inherited variables
-                        for (key, value) in &host.inherited_variables {
-                            if !handler_vars.contains_key(key) {
-                                handler_vars.insert(key.clone(), Value::String(value.clone()));
-                            }
-                        }
+            let before = active.len();
+            active.retain(|index| !task_failures.iter().any(|f| f.host == *index));
+            let failed = before - active.len();
+            failures.extend(task_failures);
+            if play_level {
+                state.abort = match state.shared_failures {
+                    Some(shared) => shared.record(self, &task.name, state.batch_size, failed),
+                    None => self.failure_limit_reached(&task.name, state.batch_size, active.len()),
+                };
+                if state.abort.is_some() {
+                    break;
+                }
+            }
+        }
 
-                        if let Err(e) = effective_handler.execute(host, &handler_vars) {
-                            error!(
-                                "Handler '{}' failed on host {}: {}",
-                                handler_name, host.name, e
-                            );
+        failures
+    }
 
-                            // Handlers failures are usually considered non-fatal
-                            // but in a more complete implementation, we might make this configurable
+    /// Whether `any_errors_fatal` or `max_fail_percentage` stop the play after hosts failed a task
+    pub(super) fn failure_limit_reached(
+        &self,
+        task_name: &str,
+        total: usize,
+        remaining: usize,
+    ) -> Option<String> {
+        if self.any_errors_fatal {
+            return Some(format!(
+                "Task '{}' failed and any_errors_fatal is set for play '{}'",
+                task_name, self.name
+            ));
+        }
+
+        let max_fail_percentage = self.max_fail_percentage?;
+        let failed = total - remaining;
+        let failed_percentage = failed as f64 * 100.0 / total as f64;
+        (failed_percentage > max_fail_percentage).then(|| {
+            format!(
+                "{} of {} hosts failed in play '{}', more than max_fail_percentage {}%",
+                failed, total, self.name, max_fail_percentage
+            )
+        })
+    }
+
+    /// Run a block, then its rescue section on the hosts where it failed, then its always section
+    fn run_block(
+        &self,
+        state: &mut PlayState,
+        hosts: &[Host],
+        block: &Block,
+        active: &[usize],
+    ) -> Vec<HostFailure> {
+        let mut failures = self.run_tasks(state, hosts, &block.block, active, false);
+
+        if !failures.is_empty() && !block.rescue.is_empty() {
+            let rescued: Vec<usize> = failures.iter().map(|f| f.host).collect();
+            for failure in &failures {
+                let mut failed_task = Mapping::new();
+                failed_task.insert(
+                    Value::String("name".to_string()),
+                    Value::String(failure.task.clone()),
+                );
+                failed_task.insert(
+                    Value::String("action".to_string()),
+                    Value::String(failure.module.clone()),
+                );
+
+                let host_vars = &mut state.hosts_vars[failure.host];
+                host_vars.insert(
+                    VarSource::Registered,
+                    "ansible_failed_task",
+                    Value::Mapping(failed_task),
+                );
+                host_vars.insert(
+                    VarSource::Registered,
+                    "ansible_failed_result",
+                    failure.result.registered_value(),
+                );
+            }
+
+            failures = self.run_tasks(state, hosts, &block.rescue, &rescued, false);
+
+            for index in rescued {
+                let host_vars = &mut state.hosts_vars[index];
+                host_vars.remove(&VarSource::Registered, "ansible_failed_task");
+                host_vars.remove(&VarSource::Registered, "ansible_failed_result");
+                if !failures.iter().any(|f| f.host == index) {
+                    debug!("Host {} recovered by rescue tasks", hosts[index].name);
+                    state.stats.rescue(&hosts[index].name);
+                }
+            }
+        }
+
+        for failure in self.run_tasks(state, hosts, &block.always, active, false) {
+            if !failures.iter().any(|f| f.host == failure.host) {
+                failures.push(failure);
+            }
+        }
+
+        failures
+    }
+
+    /// Run an `include_tasks` or `include_role` task: each host runs the tasks of the file(s)
+    /// or role(s) it includes, hosts including the same one for the same loop item run them together
+    fn run_include(
+        &self,
+        state: &mut PlayState,
+        hosts: &[Host],
+        task: &Task,
+        active: &[usize],
+    ) -> Vec<HostFailure> {
+        debug!(
+            "Including tasks for '{}' on {} hosts",
+            task.name,
+            active.len()
+        );
+        // The header is printed together with the first lines that follow it, so hosts of
+        // the free strategy don't split it from them
+        let ((mut failures, includes), mut pending) = output::capture(|| {
+            let mut failures = Vec::new();
+            let mut includes: Vec<(IncludedFile, Vec<usize>)> = Vec::new();
+            for &index in active {
+                match task.included_files(&state.hosts_vars[index].merged_with(&task.var_layers()))
+                {
+                    Ok(files) => {
+                        for file in files {
+                            match includes.iter_mut().find(|(included, _)| *included == file) {
+                                Some((_, host_indexes)) => host_indexes.push(index),
+                                None => includes.push((file, vec![index])),
+                            }
                         }
                     }
+                    Err(e) => failures.push(include_failure(state, hosts, task, index, &e)),
                 }
             }
-        }
+            (failures, includes)
+        });
+        pending.insert(0, task_header(&task.name));
+
+        for (file, host_indexes) in includes {
+            let names: Vec<&str> = host_indexes
+                .iter()
+                .map(|&index| hosts[index].name.as_str())
+                .collect();
+            pending.push(format!(
+                "{} {} for {}",
+                "included:".cyan(),
+                file.path.display(),
+                names.join(", ")
+            ));
+            output::emit_block(std::mem::take(&mut pending));
 
-        let elapsed = start_time.elapsed();
-        let execution_time = format!("{:.2}s", elapsed.as_secs_f64());
+            let loaded = if task.module == "include_role" {
+                parser::parse_included_role(&file.path, &task.args)
+            } else {
+                parser::parse_included_tasks(&file.path).map(|(tasks, handlers)| {
+                    let role_vars = task.role_vars.clone();
+                    (tasks, handlers, role_vars, task.role_defaults.clone())
+                })
+            };
+            let (tasks, handlers, role_vars, role_defaults) = match loaded {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    let (load_failures, lines) = output::capture(|| {
+                        host_indexes
+                            .into_iter()
+                            .map(|index| include_failure(state, hosts, task, index, &e))
+                            .collect::<Vec<_>>()
+                    });
+                    output::emit_block(lines);
+                    failures.extend(load_failures);
+                    continue;
+                }
+            };
+
+            // Handlers of included roles can be notified like the play's own
+            state.add_included_handlers(handlers);
+
+            // The include's vars and loop variables are visible to the included tasks
+            let mut included = Block {
+                block: tasks,
+                ..Default::default()
+            };
+            let mut vars_task = Task::new(&task.name, &task.module, Mapping::new());
+            vars_task.vars = task.vars.clone();
+            vars_task.role_vars = role_vars;
+            vars_task.role_defaults = role_defaults;
+            if task.module == "include_tasks" {
+                vars_task.role_params = task.role_params.clone();
+            }
+            if let Some((item_index, item)) = &file.item {
+                let loop_var = task.loop_var_name.as_deref().unwrap_or("item");
+                vars_task
+                    .vars
+                    .insert(Value::String(loop_var.to_string()), item.clone());
+                if let Some(index_var) = &task.index_var_name {
+                    vars_task.vars.insert(
+                        Value::String(index_var.clone()),
+                        Value::Number((*item_index).into()),
+                    );
+                }
+            }
+            included.inherit_from(&vars_task);
+
+            // A host that failed an earlier include of this task does not run the next ones
+            let targets: Vec<usize> = host_indexes
+                .into_iter()
+                .filter(|index| !failures.iter().any(|f: &HostFailure| f.host == *index))
+                .collect();
+            failures.extend(self.run_tasks(state, hosts, &included.block, &targets, false));
+        }
 
-        info!("Play '{}' completed in {}", self.name, execution_time);
+        failures
+    }
 
-        // Print a more detailed play recap similar to ad-hoc output
-        println!("\n{}", "PLAY RECAP".bold());
-        println!("{}", "*".repeat(80).dimmed());
+    /// Run a single task on the given hosts and record its results
+    fn run_task(
+        &self,
+        state: &mut PlayState,
+        hosts: &[Host],
+        task: &Task,
+        active: &[usize],
+    ) -> Vec<HostFailure> {
+        debug!("Executing task '{}' on {} hosts", task.name, active.len());
+        // Hosts running on their own print the header with their output, so that other
+        // hosts' output can't come in between
+        let header = task_header(&task.name);
+        let leading = if state.shared_failures.is_some() {
+            vec![header]
+        } else {
+            output::emit_block([header]);
+            Vec::new()
+        };
+
+        // Create a task with play's become settings if task doesn't override
+        let mut effective_task = task.clone();
+        if !effective_task.is_become && self.is_become {
+            effective_task.is_become = self.is_become;
+            effective_task.become_user = self.become_user.clone();
+        }
+        effective_task.check_mode = Some(effective_task.check_mode.unwrap_or(state.check_mode));
+
+        // All hosts finish this task before the next one starts. The task vars are merged
+        // below the registered and extra vars rather than over every variable.
+        let vars: Vec<_> = active
+            .iter()
+            .map(|&index| state.hosts_vars[index].merged_with(&effective_task.var_layers()))
+            .collect();
+        effective_task.vars.clear();
+        let targets: Vec<_> = active
+            .iter()
+            .map(|&index| &hosts[index])
+            .zip(&vars)
+            .collect();
+        let task_results = run_on_hosts(state.pool, &effective_task, &targets, leading);
+
+        let mut failures = Vec::new();
+        for (&index, result) in active.iter().zip(task_results) {
+            let host = &hosts[index];
+            let host_vars = &mut state.hosts_vars[index];
+
+            state.stats.record(&host.name, &result);
+
+            if let Some(Value::Mapping(facts)) = result.values.get("ansible_facts") {
+                merge_facts(host_vars, facts);
+            }
 
-        // 更接近ansible风格的recap输出
-        for host in hosts {
-            let mut host_attrs = Vec::new();
+            // Store the result for registered variables
+            if let Some(register_var) = &effective_task.register {
+                // Registered variables are host-specific and visible to the following tasks
+                host_vars.insert(
+                    VarSource::Registered,
+                    register_var,
+                    result.registered_value(),
+                );
+            }
 
-            if failed_hosts.contains(&host.name) {
-                host_attrs.push(format!("{}={}", "failed".red().bold(), 1));
-            } else {
-                host_attrs.push(format!("{}={}", "ok".green().bold(), 1));
+            // Check for handler notifications
+            if result.changed && !effective_task.notify.is_empty() {
+                for handler_name in &effective_task.notify {
+                    debug!(
+                        "Handler '{}' notified by task '{}'",
+                        handler_name, effective_task.name
+                    );
+                    state.notified_handlers.insert(handler_name.clone());
+                }
             }
 
-            if changed_hosts.contains(&host.name) {
-                host_attrs.push(format!("{}={}", "changed".yellow().bold(), 1));
+            // Unreachable hosts stop even when errors are ignored
+            if result.failed || result.unreachable {
+                failures.push(HostFailure {
+                    host: index,
+                    task: effective_task.name.clone(),
+                    module: effective_task.module.clone(),
+                    result,
+                });
             }
+        }
+
+        failures
+    }
+
+    /// The implicit setup task run when `gather_facts` is enabled
+    fn fact_gathering_task(&self) -> Task {
+        let mut args = Mapping::new();
+        if let Some(subset) = &self.gather_subset {
+            args.insert(Value::String("gather_subset".to_string()), subset.clone());
+        }
+        let mut task = Task::new("Gathering Facts", "setup", args);
+        task.tags = vec!["always".to_string()];
+        task
+    }
+
+    /// The variables of a host at the start of the play, by source
+    pub(super) fn host_vars(&self, host: &Host, extra_vars: &Mapping) -> HostVars {
+        let mut host_vars = HostVars::default();
+        host_vars.add_layer(VarSource::RoleDefaults, self.role_defaults.clone());
 
-            if _skipped_hosts > 0 {
-                host_attrs.push(format!("{}={}", "skipped".yellow().bold(), 1));
+        for (group, vars) in &host.group_variables {
+            host_vars.add_layer(
+                VarSource::InventoryGroup(group.clone()),
+                inventory_vars(vars),
+            );
+        }
+        for (group, vars) in &host.group_file_variables {
+            host_vars.add_layer(VarSource::GroupVarsFile(group.clone()), vars.clone());
+        }
+
+        // The connection details of the host are visible like inventory variables
+        let mut host_inventory_vars = inventory_vars(&host.variables);
+        for (key, value) in [
+            ("inventory_hostname", Value::String(host.name.clone())),
+            ("ansible_hostname", Value::String(host.hostname.clone())),
+            ("ansible_host", Value::String(host.hostname.clone())),
+            ("ansible_port", Value::Number(host.port.into())),
+        ] {
+            host_inventory_vars.insert(Value::String(key.to_string()), value);
+        }
+        host_vars.add_layer(VarSource::InventoryHost, host_inventory_vars);
+        host_vars.add_layer(VarSource::HostVarsFile, host.file_variables.clone());
+
+        host_vars.add_layer(VarSource::PlayVars, self.vars.clone());
+        for (file, vars) in &self.vars_files {
+            host_vars.add_layer(VarSource::VarsFile(file.clone()), vars.clone());
+        }
+        host_vars.add_layer(VarSource::ExtraVars, extra_vars.clone());
+
+        host_vars
+    }
+}
+
+fn inventory_vars(vars: &HashMap<String, Value>) -> Mapping {
+    vars.iter()
+        .map(|(key, value)| (Value::String(key.clone()), value.clone()))
+        .collect()
+}
+
+/// Mutable state of a play while its tasks are running
+pub(super) struct PlayState<'a> {
+    pub(super) pool: &'a ThreadPool,
+    pub(super) options: &'a PlaybookOptions,
+    pub(super) check_mode: bool,
+    pub(super) hosts_vars: Vec<HostVars>,
+    pub(super) stats: &'a mut PlaybookStats,
+    pub(super) notified_handlers: HashSet<String>,
+    pub(super) included_handlers: Vec<Handler>, // Handlers of the roles included at run time
+    pub(super) batch_size: usize, // Hosts in the batch being run, for max_fail_percentage
+    pub(super) abort: Option<String>, // Why the play stopped on every host
+    pub(super) shared_failures: Option<&'a SharedFailures>, // Set when hosts run on their own
+}
+
+impl PlayState<'_> {
+    /// Keep the handlers of an included role, once per handler name even when the role is
+    /// included again
+    pub(super) fn add_included_handlers(&mut self, handlers: Vec<Handler>) {
+        for handler in handlers {
+            if !self
+                .included_handlers
+                .iter()
+                .any(|included| included.task.name == handler.task.name)
+            {
+                self.included_handlers.push(handler);
             }
+        }
+    }
+}
 
-            // 添加执行时间信息
-            host_attrs.push(format!(
-                "{}={:.2}s",
-                "time".cyan(),
-                elapsed.as_secs_f64() / hosts.len() as f64
-            ));
+/// The failures of hosts that each run the task list on their own, shared between them so the
+/// play's failure limits stop every host before its next task
+#[derive(Default)]
+pub(super) struct SharedFailures {
+    failed_hosts: AtomicUsize,
+    aborted: AtomicBool,
+    reason: Mutex<Option<String>>,
+}
 
-            println!("{:<30} : {}", host.name.bold(), host_attrs.join("  "));
+impl SharedFailures {
+    /// Start from the hosts of the batch that already failed, such as while gathering facts
+    pub(super) fn new(failed_hosts: usize) -> Self {
+        SharedFailures {
+            failed_hosts: AtomicUsize::new(failed_hosts),
+            ..Default::default()
         }
+    }
 
-        println!("\nPlay execution completed in {}", execution_time);
+    pub(super) fn aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Why the play stopped, if it did
+    pub(super) fn reason(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+
+    /// Count hosts that failed a task, returning why the play stops if that hits its limits
+    fn record(&self, play: &Play, task_name: &str, total: usize, failed: usize) -> Option<String> {
+        let failed = self.failed_hosts.fetch_add(failed, Ordering::SeqCst) + failed;
+        let reason = play.failure_limit_reached(task_name, total, total.saturating_sub(failed))?;
+        let mut stored = self.reason.lock().unwrap();
+        self.aborted.store(true, Ordering::SeqCst);
+        Some(stored.get_or_insert(reason).clone())
+    }
+}
+
+/// A host that failed a task and stopped running the rest of its task list
+pub(super) struct HostFailure {
+    pub(super) host: usize,
+    pub(super) task: String,
+    pub(super) module: String,
+    pub(super) result: TaskResult,
+}
+
+fn task_header(name: &str) -> String {
+    // 更接近ansible风格的任务标题
+    format!(
+        "\nTASK [{}] {}",
+        name,
+        "*".repeat(80usize.saturating_sub(name.len() + 8)).dimmed()
+    )
+}
 
-        Ok(())
+/// Record a host whose `include_tasks` or `include_role` could not be resolved or loaded
+fn include_failure(
+    state: &mut PlayState,
+    hosts: &[Host],
+    task: &Task,
+    index: usize,
+    error: &anyhow::Error,
+) -> HostFailure {
+    let host = &hosts[index];
+    error!("Include failed on host {}: {:#}", host.name, error);
+
+    let mut result = TaskResult::new(&host.name);
+    result.failed = true;
+    let included = if task.module == "include_role" {
+        "role"
+    } else {
+        "tasks"
+    };
+    result.msg = format!("Failed to include {}: {:#}", included, error);
+    crate::playbook::task::print_task_result(&host.name, &task.name, &result, "0.00s");
+    state.stats.record(&host.name, &result);
+
+    HostFailure {
+        host: index,
+        task: task.name.clone(),
+        module: task.module.clone(),
+        result,
     }
 }
 
+/// Run a task on every target host using the worker pool and print each host's output as one
+/// block, after the `leading` lines. Results are returned in the same order as `targets`.
+fn run_on_hosts(
+    pool: &ThreadPool,
+    task: &Task,
+    targets: &[(&Host, &HashMap<String, Value>)],
+    leading: Vec<String>,
+) -> Vec<TaskResult> {
+    let outcomes: Vec<(TaskResult, Vec<String>)> = pool.install(|| {
+        targets
+            .par_iter()
+            .map(|(host, host_vars)| output::capture(|| run_on_host(task, host, host_vars)))
+            .collect()
+    });
+
+    let mut block = leading;
+    let results = outcomes
+        .into_iter()
+        .map(|(result, lines)| {
+            block.extend(lines);
+            result
+        })
+        .collect();
+    output::emit_block(block);
+    results
+}
+
+/// Execute a task on a single host, turning execution errors into a failed result
+fn run_on_host(task: &Task, host: &Host, host_vars: &HashMap<String, Value>) -> TaskResult {
+    match task.execute(host, host_vars) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Task execution failed on host {}: {}", host.name, e);
+
+            // Create a failed result
+            let mut result = TaskResult::new(&host.name);
+            result.failed = true;
+            result.msg = format!("Task execution error: {}", e);
+
+            // Explicitly print the failure result to console
+            let error_time = "0.00s"; // Execution time is not available here
+            crate::playbook::task::print_task_result(&host.name, &task.name, &result, error_time);
+
+            result
+        }
+    }
+}
+
+/// Expose gathered facts both under `ansible_facts` and as top-level `ansible_*` variables
+fn merge_facts(host_vars: &mut HostVars, facts: &Mapping) {
+    let mut all_facts = match host_vars.remove(&VarSource::Facts, "ansible_facts") {
+        Some(Value::Mapping(existing)) => existing,
+        _ => Mapping::new(),
+    };
+
+    for (key, value) in facts {
+        if let Value::String(name) = key {
+            host_vars.insert(
+                VarSource::Facts,
+                &format!("ansible_{}", name),
+                value.clone(),
+            );
+        }
+        all_facts.insert(key.clone(), value.clone());
+    }
+
+    host_vars.insert(VarSource::Facts, "ansible_facts", Value::Mapping(all_facts));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -332,6 +872,16 @@ mod tests {
             is_become: false,
             become_user: "root".to_string(),
             tags: Vec::new(),
+            check_mode: None,
+            forks: None,
+            gather_facts: false,
+            gather_subset: None,
+            role_defaults: Mapping::new(),
+            vars_files: Vec::new(),
+            any_errors_fatal: false,
+            max_fail_percentage: None,
+            serial: Vec::new(),
+            strategy: "linear".to_string(),
         }
     }
 
@@ -350,12 +900,23 @@ mod tests {
             become_user: "root".to_string(),
             register: None,
             when: None,
+            changed_when: None,
+            failed_when: None,
+            until: None,
+            retries: None,
+            delay: None,
             notify: Vec::new(),
-            ignore_errors: false,
+            ignore_errors: None,
             tags: Vec::new(),
             loop_items: None,
             loop_var_name: None,
             index_var_name: None,
+            check_mode: None,
+            vars: Mapping::new(),
+            role_vars: Mapping::new(),
+            role_params: Mapping::new(),
+            role_defaults: Mapping::new(),
+            block: None,
         }
     }
 
@@ -388,7 +949,7 @@ mod tests {
     fn test_play_execute_ignore_errors() {
         let mut play = create_test_play();
         let mut task = create_command_task("Fail Task", "false");
-        task.ignore_errors = true;
+        task.ignore_errors = Some(true);
         play.tasks.push(task);
         play.tasks
             .push(create_command_task("Success Task", "echo success"));
@@ -415,4 +976,693 @@ mod tests {
         // In a real test we'd check if it ran, but here we just check it didn't crash
         // and returned success (skipped tasks are successful)
     }
+
+    #[test]
+    fn test_play_execute_hosts_in_parallel() {
+        let mut play = create_test_play();
+        play.forks = Some(3);
+        play.tasks
+            .push(create_command_task("Slow Task", "sleep 0.5"));
+
+        let hosts: Vec<Host> = ["node1", "node2", "node3"]
+            .iter()
+            .map(|name| create_test_host(name, "localhost", 22, None, None))
+            .collect();
+
+        let start = Instant::now();
+        let result = play.execute(&hosts);
+
+        assert!(result.is_ok());
+        // Sequential execution would take at least 1.5s
+        assert!(start.elapsed().as_secs_f64() < 1.4);
+    }
+
+    #[test]
+    fn test_play_extra_vars_override_other_vars() {
+        let mut play = create_test_play();
+        play.vars.insert(
+            Value::String("version".to_string()),
+            Value::String("play".to_string()),
+        );
+
+        // Each of these tasks fails if anything but the extra var is visible
+        let mut task_vars = create_command_task("Task vars", "false");
+        task_vars.vars.insert(
+            Value::String("version".to_string()),
+            Value::String("task".to_string()),
+        );
+        task_vars.when = Some(Value::String("version != '1.10'".to_string()));
+        play.tasks.push(task_vars);
+
+        let mut register_task = create_command_task("Register", "echo hello");
+        register_task.register = Some("version".to_string());
+        play.tasks.push(register_task);
+
+        let mut check_task = create_command_task("Check", "false");
+        check_task.when = Some(Value::String("version != '1.10'".to_string()));
+        play.tasks.push(check_task);
+
+        let mut options = PlaybookOptions::default();
+        options.extra_vars.insert(
+            Value::String("version".to_string()),
+            Value::String("1.10".to_string()),
+        );
+        let hosts = vec![create_local_host()];
+        assert!(play.execute_with_options(&hosts, &options).is_ok());
+        assert!(play.execute(&hosts).is_err());
+    }
+
+    #[test]
+    fn test_play_role_vars_and_params_precedence() {
+        let mut play = create_test_play();
+        let mut register_task = create_command_task("Register", "echo hello");
+        register_task.register = Some("port".to_string());
+        play.tasks.push(register_task);
+
+        // Fails if the registered result shadows the role parameter, or the role var the task var
+        let mut check_task = create_command_task("Check", "false");
+        check_task.role_params.insert(
+            Value::String("port".to_string()),
+            Value::Number(8080.into()),
+        );
+        for (vars, user) in [
+            (&mut check_task.role_vars, "role"),
+            (&mut check_task.vars, "task"),
+        ] {
+            vars.insert(
+                Value::String("user".to_string()),
+                Value::String(user.to_string()),
+            );
+        }
+        check_task.when = Some(Value::String("port != 8080 or user != 'task'".to_string()));
+        play.tasks.push(check_task);
+
+        assert!(play.execute(&[create_local_host()]).is_ok());
+    }
+
+    #[test]
+    fn test_play_check_mode_is_recorded_for_recap() {
+        let mut play = create_test_play();
+        play.tasks.push(create_command_task("Echo", "echo hello"));
+        let hosts = vec![create_local_host()];
+        let options = PlaybookOptions::default();
+
+        let mut stats = PlaybookStats::default();
+        play.run(&hosts, &options, &mut stats).unwrap();
+        assert!(!stats.check_mode());
+
+        // The play's own check_mode counts even without --check
+        play.check_mode = Some(true);
+        let mut play_stats = PlaybookStats::default();
+        play.run(&hosts, &options, &mut play_stats).unwrap();
+        stats.merge(play_stats);
+        assert!(stats.check_mode());
+    }
+
+    #[test]
+    fn test_play_registered_vars_persist_across_tasks() {
+        let mut play = create_test_play();
+        let mut register_task = create_command_task("Register", "echo hello");
+        register_task.register = Some("greeting".to_string());
+        play.tasks.push(register_task);
+
+        // Only runs (and fails) if the registered result is visible to this task
+        let mut check_task = create_command_task("Use registered", "false");
+        check_task.when = Some(Value::String("greeting.stdout is defined".to_string()));
+        play.tasks.push(check_task);
+
+        let hosts = vec![create_local_host()];
+        assert!(play.execute(&hosts).is_err());
+    }
+
+    /// Three hosts where `node1` fails the first task, the second task leaves a marker per host
+    fn create_partial_failure_play(dir: &std::path::Path) -> (Play, Vec<Host>) {
+        let mut play = create_test_play();
+        let mut fail = create_command_task("Fail on node1", "false");
+        fail.when = Some(Value::String("inventory_hostname == 'node1'".to_string()));
+        play.tasks.push(fail);
+        play.tasks.push(create_command_task(
+            "Mark",
+            &format!("touch {}/{{{{ inventory_hostname }}}}", dir.display()),
+        ));
+
+        let hosts = ["node1", "node2", "node3"]
+            .iter()
+            .map(|name| create_test_host(name, "localhost", 22, None, None))
+            .collect();
+        (play, hosts)
+    }
+
+    #[test]
+    fn test_play_failed_host_does_not_stop_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let (play, hosts) = create_partial_failure_play(dir.path());
+
+        let failed = play.execute(&hosts).unwrap();
+        assert_eq!(failed, HashSet::from(["node1".to_string()]));
+        assert!(!dir.path().join("node1").exists());
+        assert!(dir.path().join("node2").exists());
+        assert!(dir.path().join("node3").exists());
+    }
+
+    #[test]
+    fn test_play_any_errors_fatal() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut play, hosts) = create_partial_failure_play(dir.path());
+        play.any_errors_fatal = true;
+
+        assert!(play.execute(&hosts).is_err());
+        assert!(!dir.path().join("node2").exists());
+    }
+
+    #[test]
+    fn test_play_max_fail_percentage() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut play, hosts) = create_partial_failure_play(dir.path());
+
+        // One of three hosts is 33% of them
+        play.max_fail_percentage = Some(40.0);
+        assert!(play.execute(&hosts).is_ok());
+        assert!(dir.path().join("node2").exists());
+
+        std::fs::remove_file(dir.path().join("node2")).unwrap();
+        play.max_fail_percentage = Some(30.0);
+        assert!(play.execute(&hosts).is_err());
+        assert!(!dir.path().join("node2").exists());
+    }
+
+    #[test]
+    fn test_play_counts_fact_gathering_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut play, mut hosts) = create_partial_failure_play(dir.path());
+        play.gather_facts = true;
+        play.tasks[0].when = Some(Value::String("inventory_hostname == 'node2'".to_string()));
+        // Nothing listens on port 1, so node1 is unreachable while gathering facts
+        hosts[0] = create_test_host("node1", "127.0.0.2", 1, None, None);
+
+        for strategy in ["linear", "free"] {
+            play.strategy = strategy.to_string();
+            // node1 fails gathering facts and node2 the first task: 2 of 3 hosts
+            play.max_fail_percentage = Some(50.0);
+            assert!(play.execute(&hosts).is_err(), "{}", strategy);
+
+            play.max_fail_percentage = Some(70.0);
+            assert!(play.execute(&hosts).is_ok(), "{}", strategy);
+            assert!(dir.path().join("node3").exists(), "{}", strategy);
+        }
+    }
+
+    #[test]
+    fn test_play_serial_runs_batches_in_turn() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let mut play = create_test_play();
+        play.serial = vec![SerialBatch::Hosts(1)];
+        for step in ["first", "second"] {
+            let mut task = create_command_task(
+                step,
+                &format!(
+                    "echo {{{{ inventory_hostname }}}}-{} >> {}",
+                    step,
+                    log.display()
+                ),
+            );
+            task.module = "shell".to_string();
+            play.tasks.push(task);
+        }
+
+        let hosts: Vec<Host> = ["node1", "node2"]
+            .iter()
+            .map(|name| create_test_host(name, "localhost", 22, None, None))
+            .collect();
+        assert!(play.execute(&hosts).unwrap().is_empty());
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "node1-first\nnode1-second\nnode2-first\nnode2-second\n"
+        );
+    }
+
+    #[test]
+    fn test_play_serial_stops_after_failed_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut play, hosts) = create_partial_failure_play(dir.path());
+        play.serial = vec![SerialBatch::Hosts(1), SerialBatch::Hosts(2)];
+
+        // Without limits only a batch where every host failed stops the play
+        assert!(play.execute(&hosts).is_err());
+        assert!(!dir.path().join("node2").exists());
+
+        play.serial = vec![SerialBatch::Hosts(2)];
+        play.max_fail_percentage = Some(50.0);
+        assert_eq!(
+            play.execute(&hosts).unwrap(),
+            HashSet::from(["node1".to_string()])
+        );
+        assert!(dir.path().join("node3").exists());
+
+        // Half of the first batch failing exceeds 40%
+        std::fs::remove_file(dir.path().join("node3")).unwrap();
+        play.max_fail_percentage = Some(40.0);
+        assert!(play.execute(&hosts).is_err());
+        assert!(!dir.path().join("node3").exists());
+    }
+
+    #[test]
+    fn test_play_free_strategy_does_not_wait_for_slow_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let mut play = create_test_play();
+        play.strategy = "free".to_string();
+        let mut slow = create_command_task("Slow", "sleep 0.5");
+        slow.when = Some(Value::String("inventory_hostname == 'slow'".to_string()));
+        play.tasks.push(slow);
+        let mut done = create_command_task(
+            "Done",
+            &format!("echo {{{{ inventory_hostname }}}} >> {}", log.display()),
+        );
+        done.module = "shell".to_string();
+        done.register = Some("done".to_string());
+        play.tasks.push(done);
+        // Registered results still reach the host's later tasks
+        let mut fail = create_command_task("Fail", "false");
+        fail.when = Some(Value::String(
+            "done.rc == 0 and inventory_hostname == 'fast'".to_string(),
+        ));
+        play.tasks.push(fail);
+
+        let hosts: Vec<Host> = ["slow", "fast"]
+            .iter()
+            .map(|name| create_test_host(name, "localhost", 22, None, None))
+            .collect();
+        let failed = play.execute(&hosts).unwrap();
+
+        assert_eq!(failed, HashSet::from(["fast".to_string()]));
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "fast\nslow\n");
+    }
+
+    #[test]
+    fn test_play_free_strategy_stops_hosts_on_fatal_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut play = create_test_play();
+        play.strategy = "free".to_string();
+        play.any_errors_fatal = true;
+        let mut fail = create_command_task("Fail", "false");
+        fail.when = Some(Value::String("inventory_hostname == 'bad'".to_string()));
+        play.tasks.push(fail);
+        let mut slow = create_command_task("Slow", "sleep 0.5");
+        slow.when = Some(Value::String("inventory_hostname != 'bad'".to_string()));
+        play.tasks.push(slow);
+        play.tasks.push(create_command_task(
+            "Touch",
+            &format!(
+                "touch {}/{{{{ inventory_hostname }}}}",
+                dir.path().display()
+            ),
+        ));
+
+        let hosts: Vec<Host> = ["bad", "good1", "good2"]
+            .iter()
+            .map(|name| create_test_host(name, "localhost", 22, None, None))
+            .collect();
+        let error = play.execute(&hosts).unwrap_err();
+        assert!(error.to_string().contains("any_errors_fatal"));
+        // The other hosts stop before the task after the one they were running
+        assert!(!dir.path().join("good1").exists());
+        assert!(!dir.path().join("good2").exists());
+    }
+
+    #[test]
+    fn test_play_runs_tasks_selected_by_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut play = create_test_play();
+        play.gather_facts = true;
+        for (name, tags) in [
+            ("config", vec!["config"]),
+            ("deploy", vec!["deploy"]),
+            ("untagged", vec![]),
+            ("always", vec!["always"]),
+            ("never", vec!["never", "debug"]),
+        ] {
+            let mut task =
+                create_command_task(name, &format!("touch {}/{}", dir.path().display(), name));
+            task.tags = tags.into_iter().map(str::to_string).collect();
+            play.tasks.push(task);
+        }
+        // Tasks of a block are selected one by one
+        let mut nested =
+            create_command_task("nested", &format!("touch {}/nested", dir.path().display()));
+        nested.tags = vec!["config".to_string()];
+        play.tasks
+            .push(create_block_task(vec![nested], Vec::new(), Vec::new()));
+
+        let options = PlaybookOptions {
+            tags: vec!["config".to_string(), "debug".to_string()],
+            skip_tags: vec!["never".to_string()],
+            ..Default::default()
+        };
+        let mut stats = PlaybookStats::default();
+        play.run(&[create_local_host()], &options, &mut stats)
+            .unwrap();
+
+        let ran: Vec<&str> = ["config", "deploy", "untagged", "always", "never", "nested"]
+            .into_iter()
+            .filter(|name| dir.path().join(name).exists())
+            .collect();
+        assert_eq!(ran, ["config", "always", "nested"]);
+        // Facts are gathered whatever the tags
+        assert_eq!(stats.get("localhost").unwrap().ok, 4);
+    }
+
+    #[test]
+    fn test_play_counts_results_per_host() {
+        let mut play = create_test_play();
+        let mut notify = create_command_task("Changed", "echo changed");
+        notify.notify = vec!["Handler".to_string()];
+        play.tasks.push(notify);
+        let mut ignored = create_command_task("Ignored", "false");
+        ignored.ignore_errors = Some(true);
+        play.tasks.push(ignored);
+        let mut skipped = create_command_task("Skipped", "echo skipped");
+        skipped.when = Some(Value::Bool(false));
+        play.tasks.push(skipped);
+        // A loop counts as a single result
+        let mut looped = create_command_task("Loop", "echo {{ item }}");
+        looped.loop_items = Some(Value::Sequence(vec![
+            Value::String("a".to_string()),
+            Value::String("b".to_string()),
+        ]));
+        play.tasks.push(looped);
+        play.tasks.push(create_block_task(
+            vec![create_command_task("Broken", "false")],
+            vec![create_command_task("Rescue", "echo rescued")],
+            Vec::new(),
+        ));
+        play.handlers.push(Handler {
+            task: create_command_task("Handler", "echo handled"),
+        });
+
+        let mut stats = PlaybookStats::default();
+        let hosts = vec![create_local_host()];
+        let failed = play
+            .run(&hosts, &PlaybookOptions::default(), &mut stats)
+            .unwrap();
+
+        assert!(failed.is_empty());
+        assert_eq!(
+            stats.get("localhost"),
+            Some(&crate::playbook::HostStats {
+                ok: 5,
+                changed: 4,
+                skipped: 1,
+                rescued: 1,
+                ignored: 1,
+                ..Default::default()
+            })
+        );
+        assert_eq!(stats.exit_code(), 0);
+    }
+
+    fn create_block_task(block: Vec<Task>, rescue: Vec<Task>, always: Vec<Task>) -> Task {
+        let mut task = Task::new("block", "block", Mapping::new());
+        task.block = Some(Block {
+            block,
+            rescue,
+            always,
+        });
+        task
+    }
+
+    #[test]
+    fn test_play_block_rescue_recovers_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("rescued");
+
+        // The rescue task only runs if the failure details are exposed to it
+        let mut rescue = create_command_task("Rescue", &format!("touch {}", marker.display()));
+        rescue.when = Some(Value::String(
+            "ansible_failed_task.name == 'Broken' and ansible_failed_result.failed".to_string(),
+        ));
+
+        let mut play = create_test_play();
+        play.tasks.push(create_block_task(
+            vec![create_command_task("Broken", "false")],
+            vec![rescue],
+            Vec::new(),
+        ));
+
+        let hosts = vec![create_local_host()];
+        assert!(play.execute(&hosts).is_ok());
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn test_play_block_always_runs_after_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let always_marker = dir.path().join("always");
+        let after_marker = dir.path().join("after");
+
+        let mut play = create_test_play();
+        play.tasks.push(create_block_task(
+            vec![
+                create_command_task("Broken", "false"),
+                create_command_task("Skipped", &format!("touch {}", after_marker.display())),
+            ],
+            Vec::new(),
+            vec![create_command_task(
+                "Always",
+                &format!("touch {}", always_marker.display()),
+            )],
+        ));
+        play.tasks.push(create_command_task(
+            "After block",
+            &format!("touch {}", after_marker.display()),
+        ));
+
+        let hosts = vec![create_local_host()];
+        // Without a rescue section the failure still fails the play
+        assert!(play.execute(&hosts).is_err());
+        assert!(always_marker.exists());
+        assert!(!after_marker.exists());
+    }
+
+    #[test]
+    fn test_play_include_tasks_at_run_time() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("touch.yml"),
+            "- name: Touch item\n  command: touch {{ out_dir }}/{{ item }}-{{ suffix }}\n",
+        )
+        .unwrap();
+
+        let mut include_args = Mapping::new();
+        include_args.insert(
+            Value::String("_raw_params".to_string()),
+            Value::String("{{ kind }}.yml".to_string()),
+        );
+        include_args.insert(
+            Value::String(crate::playbook::task::INCLUDE_DIR_ARG.to_string()),
+            Value::String(dir.path().to_string_lossy().to_string()),
+        );
+        let mut include = Task::new("Include", "include_tasks", include_args);
+        include.loop_items = Some(Value::Sequence(vec![
+            Value::String("a".to_string()),
+            Value::String("b".to_string()),
+        ]));
+        include.when = Some(Value::String("item != 'b'".to_string()));
+        include.vars.insert(
+            Value::String("suffix".to_string()),
+            Value::String("done".to_string()),
+        );
+
+        let mut play = create_test_play();
+        play.vars.insert(
+            Value::String("kind".to_string()),
+            Value::String("touch".to_string()),
+        );
+        play.vars.insert(
+            Value::String("out_dir".to_string()),
+            Value::String(dir.path().to_string_lossy().to_string()),
+        );
+        play.tasks.push(include);
+
+        let hosts = vec![create_local_host()];
+        assert!(play.execute(&hosts).is_ok());
+        assert!(dir.path().join("a-done").exists());
+        assert!(!dir.path().join("b-done").exists());
+
+        // A missing file fails the host
+        play.vars.insert(
+            Value::String("kind".to_string()),
+            Value::String("missing".to_string()),
+        );
+        assert!(play.execute(&hosts).is_err());
+    }
+
+    #[test]
+    fn test_play_include_role_at_run_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let task = "- name: Touch\n  command: touch {{ out_dir }}/{{ role_name }}-{{ greeting }}-{{ item }}\n";
+        let notifying_task = format!("{}  notify: Restart first\n", task);
+        for (file, content) in [
+            ("roles/first/tasks/main.yml", notifying_task.as_str()),
+            (
+                "roles/first/handlers/main.yml",
+                "- name: Restart first\n  command: touch {{ out_dir }}/first-handled\n",
+            ),
+            ("roles/first/defaults/main.yml", "greeting: hello\n"),
+            ("roles/second/tasks/main.yml", task),
+            ("roles/second/defaults/main.yml", "greeting: hello\n"),
+            ("roles/second/vars/main.yml", "greeting: hey\n"),
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let mut include_args = Mapping::new();
+        include_args.insert(
+            Value::String("name".to_string()),
+            Value::String("{{ item }}".to_string()),
+        );
+        include_args.insert(
+            Value::String(crate::playbook::task::INCLUDE_DIR_ARG.to_string()),
+            Value::String(dir.path().to_string_lossy().to_string()),
+        );
+        let mut include = Task::new("Include", "include_role", include_args);
+        include.loop_items = Some(Value::Sequence(vec![
+            Value::String("first".to_string()),
+            Value::String("second".to_string()),
+        ]));
+
+        let mut play = create_test_play();
+        play.vars.insert(
+            Value::String("out_dir".to_string()),
+            Value::String(dir.path().to_string_lossy().to_string()),
+        );
+        play.tasks.push(include);
+
+        let hosts = vec![create_local_host()];
+        assert!(play.execute(&hosts).is_ok());
+        assert!(dir.path().join("first-hello-first").exists());
+        assert!(dir.path().join("second-hey-second").exists());
+        // The included role's handlers run when its tasks notify them
+        assert!(dir.path().join("first-handled").exists());
+
+        // Play vars override the role defaults but not the role vars
+        play.vars.insert(
+            Value::String("greeting".to_string()),
+            Value::String("play".to_string()),
+        );
+        assert!(play.execute(&hosts).is_ok());
+        assert!(dir.path().join("first-play-first").exists());
+        assert!(!dir.path().join("second-play-second").exists());
+
+        // A role that doesn't exist fails the host
+        play.tasks[0].loop_items =
+            Some(Value::Sequence(vec![Value::String("missing".to_string())]));
+        assert!(play.execute(&hosts).is_err());
+    }
+
+    #[test]
+    fn test_role_defaults_have_lowest_precedence() {
+        let mut play = create_test_play();
+        play.role_defaults.insert(
+            Value::String("http_port".to_string()),
+            Value::Number(80.into()),
+        );
+        play.role_defaults.insert(
+            Value::String("workers".to_string()),
+            Value::Number(2.into()),
+        );
+        play.vars.insert(
+            Value::String("http_port".to_string()),
+            Value::Number(8080.into()),
+        );
+
+        let vars = play
+            .host_vars(&create_local_host(), &Mapping::new())
+            .merged_with(&[]);
+        assert_eq!(vars.get("http_port"), Some(&Value::Number(8080.into())));
+        assert_eq!(vars.get("workers"), Some(&Value::Number(2.into())));
+    }
+
+    #[test]
+    fn test_play_vars_override_inventory_vars() {
+        let mut play = create_test_play();
+        play.vars.insert(
+            Value::String("http_port".to_string()),
+            Value::String("8080".to_string()),
+        );
+        play.vars_files.push((
+            "vars/main.yml".to_string(),
+            serde_yaml::from_str("{tier: file}").unwrap(),
+        ));
+
+        let mut host = create_local_host();
+        host.set_variable("http_port", "80");
+        host.set_variable("tier", "host");
+        host.set_variable("region", "eu");
+        host.group_variables = vec![
+            (
+                "all".to_string(),
+                HashMap::from([("region".to_string(), Value::from("us"))]),
+            ),
+            (
+                "web".to_string(),
+                HashMap::from([("team".to_string(), Value::from("web"))]),
+            ),
+        ];
+
+        let host_vars = play.host_vars(&host, &Mapping::new());
+        let vars = host_vars.merged_with(&[]);
+        let get = |name: &str| vars.get(name).and_then(Value::as_str);
+        assert_eq!(get("http_port"), Some("8080"));
+        assert_eq!(get("tier"), Some("file"));
+        assert_eq!(get("region"), Some("eu"));
+        assert_eq!(get("team"), Some("web"));
+        assert_eq!(get("inventory_hostname"), Some("localhost"));
+
+        let origins = host_vars.origins();
+        let sources: Vec<String> = origins["http_port"]
+            .iter()
+            .map(|(source, _)| source.to_string())
+            .collect();
+        assert_eq!(sources, ["play vars", "inventory host"]);
+    }
+
+    #[test]
+    fn test_play_inventory_vars_keep_types() {
+        let mut play = create_test_play();
+        // Only runs (and fails) when the number compares as one and the boolean is true
+        let mut task = create_command_task("Typed", "false");
+        task.when = Some(Value::String(
+            "http_port > 1024 and backup_enabled".to_string(),
+        ));
+        play.tasks.push(task);
+
+        let mut host = create_local_host();
+        host.set_variable("http_port", 8080);
+        host.set_variable("backup_enabled", false);
+        assert!(play.execute(&vec![host.clone()]).is_ok());
+
+        host.set_variable("backup_enabled", true);
+        assert!(play.execute(&vec![host]).is_err());
+    }
+
+    #[test]
+    fn test_play_gathers_facts_for_conditions() {
+        let mut play = create_test_play();
+        play.gather_facts = true;
+        play.gather_subset = Some(Value::String("min".to_string()));
+
+        // Fails only if the facts were gathered and exposed as ansible_* variables
+        let mut task = create_command_task("Needs facts", "false");
+        task.when = Some(Value::String(
+            "ansible_system is defined and ansible_facts.date_time.epoch is defined".to_string(),
+        ));
+        play.tasks.push(task);
+
+        let hosts = vec![create_local_host()];
+        assert!(play.execute(&hosts).is_err());
+    }
 }
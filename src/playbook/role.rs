@@ -0,0 +1,286 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::playbook::parser::{self, PlayContext};
+use crate::playbook::{Block, Task};
+
+/// Keys of a `roles:` entry that apply to the role as a whole, every other key is a role parameter
+const ROLE_ENTRY_KEYWORDS: &[&str] = &[
+    "when",
+    "tags",
+    "become",
+    "become_user",
+    "ignore_errors",
+    "check_mode",
+];
+
+/// Private `import_role` argument holding the parameters of a `roles:` entry
+pub(super) const ROLE_PARAMS_ARG: &str = "_role_params";
+
+/// Convert an entry of a play's `roles:` list (or of a role's dependencies) into the
+/// equivalent `import_role` task. Returns the role name and the task mapping.
+pub(super) fn role_entry_task(entry: &Value) -> Result<(String, Mapping)> {
+    let entry_map = match entry {
+        Value::String(name) => {
+            let mut entry_map = Mapping::new();
+            entry_map.insert(
+                Value::String("role".to_string()),
+                Value::String(name.clone()),
+            );
+            entry_map
+        }
+        Value::Mapping(entry_map) => entry_map.clone(),
+        _ => return Err(anyhow::anyhow!("Role entry must be a name or a mapping")),
+    };
+
+    let name = match entry_map
+        .get(Value::String("role".to_string()))
+        .or_else(|| entry_map.get(Value::String("name".to_string())))
+    {
+        Some(Value::String(name)) => name.clone(),
+        _ => return Err(anyhow::anyhow!("Role entry requires a 'role' name")),
+    };
+
+    let mut task_map = Mapping::new();
+    let mut params = Mapping::new();
+    for (key, value) in &entry_map {
+        match key.as_str() {
+            Some("role") | Some("name") => {}
+            Some("vars") => {
+                if let Value::Mapping(vars) = value {
+                    for (var, var_value) in vars {
+                        params.insert(var.clone(), var_value.clone());
+                    }
+                }
+            }
+            Some(keyword) if ROLE_ENTRY_KEYWORDS.contains(&keyword) => {
+                task_map.insert(key.clone(), value.clone());
+            }
+            _ => {
+                params.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    let mut import_args = Mapping::new();
+    import_args.insert(
+        Value::String("name".to_string()),
+        Value::String(name.clone()),
+    );
+    task_map.insert(
+        Value::String("name".to_string()),
+        Value::String(name.clone()),
+    );
+    import_args.insert(
+        Value::String(ROLE_PARAMS_ARG.to_string()),
+        Value::Mapping(params),
+    );
+    task_map.insert(
+        Value::String("import_role".to_string()),
+        Value::Mapping(import_args),
+    );
+
+    Ok((name, task_map))
+}
+
+/// Locate a role directory: `roles/<name>` next to the playbook, or a path to the role itself
+pub(super) fn find_role(name: &str, base_dir: &Path) -> Result<PathBuf> {
+    let roles_dir = base_dir.join("roles");
+    [roles_dir.join(name), base_dir.join(name)]
+        .into_iter()
+        .find(|candidate| candidate.is_dir())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Role '{}' not found in {} or {}",
+                name,
+                roles_dir.display(),
+                base_dir.display()
+            )
+        })
+}
+
+/// Load the role used by an `import_role` task: its dependencies and tasks
+/// as a block, plus the role variables. Handlers and defaults are added to the play context.
+pub(super) fn load_role(args: &Mapping, ctx: &mut PlayContext) -> Result<(Block, Mapping)> {
+    let name = match args.get(Value::String("name".to_string())) {
+        Some(Value::String(name)) => name.clone(),
+        _ => return Err(anyhow::anyhow!("Role include requires a 'name' argument")),
+    };
+    let role_path = find_role(&name, &ctx.base_dir)?;
+    load_role_at(&name, &role_path, args, ctx)
+}
+
+/// Load the role found at `role_path` like `load_role`, for an `include_role` resolved at run time
+pub(super) fn load_role_at(
+    name: &str,
+    role_path: &Path,
+    args: &Mapping,
+    ctx: &mut PlayContext,
+) -> Result<(Block, Mapping)> {
+    let tasks_from = match args.get(Value::String("tasks_from".to_string())) {
+        Some(Value::String(file)) => file.clone(),
+        _ => "main".to_string(),
+    };
+
+    if ctx.include_stack.iter().any(|path| path == role_path) {
+        return Err(anyhow::anyhow!(
+            "Role '{}' depends on itself through its dependencies",
+            name
+        ));
+    }
+    debug!("Loading role '{}' from {}", name, role_path.display());
+
+    ctx.include_stack.push(role_path.to_path_buf());
+    let loaded = load_role_content(name, role_path, &tasks_from, ctx)
+        .with_context(|| format!("Failed to load role '{}'", name));
+    ctx.include_stack.pop();
+    let tasks = loaded?;
+
+    let mut vars = match read_role_file(role_path, "vars", "main")? {
+        Some(Value::Mapping(vars)) => vars,
+        _ => Mapping::new(),
+    };
+    vars.insert(
+        Value::String("role_name".to_string()),
+        Value::String(name.to_string()),
+    );
+    vars.insert(
+        Value::String("role_path".to_string()),
+        Value::String(role_path.to_string_lossy().to_string()),
+    );
+
+    Ok((
+        Block {
+            block: tasks,
+            ..Default::default()
+        },
+        vars,
+    ))
+}
+
+fn load_role_content(
+    name: &str,
+    role_path: &Path,
+    tasks_from: &str,
+    ctx: &mut PlayContext,
+) -> Result<Vec<Task>> {
+    // Dependencies run first, only once per play for the same parameters
+    let mut tasks = Vec::new();
+    if let Some(meta) = read_role_file(role_path, "meta", "main")? {
+        if let Some(Value::Sequence(dependencies)) = meta.get("dependencies") {
+            for dependency in dependencies {
+                if let Some(task) = parser::parse_role_entry(dependency, ctx)? {
+                    tasks.push(task);
+                }
+            }
+        }
+    }
+
+    // Defaults of every role used by the play are visible to the whole play
+    if let Some(Value::Mapping(defaults)) = read_role_file(role_path, "defaults", "main")? {
+        for (key, value) in defaults {
+            ctx.role_defaults.insert(key, value);
+        }
+    }
+
+    if ctx.roles_with_handlers.insert(role_path.to_path_buf()) {
+        let handler_maps = parser::task_maps(read_role_file(role_path, "handlers", "main")?);
+        for (index, handler_map) in handler_maps {
+            let handler = parser::parse_handler(handler_map, index, ctx)
+                .with_context(|| format!("Failed to parse handler at index {}", index))?;
+            ctx.handlers.push(handler);
+        }
+    }
+
+    // Files imported by role tasks are looked up next to them
+    let task_maps = parser::task_maps(read_role_file(role_path, "tasks", tasks_from)?);
+    let mut role_tasks = parser::parse_tasks_in(&role_path.join("tasks"), task_maps, ctx)?;
+    prefix_task_names(&mut role_tasks, name);
+    tasks.append(&mut role_tasks);
+
+    Ok(tasks)
+}
+
+/// Read `<role>/<dir>/<file>.yml` (or `.yaml`), None when the role does not provide it
+fn read_role_file(role_path: &Path, dir: &str, file: &str) -> Result<Option<Value>> {
+    let dir_path = role_path.join(dir);
+    let candidates = if Path::new(file).extension().is_some() {
+        vec![dir_path.join(file)]
+    } else {
+        vec![
+            dir_path.join(format!("{}.yml", file)),
+            dir_path.join(format!("{}.yaml", file)),
+        ]
+    };
+
+    let Some(path) = candidates.into_iter().find(|path| path.is_file()) else {
+        return Ok(None);
+    };
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read role file: {}", path.display()))?;
+    let value: Value = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse role file: {}", path.display()))?;
+
+    Ok(match value {
+        Value::Null => None,
+        value => Some(value),
+    })
+}
+
+/// Show role tasks as `role : task` like Ansible does, including nested blocks and imports
+fn prefix_task_names(tasks: &mut [Task], role_name: &str) {
+    for task in tasks {
+        task.name = format!("{} : {}", role_name, task.name);
+        if task.module != "block" && task.module != "import_tasks" {
+            continue;
+        }
+        if let Some(block) = task.block.as_mut() {
+            prefix_task_names(&mut block.block, role_name);
+            prefix_task_names(&mut block.rescue, role_name);
+            prefix_task_names(&mut block.always, role_name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_entry_task() {
+        let entry: Value = serde_yaml::from_str(
+            "{role: nginx, when: web, tags: [www], http_port: 8080, vars: {worker_count: 2}}",
+        )
+        .unwrap();
+        let (name, task_map) = role_entry_task(&entry).unwrap();
+
+        assert_eq!(name, "nginx");
+        assert_eq!(
+            task_map.get("when"),
+            Some(&Value::String("web".to_string()))
+        );
+        assert!(task_map.contains_key("tags"));
+
+        let params = task_map
+            .get("import_role")
+            .and_then(|args| args.get(ROLE_PARAMS_ARG))
+            .unwrap();
+        assert_eq!(params.get("http_port"), Some(&Value::Number(8080.into())));
+        assert_eq!(params.get("worker_count"), Some(&Value::Number(2.into())));
+        assert!(params.get("tags").is_none());
+
+        let (name, task_map) = role_entry_task(&Value::String("common".to_string())).unwrap();
+        assert_eq!(name, "common");
+        assert_eq!(
+            task_map
+                .get("import_role")
+                .and_then(|args| args.get("name")),
+            Some(&Value::String("common".to_string()))
+        );
+
+        assert!(role_entry_task(&Value::Number(1.into())).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use serde_yaml::Value;
+
+/// Size of a batch of hosts set by the `serial` play keyword
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerialBatch {
+    Hosts(usize),
+    Percent(f64), // Of all the hosts of the play
+}
+
+impl SerialBatch {
+    fn hosts(&self, host_count: usize) -> usize {
+        match self {
+            SerialBatch::Hosts(0) => host_count,
+            SerialBatch::Hosts(count) => *count,
+            SerialBatch::Percent(percent) => {
+                ((host_count as f64 * percent / 100.0).floor() as usize).max(1)
+            }
+        }
+    }
+}
+
+/// Parse `serial`: a number of hosts, a percentage like `"25%"`, or a list of them
+pub(super) fn parse_serial(value: &Value) -> Result<Vec<SerialBatch>> {
+    match value {
+        Value::Sequence(sizes) => sizes.iter().map(parse_batch).collect(),
+        size => Ok(vec![parse_batch(size)?]),
+    }
+}
+
+fn parse_batch(value: &Value) -> Result<SerialBatch> {
+    let batch = match value {
+        Value::Number(n) => n.as_u64().map(|count| SerialBatch::Hosts(count as usize)),
+        Value::String(s) => match s.trim().strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| *percent > 0.0 && *percent <= 100.0)
+                .map(SerialBatch::Percent),
+            None => s.trim().parse::<usize>().ok().map(SerialBatch::Hosts),
+        },
+        _ => None,
+    };
+    batch.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Invalid 'serial' value {:?}, expected a number of hosts or a percentage",
+            value
+        )
+    })
+}
+
+/// Split the hosts of a play (by index) into the batches it runs in, one after the other.
+/// The last batch size is repeated until every host is in a batch.
+pub(super) fn batches(serial: &[SerialBatch], host_count: usize) -> Vec<Vec<usize>> {
+    let mut batches = Vec::new();
+    let mut sizes = serial.iter();
+    let mut size = host_count;
+    let mut start = 0;
+
+    while start < host_count {
+        if let Some(batch) = sizes.next() {
+            size = batch.hosts(host_count);
+        }
+        let end = (start + size).min(host_count);
+        batches.push((start..end).collect());
+        start = end;
+    }
+
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_batches() {
+        let serial = parse_serial(&serde_yaml::from_str("[1, 3, '50%']").unwrap()).unwrap();
+        assert_eq!(
+            serial,
+            vec![
+                SerialBatch::Hosts(1),
+                SerialBatch::Hosts(3),
+                SerialBatch::Percent(50.0)
+            ]
+        );
+
+        let sizes: Vec<usize> = batches(&serial, 10).iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![1, 3, 5, 1]);
+        assert_eq!(batches(&serial, 10)[1], vec![1, 2, 3]);
+
+        // Without serial (or with 0) all hosts form a single batch
+        assert_eq!(batches(&[], 3), vec![vec![0, 1, 2]]);
+        assert_eq!(batches(&[SerialBatch::Hosts(0)], 3).len(), 1);
+        assert!(batches(&[], 0).is_empty());
+
+        // A percentage always covers at least one host
+        assert_eq!(batches(&[SerialBatch::Percent(10.0)], 3).len(), 3);
+
+        assert!(parse_serial(&Value::String("all".to_string())).is_err());
+        assert!(parse_serial(&Value::String("0%".to_string())).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::Range;
+
+/// The lines of a YAML file, used to point parse errors at the play or task they come from.
+///
+/// `serde_yaml::Value` doesn't keep positions, so list entries are found from their `- `
+/// markers and mapping keys from their indentation, which covers block-style YAML.
+pub(super) struct Source<'a> {
+    path: &'a str,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(path: &'a str, content: &'a str) -> Self {
+        Source {
+            path,
+            lines: content.lines().collect(),
+        }
+    }
+
+    /// Every line of the file
+    pub fn all(&self) -> Range<usize> {
+        0..self.lines.len()
+    }
+
+    /// The line ranges of the entries of the list starting in `range`, one per entry
+    pub fn entries(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        let mut starts = Vec::new();
+        let mut end = range.end;
+        let mut list_indent = None;
+
+        for index in range.clone() {
+            let line = self.lines[index];
+            let content = line.trim_start();
+            if content.is_empty() || content.starts_with('#') || content.starts_with("---") {
+                continue;
+            }
+
+            let indent = line.len() - content.len();
+            let is_entry = content == "-" || content.starts_with("- ");
+            match list_indent {
+                None if is_entry => list_indent = Some(indent),
+                None => break,
+                Some(list_indent) if indent == list_indent && is_entry => {}
+                Some(list_indent) if indent > list_indent => continue,
+                Some(_) => {
+                    end = index;
+                    break;
+                }
+            }
+            starts.push(index);
+        }
+
+        let ends = starts.iter().skip(1).copied().chain([end]);
+        starts
+            .iter()
+            .zip(ends)
+            .map(|(&start, end)| start..end)
+            .collect()
+    }
+
+    /// The lines following `key` in the list entry spanning `entry`, such as a play's `tasks`
+    pub fn key(&self, entry: Range<usize>, key: &str) -> Range<usize> {
+        if entry.is_empty() {
+            return entry.end..entry.end;
+        }
+        let Some(first) = self.lines.get(entry.start) else {
+            return entry.end..entry.end;
+        };
+        // Keys are aligned with the first key, written after the entry's dash
+        let dash = first.len() - first.trim_start().len();
+        let Some(after_dash) = first.get(dash + 1..) else {
+            return entry.end..entry.end;
+        };
+        let key_indent = dash + 1 + (after_dash.len() - after_dash.trim_start().len());
+        let prefix = format!("{}:", key);
+
+        entry
+            .clone()
+            .find(|&index| {
+                let line = self.lines[index].as_bytes();
+                line.len() > key_indent
+                    && line[..key_indent].iter().all(|&c| c == b' ' || c == b'-')
+                    && line[key_indent..].starts_with(prefix.as_bytes())
+            })
+            .map(|index| index + 1..entry.end)
+            .unwrap_or(entry.end..entry.end)
+    }
+
+    /// `path:line` of the entry at `index` of `entries`, or only the path when it isn't known
+    pub fn locate(&self, entries: &[Range<usize>], index: usize) -> String {
+        match entries.get(index) {
+            Some(entry) => format!("{}:{}", self.path, entry.start + 1),
+            None => self.path.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_plays_and_tasks() {
+        let content = r#"---
+# Web servers
+- name: First play
+  hosts: web
+  vars:
+    tasks: not the task list
+  tasks:
+    - name: Install
+      apt:
+        name: nginx
+
+    - name: Start
+      service: name=nginx
+
+- hosts: db
+  name: Second play
+  tasks:
+  - name: Only task
+    ping:
+  handlers:
+  - name: Handler
+    ping:
+"#;
+        let source = Source::new("site.yml", content);
+        let plays = source.entries(source.all());
+        assert_eq!(plays.len(), 2);
+        assert_eq!(source.locate(&plays, 0), "site.yml:3");
+        assert_eq!(source.locate(&plays, 1), "site.yml:15");
+        assert_eq!(source.locate(&plays, 2), "site.yml");
+
+        let tasks = source.entries(source.key(plays[0].clone(), "tasks"));
+        assert_eq!(source.locate(&tasks, 0), "site.yml:8");
+        assert_eq!(source.locate(&tasks, 1), "site.yml:12");
+
+        let tasks = source.entries(source.key(plays[1].clone(), "tasks"));
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(source.locate(&tasks, 0), "site.yml:18");
+        let handlers = source.entries(source.key(plays[1].clone(), "handlers"));
+        assert_eq!(source.locate(&handlers, 0), "site.yml:21");
+
+        assert!(source.key(plays[1].clone(), "roles").is_empty());
+        assert!(source.key(0..0, "tasks").is_empty());
+    }
+}
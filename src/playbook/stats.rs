@@ -0,0 +1,201 @@
+use colored::Colorize;
+use std::collections::BTreeMap;
+
+use crate::playbook::TaskResult;
+
+/// Exit code of a run where a host failed a task
+pub const EXIT_FAILED_HOSTS: i32 = 2;
+/// Exit code of a run where a host could not be reached
+pub const EXIT_UNREACHABLE_HOSTS: i32 = 4;
+
+/// Task results of a host, counted like Ansible's PLAY RECAP
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostStats {
+    pub ok: usize,
+    pub changed: usize,
+    pub unreachable: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub rescued: usize,
+    pub ignored: usize,
+}
+
+/// Per-host counters of every play of a playbook run
+#[derive(Debug, Clone, Default)]
+pub struct PlaybookStats {
+    hosts: BTreeMap<String, HostStats>,
+    check_mode: bool, // A play ran in check mode, so its changes are only predicted
+}
+
+impl PlaybookStats {
+    /// Make a host show up in the recap even if it never gets a task result
+    pub fn add_host(&mut self, host: &str) {
+        self.hosts.entry(host.to_string()).or_default();
+    }
+
+    /// Count the result of a task (or handler) on a host
+    pub fn record(&mut self, host: &str, result: &TaskResult) {
+        let stats = self.hosts.entry(host.to_string()).or_default();
+        if result.unreachable {
+            stats.unreachable += 1;
+        } else if result.failed {
+            stats.failed += 1;
+        } else if result.skipped {
+            stats.skipped += 1;
+        } else {
+            stats.ok += 1;
+            if result.changed {
+                stats.changed += 1;
+            }
+            if result.ignored {
+                stats.ignored += 1;
+            }
+        }
+    }
+
+    /// A host recovered by the rescue section of a block no longer counts the failure
+    pub fn rescue(&mut self, host: &str) {
+        let stats = self.hosts.entry(host.to_string()).or_default();
+        stats.failed = stats.failed.saturating_sub(1);
+        stats.rescued += 1;
+    }
+
+    /// Add the counters of another run, such as a single host's part of a play
+    pub fn merge(&mut self, other: PlaybookStats) {
+        self.check_mode |= other.check_mode;
+        for (host, other) in other.hosts {
+            let stats = self.hosts.entry(host).or_default();
+            stats.ok += other.ok;
+            stats.changed += other.changed;
+            stats.unreachable += other.unreachable;
+            stats.failed += other.failed;
+            stats.skipped += other.skipped;
+            stats.rescued += other.rescued;
+            stats.ignored += other.ignored;
+        }
+    }
+
+    /// Record that a play ran in check mode, which the recap points out
+    pub fn mark_check_mode(&mut self) {
+        self.check_mode = true;
+    }
+
+    pub fn check_mode(&self) -> bool {
+        self.check_mode
+    }
+
+    pub fn get(&self, host: &str) -> Option<&HostStats> {
+        self.hosts.get(host)
+    }
+
+    /// 4 when a host was unreachable, 2 when a host failed, both combined like Ansible (6)
+    /// when each happened, 0 otherwise
+    pub fn exit_code(&self) -> i32 {
+        let mut code = 0;
+        if self.hosts.values().any(|stats| stats.unreachable > 0) {
+            code |= EXIT_UNREACHABLE_HOSTS;
+        }
+        if self.hosts.values().any(|stats| stats.failed > 0) {
+            code |= EXIT_FAILED_HOSTS;
+        }
+        code
+    }
+
+    pub fn print_recap(&self) {
+        if self.check_mode {
+            println!(
+                "\n{}",
+                "PLAY RECAP (check mode, changes are predicted)".bold()
+            );
+        } else {
+            println!("\n{}", "PLAY RECAP".bold());
+        }
+        println!("{}", "*".repeat(80).dimmed());
+
+        for (host, stats) in &self.hosts {
+            let name = if stats.failed > 0 || stats.unreachable > 0 {
+                host.red().bold()
+            } else if stats.changed > 0 {
+                host.yellow().bold()
+            } else {
+                host.green().bold()
+            };
+
+            let counters = [
+                ("ok", stats.ok, "green"),
+                ("changed", stats.changed, "yellow"),
+                ("unreachable", stats.unreachable, "red"),
+                ("failed", stats.failed, "red"),
+                ("skipped", stats.skipped, "cyan"),
+                ("rescued", stats.rescued, "magenta"),
+                ("ignored", stats.ignored, "magenta"),
+            ];
+            let counters: Vec<String> = counters
+                .iter()
+                .map(|(label, count, color)| {
+                    let counter = format!("{}={}", label, count);
+                    if *count > 0 {
+                        counter.color(*color).to_string()
+                    } else {
+                        counter
+                    }
+                })
+                .collect();
+
+            println!("{:<30} : {}", name, counters.join("  "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_counters_and_exit_code() {
+        let mut stats = PlaybookStats::default();
+        stats.add_host("idle");
+
+        let mut changed = TaskResult::new("web");
+        changed.changed = true;
+        stats.record("web", &changed);
+        let mut ignored = TaskResult::new("web");
+        ignored.ignored = true;
+        stats.record("web", &ignored);
+        let mut skipped = TaskResult::new("web");
+        skipped.skipped = true;
+        stats.record("web", &skipped);
+
+        assert_eq!(
+            stats.get("web"),
+            Some(&HostStats {
+                ok: 2,
+                changed: 1,
+                skipped: 1,
+                ignored: 1,
+                ..Default::default()
+            })
+        );
+        assert_eq!(stats.get("idle"), Some(&HostStats::default()));
+        assert_eq!(stats.exit_code(), 0);
+
+        let mut failed = TaskResult::new("db");
+        failed.failed = true;
+        stats.record("db", &failed);
+        assert_eq!(stats.exit_code(), EXIT_FAILED_HOSTS);
+        stats.rescue("db");
+        assert_eq!(stats.get("db").map(|s| (s.failed, s.rescued)), Some((0, 1)));
+        assert_eq!(stats.exit_code(), 0);
+
+        let mut unreachable = TaskResult::new("db");
+        unreachable.failed = true;
+        unreachable.unreachable = true;
+        stats.record("db", &unreachable);
+        assert_eq!(stats.exit_code(), EXIT_UNREACHABLE_HOSTS);
+        stats.record("web", &failed);
+        assert_eq!(
+            stats.exit_code(),
+            EXIT_UNREACHABLE_HOSTS | EXIT_FAILED_HOSTS
+        );
+    }
+}
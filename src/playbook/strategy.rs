@@ -0,0 +1,151 @@
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::slice;
+
+use crate::inventory::Host;
+use crate::playbook::play::{HostFailure, PlayState, SharedFailures};
+use crate::playbook::vars::HostVars;
+use crate::playbook::{Handler, Play, PlaybookStats, Task};
+
+/// Strategy used when a play doesn't set `strategy`
+pub const DEFAULT_STRATEGY: &str = "linear";
+
+/// How the hosts of a play go through the play's task list
+pub(super) trait Strategy: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Run `tasks` on the `active` hosts (indexes into `hosts`), returning the hosts that failed.
+    /// The play's failure limits (`any_errors_fatal`, `max_fail_percentage`) set `state.abort`.
+    fn run_tasks(
+        &self,
+        play: &Play,
+        state: &mut PlayState,
+        hosts: &[Host],
+        tasks: &[Task],
+        active: &[usize],
+    ) -> Vec<HostFailure>;
+}
+
+/// Every host finishes a task before any host starts the next one
+struct Linear;
+
+impl Strategy for Linear {
+    fn name(&self) -> &'static str {
+        "linear"
+    }
+
+    fn run_tasks(
+        &self,
+        play: &Play,
+        state: &mut PlayState,
+        hosts: &[Host],
+        tasks: &[Task],
+        active: &[usize],
+    ) -> Vec<HostFailure> {
+        play.run_tasks(state, hosts, tasks, active, true)
+    }
+}
+
+/// Each host runs through the task list on its own as fast as it can, up to `forks` hosts at a
+/// time. The hosts share their failures, so when the failure limits are hit the other hosts stop
+/// before their next task.
+struct Free;
+
+/// What a host left behind after running the task list on its own
+struct HostRun {
+    index: usize,
+    failures: Vec<HostFailure>,
+    vars: HostVars,
+    notified_handlers: HashSet<String>,
+    included_handlers: Vec<Handler>,
+    stats: PlaybookStats,
+}
+
+impl Strategy for Free {
+    fn name(&self) -> &'static str {
+        "free"
+    }
+
+    fn run_tasks(
+        &self,
+        play: &Play,
+        state: &mut PlayState,
+        hosts: &[Host],
+        tasks: &[Task],
+        active: &[usize],
+    ) -> Vec<HostFailure> {
+        let pool = state.pool;
+        let options = state.options;
+        let check_mode = state.check_mode;
+        let hosts_vars = &state.hosts_vars;
+        let batch_size = state.batch_size;
+        // Hosts of the batch that failed an earlier task list count towards the limits too
+        let shared_failures = SharedFailures::new(batch_size.saturating_sub(active.len()));
+
+        let runs: Vec<HostRun> = pool.install(|| {
+            active
+                .par_iter()
+                .map(|&index| {
+                    // The host runs the tasks as a play of its own
+                    let mut stats = PlaybookStats::default();
+                    let mut host_state = PlayState {
+                        pool,
+                        options,
+                        check_mode,
+                        hosts_vars: vec![hosts_vars[index].clone()],
+                        stats: &mut stats,
+                        notified_handlers: HashSet::new(),
+                        included_handlers: Vec::new(),
+                        batch_size,
+                        abort: None,
+                        shared_failures: Some(&shared_failures),
+                    };
+                    let failures = play.run_tasks(
+                        &mut host_state,
+                        slice::from_ref(&hosts[index]),
+                        tasks,
+                        &[0],
+                        true,
+                    );
+                    let vars = host_state.hosts_vars.pop().unwrap_or_default();
+                    let notified_handlers = host_state.notified_handlers;
+                    let included_handlers = host_state.included_handlers;
+
+                    HostRun {
+                        index,
+                        failures,
+                        vars,
+                        notified_handlers,
+                        included_handlers,
+                        stats,
+                    }
+                })
+                .collect()
+        });
+
+        let mut failures = Vec::new();
+        for run in runs {
+            state.hosts_vars[run.index] = run.vars;
+            state.notified_handlers.extend(run.notified_handlers);
+            state.add_included_handlers(run.included_handlers);
+            state.stats.merge(run.stats);
+            failures.extend(run.failures.into_iter().map(|failure| HostFailure {
+                host: run.index,
+                ..failure
+            }));
+        }
+
+        state.abort = shared_failures.reason();
+        failures
+    }
+}
+
+const STRATEGIES: &[&dyn Strategy] = &[&Linear, &Free];
+
+/// Look up a strategy by the name used in the `strategy` play keyword
+pub(super) fn find(name: &str) -> Option<&'static dyn Strategy> {
+    STRATEGIES
+        .iter()
+        .copied()
+        .find(|strategy| strategy.name() == name)
+}
@@ -0,0 +1,73 @@
+/// Whether a task with `tags` runs for the `--tags` (`only_tags`) and `--skip-tags` options,
+/// following Ansible's rules for the special `always`, `never`, `tagged`, `untagged` and `all` tags
+pub(super) fn selects(tags: &[String], only_tags: &[String], skip_tags: &[String]) -> bool {
+    let has = |tag: &str| tags.iter().any(|t| t == tag);
+    let untagged = tags.is_empty();
+    // A `never` task is only selected by naming one of its tags, `tagged` doesn't count
+    // there, while `--skip-tags tagged` still skips it
+    let matches_any = |selection: &[String], never_tagged: bool| {
+        selection.iter().any(|tag| {
+            has(tag)
+                || (tag == "tagged" && !untagged && (never_tagged || !has("never")))
+                || (tag == "untagged" && untagged)
+        })
+    };
+
+    // Without --tags every task runs except the ones tagged `never`
+    let selected = if has("always") {
+        true
+    } else if only_tags.is_empty() || only_tags.iter().any(|tag| tag == "all") {
+        !has("never") || matches_any(only_tags, false)
+    } else {
+        matches_any(only_tags, false)
+    };
+    if !selected {
+        return false;
+    }
+
+    if skip_tags.iter().any(|tag| tag == "all") {
+        return has("always") && !skip_tags.iter().any(|tag| tag == "always");
+    }
+    !matches_any(skip_tags, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tag_selection() {
+        let config = tags(&["config"]);
+        let untagged = tags(&[]);
+        let always = tags(&["always"]);
+        let never = tags(&["never", "debug"]);
+
+        // No filters: everything but `never`
+        assert!(selects(&config, &[], &[]));
+        assert!(selects(&untagged, &[], &[]));
+        assert!(!selects(&never, &[], &[]));
+
+        let only_config = tags(&["config"]);
+        assert!(selects(&config, &only_config, &[]));
+        assert!(!selects(&untagged, &only_config, &[]));
+        assert!(selects(&always, &only_config, &[]));
+        assert!(selects(&never, &tags(&["debug"]), &[]));
+
+        assert!(selects(&config, &tags(&["tagged"]), &[]));
+        assert!(!selects(&untagged, &tags(&["tagged"]), &[]));
+        assert!(!selects(&never, &tags(&["tagged"]), &[]));
+        assert!(selects(&untagged, &tags(&["untagged"]), &[]));
+        assert!(!selects(&never, &tags(&["all"]), &[]));
+
+        assert!(!selects(&config, &[], &only_config));
+        assert!(selects(&untagged, &[], &only_config));
+        assert!(!selects(&config, &[], &tags(&["all"])));
+        assert!(selects(&always, &[], &tags(&["all"])));
+        assert!(!selects(&always, &[], &tags(&["always"])));
+        assert!(!selects(&untagged, &[], &tags(&["untagged"])));
+    }
+}
@@ -3,14 +3,25 @@ use anyhow::anyhow;
 use anyhow::Result;
 use log::{debug, info, warn};
 use serde_yaml::{Mapping, Value};
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tera::{Context as TeraContext, Tera};
 
 use crate::inventory::Host;
 use crate::modules::ModuleResult;
 use crate::playbook::filters::register_ansible_filters;
+use crate::playbook::output;
+use crate::playbook::vars::VarSource;
+use crate::playbook::Block;
 use crate::ssh::connection::SshClient;
+use crate::ssh::pool::ConnectionPool;
+
+/// Retries of an `until` loop when the task doesn't set `retries`
+const DEFAULT_RETRIES: u64 = 3;
+/// Seconds between the attempts of an `until` loop when the task doesn't set `delay`
+const DEFAULT_DELAY: u64 = 5;
 
 /// Task result structure for tracking execution status
 #[derive(Debug, Clone)]
@@ -18,6 +29,8 @@ pub struct TaskResult {
     pub changed: bool,
     pub failed: bool,
     pub skipped: bool,
+    pub unreachable: bool, // The host could not be connected to
+    pub ignored: bool,     // Failed, but the task ignores errors
     pub msg: String,
     #[allow(dead_code)]
     pub host: String, // Keep this for future use
@@ -30,12 +43,49 @@ impl TaskResult {
             changed: false,
             failed: false,
             skipped: false,
+            unreachable: false,
+            ignored: false,
             msg: String::new(),
             host: host.to_string(),
             values: HashMap::new(),
         }
     }
 
+    /// The value stored under the task's `register` name
+    pub fn registered_value(&self) -> Value {
+        let mut result_value = Mapping::new();
+        result_value.insert(
+            Value::String("changed".to_string()),
+            Value::Bool(self.changed),
+        );
+        result_value.insert(
+            Value::String("failed".to_string()),
+            Value::Bool(self.failed),
+        );
+        result_value.insert(
+            Value::String("skipped".to_string()),
+            Value::Bool(self.skipped),
+        );
+
+        if self.unreachable {
+            result_value.insert(Value::String("unreachable".to_string()), Value::Bool(true));
+        }
+
+        if !self.msg.is_empty() {
+            result_value.insert(
+                Value::String("msg".to_string()),
+                Value::String(self.msg.clone()),
+            );
+        }
+
+        // Add any module-specific values
+        for (k, v) in &self.values {
+            result_value.insert(Value::String(k.clone()), v.clone());
+        }
+
+        Value::Mapping(result_value)
+    }
+
     pub fn from_module_result(host: &str, module_result: ModuleResult) -> Self {
         let mut values = HashMap::new();
 
@@ -52,6 +102,8 @@ impl TaskResult {
             changed: module_result.changed,
             failed: module_result.failed,
             skipped: false,
+            unreachable: false,
+            ignored: false,
             msg: module_result.msg,
             host: host.to_string(),
             values,
@@ -59,6 +111,18 @@ impl TaskResult {
     }
 }
 
+/// Private argument recording the directory `include_tasks` file names (or `include_role`
+/// role names) are relative to
+pub(crate) const INCLUDE_DIR_ARG: &str = "_include_dir";
+
+/// A task file included on a host by `include_tasks`, or the directory of a role included by
+/// `include_role`, with the loop index and item it was included for
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludedFile {
+    pub path: PathBuf,
+    pub item: Option<(usize, Value)>,
+}
+
 /// Task structure representing a single action in a play
 #[derive(Debug, Clone)]
 pub struct Task {
@@ -69,13 +133,23 @@ pub struct Task {
     pub become_user: String,
     pub register: Option<String>,
     pub when: Option<Value>,
+    pub changed_when: Option<Value>, // Overrides the changed status of the result
+    pub failed_when: Option<Value>,  // Overrides the failed status of the result
+    pub until: Option<Value>,        // Repeats the task until the condition holds on its result
+    pub retries: Option<u64>,
+    pub delay: Option<u64>, // Seconds between until attempts
     pub notify: Vec<String>,
-    pub ignore_errors: bool,
-    #[allow(dead_code)]
-    pub tags: Vec<String>, // Keep this for future use
+    pub ignore_errors: Option<bool>, // Unset inherits the value of the enclosing block
+    pub tags: Vec<String>,           // Select the task with --tags/--skip-tags
     pub loop_items: Option<Value>,
     pub loop_var_name: Option<String>, // Name for loop variable (default: item)
     pub index_var_name: Option<String>, // Name for index variable
+    pub check_mode: Option<bool>,      // Predict changes instead of applying them
+    pub vars: Mapping,                 // Task-level variables, override play and host vars
+    pub role_vars: Mapping,            // Variables of the role the task belongs to
+    pub role_params: Mapping,          // Parameters the task's role was used with
+    pub role_defaults: Mapping,        // Defaults of a role included at run time
+    pub block: Option<Block>,          // Set when the task is a block grouping other tasks
 }
 
 // Helper function to check for and extract simple variable names like {{ var }} or {{ var.sub_var }}
@@ -127,10 +201,52 @@ fn get_nested_value<'a>(key: &str, vars: &'a HashMap<String, Value>) -> Option<&
 }
 
 impl Task {
+    /// Create a task with default keywords, e.g. for tasks generated by the play itself
+    pub fn new(name: &str, module: &str, args: Mapping) -> Self {
+        Task {
+            name: name.to_string(),
+            module: module.to_string(),
+            args,
+            is_become: false,
+            become_user: "root".to_string(),
+            register: None,
+            when: None,
+            changed_when: None,
+            failed_when: None,
+            until: None,
+            retries: None,
+            delay: None,
+            notify: Vec::new(),
+            ignore_errors: None,
+            tags: Vec::new(),
+            loop_items: None,
+            loop_var_name: None,
+            index_var_name: None,
+            check_mode: None,
+            vars: Mapping::new(),
+            role_vars: Mapping::new(),
+            role_params: Mapping::new(),
+            role_defaults: Mapping::new(),
+            block: None,
+        }
+    }
+
+    /// The variables set on the task and its role, each with its precedence
+    pub(crate) fn var_layers(&self) -> [(VarSource, &Mapping); 4] {
+        [
+            (VarSource::RoleDefaults, &self.role_defaults),
+            (VarSource::RoleVars, &self.role_vars),
+            (VarSource::TaskVars, &self.vars),
+            (VarSource::RoleParams, &self.role_params),
+        ]
+    }
+
     pub fn execute(&self, host: &Host, vars: &HashMap<String, Value>) -> Result<TaskResult> {
         let start_time = Instant::now();
         info!("TASK [{}] on host {}", self.name, host.name);
 
+        let vars = &*self.scoped_vars(vars);
+
         let mut tera = Tera::default();
         register_ansible_filters(&mut tera);
         let mut results = Vec::new();
@@ -181,7 +297,7 @@ impl Task {
                 iter_task.loop_items = None;
 
                 let result =
-                    iter_task.execute_module(host, &mut tera, &iter_context, &iter_vars)?;
+                    self.run_attempts(&iter_task, host, &mut tera, &iter_context, &iter_vars)?;
 
                 let elapsed = start_time.elapsed();
                 let execution_time = format!("{:.2}s", elapsed.as_secs_f64());
@@ -209,16 +325,20 @@ impl Task {
                 }
             }
 
-            let result = self.execute_module(host, &mut tera, &context, vars)?;
+            let result = self.run_attempts(self, host, &mut tera, &context, vars)?;
             results.push(result);
         }
 
         let changed = results.iter().any(|r| r.changed);
         let failed = results.iter().any(|r| r.failed);
 
+        let ignore_errors = self.ignore_errors.unwrap_or(false);
         let mut final_result = TaskResult::new(&host.name);
         final_result.changed = changed;
-        final_result.failed = failed && !self.ignore_errors;
+        final_result.failed = failed && !ignore_errors;
+        final_result.unreachable = results.iter().any(|r| r.unreachable);
+        final_result.ignored = failed && ignore_errors && !final_result.unreachable;
+        final_result.skipped = !results.is_empty() && results.iter().all(|r| r.skipped);
 
         if results.len() == 1 {
             final_result.msg = results[0].msg.clone();
@@ -259,6 +379,101 @@ impl Task {
         Ok(final_result)
     }
 
+    /// Work out the files an `include_tasks` task (or the roles an `include_role` task)
+    /// includes on a host: one entry per loop item whose `when` condition holds, with the
+    /// name rendered for that item. `vars` already include the task's own vars.
+    pub fn included_files(&self, vars: &HashMap<String, Value>) -> Result<Vec<IncludedFile>> {
+        let is_role = self.module == "include_role";
+        let file = match if is_role {
+            self.args.get(Value::String("name".to_string()))
+        } else {
+            self.args
+                .get(Value::String("_raw_params".to_string()))
+                .or_else(|| self.args.get(Value::String("file".to_string())))
+        } {
+            Some(Value::String(file)) => file.clone(),
+            _ => {
+                return Err(anyhow!(
+                    "Task '{}' does not name a {} to include",
+                    self.name,
+                    if is_role { "role" } else { "file" }
+                ))
+            }
+        };
+        let include_dir = match self.args.get(Value::String(INCLUDE_DIR_ARG.to_string())) {
+            Some(Value::String(dir)) => Path::new(dir).to_path_buf(),
+            _ => PathBuf::new(),
+        };
+
+        let mut tera = Tera::default();
+        register_ansible_filters(&mut tera);
+
+        let items = match &self.loop_items {
+            Some(items) => {
+                let context = crate::playbook::templar::create_tera_context(vars);
+                self.resolve_loop_items(items, &mut tera, &context, vars)?
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Some)
+                    .collect()
+            }
+            None => vec![None],
+        };
+
+        let loop_var = self.loop_var_name.as_deref().unwrap_or("item");
+        let mut included = Vec::new();
+        for (index, item) in items.into_iter().enumerate() {
+            let mut iter_vars = vars.clone();
+            if let Some(item) = &item {
+                iter_vars.insert(loop_var.to_string(), item.clone());
+                if let Some(index_var) = &self.index_var_name {
+                    iter_vars.insert(index_var.clone(), Value::Number(index.into()));
+                }
+            }
+            let context = crate::playbook::templar::create_tera_context(&iter_vars);
+
+            if let Some(when) = &self.when {
+                if !self.evaluate_condition(when, &mut Tera::default(), &context)? {
+                    debug!("Skipping include of '{}' due to when condition", file);
+                    continue;
+                }
+            }
+
+            let rendered =
+                crate::playbook::templar::render_value(&file, &mut tera, &context, true)?;
+            let file_name = match rendered {
+                Value::String(file_name) => file_name,
+                other => serde_yaml::to_string(&other)?.trim().to_string(),
+            };
+            let path = if is_role {
+                crate::playbook::role::find_role(&file_name, &include_dir)?
+            } else {
+                include_dir.join(file_name)
+            };
+            included.push(IncludedFile {
+                path,
+                item: item.map(|item| (index, item)),
+            });
+        }
+
+        Ok(included)
+    }
+
+    /// The variables visible to this task: task vars override the ones passed in
+    fn scoped_vars<'a>(&self, vars: &'a HashMap<String, Value>) -> Cow<'a, HashMap<String, Value>> {
+        if self.vars.is_empty() {
+            return Cow::Borrowed(vars);
+        }
+
+        let mut merged = vars.clone();
+        for (key, value) in &self.vars {
+            if let Value::String(k) = key {
+                merged.insert(k.clone(), value.clone());
+            }
+        }
+        Cow::Owned(merged)
+    }
+
     fn execute_module(
         &self,
         host: &Host,
@@ -335,27 +550,41 @@ impl Task {
         // Create a new context with the updated vars
         let context_with_date = crate::playbook::templar::create_tera_context(&vars_with_date);
         let mut resolved_args = self.resolve_args(tera, &context_with_date, &vars_with_date)?;
+        if let Some(Value::String(role_path)) = vars.get("role_path") {
+            resolve_role_src(&self.module, &mut resolved_args, Path::new(role_path));
+        }
+
+        if self.check_mode.unwrap_or(false) {
+            // Commands cannot predict their own effects, so they are skipped like in Ansible
+            if self.module == "command" || self.module == "shell" {
+                let mut result = TaskResult::new(&host.name);
+                result.skipped = true;
+                result.msg = "Command would have run if not in check mode".to_string();
+                return Ok(result);
+            }
+            resolved_args.insert(
+                Value::String(crate::modules::CHECK_MODE_ARG.to_string()),
+                Value::Bool(true),
+            );
+        }
 
         let is_local = host.hostname == "localhost" || host.hostname == "127.0.0.1";
+        if self.module == "setup" {
+            return self.gather_facts(host, is_local, &Value::Mapping(resolved_args));
+        }
+
         if is_local {
             resolved_args.insert(
                 Value::String("_host_type".to_string()),
                 Value::String("local".to_string()),
             );
+            if self.module == "command" || self.module == "shell" {
+                let cmd = command_line(&resolved_args);
+                let (exit_code, stdout, stderr) =
+                    crate::modules::local::execute_local_command(&cmd)?;
+                return Ok(self.command_result(host, exit_code, stdout, stderr));
+            }
             let module_result = match self.module.as_str() {
-                "command" | "shell" => {
-                    let raw_param_value = resolved_args
-                        .get(&Value::String("_raw_params".to_string()))
-                        .cloned()
-                        .unwrap_or(Value::Null);
-                    let cmd = match raw_param_value {
-                        Value::String(ref s) => s.clone(),
-                        _ => serde_yaml::to_string(&raw_param_value).unwrap_or_default(),
-                    };
-                    let (exit_code, stdout, stderr) =
-                        crate::modules::local::execute_local_command(&cmd)?;
-                    self.process_command_result(exit_code, stdout, stderr)
-                }
                 "debug" => {
                     crate::modules::debug::execute_adhoc(host, &Value::Mapping(resolved_args))?
                 }
@@ -415,42 +644,42 @@ impl Task {
             return Ok(TaskResult::from_module_result(&host.name, module_result));
         }
 
-        let client = match SshClient::connect(host) {
-            Ok(client) => client,
-            Err(e) => {
-                let mut result = TaskResult::new(&host.name);
-                result.failed = true;
-                result.msg = format!("Failed to connect to host: {}", e);
-                return Ok(result);
-            }
-        };
+        with_session(host, |client| {
+            self.execute_remote(client, host, &resolved_args, &vars_with_date)
+        })
+    }
+
+    /// Run the module on a remote host over an SSH session
+    fn execute_remote(
+        &self,
+        client: &SshClient,
+        host: &Host,
+        resolved_args: &Mapping,
+        vars_with_date: &HashMap<String, Value>,
+    ) -> Result<TaskResult> {
+        let resolved_args = resolved_args.clone();
+        if self.module == "command" || self.module == "shell" {
+            debug!(
+                "Executing command/shell module with args: {:?}",
+                resolved_args
+            );
+            let command = command_line(&resolved_args);
+            debug!("Executing command: {:?}", command);
+            // Non-zero exit codes are reported in the result so failed_when can inspect them
+            let (exit_code, stdout, stderr) = crate::modules::command::run(
+                client,
+                &Value::String(command),
+                self.is_become,
+                &self.become_user,
+            )?;
+            return Ok(self.command_result(host, exit_code, stdout, stderr));
+        }
 
         let module_result = match self.module.as_str() {
-            "command" | "shell" => {
-                debug!(
-                    "Executing command/shell module with args: {:?}",
-                    resolved_args
-                );
-                let raw_param_value = resolved_args
-                    .get(&Value::String("_raw_params".to_string()))
-                    .cloned()
-                    .unwrap_or(Value::Null);
-                let command = match raw_param_value {
-                    Value::String(ref s) => s.clone(),
-                    _ => serde_yaml::to_string(&raw_param_value).unwrap_or_default(),
-                };
-                debug!("Executing command: {:?}", command);
-                crate::modules::command::execute(
-                    &client,
-                    &Value::String(command),
-                    self.is_become,
-                    &self.become_user,
-                )?
-            }
             "debug" => {
                 debug!("Executing debug module with args: {:?}", resolved_args);
                 crate::modules::debug::execute(
-                    &client,
+                    client,
                     &Value::Mapping(resolved_args),
                     self.is_become,
                     &self.become_user,
@@ -459,7 +688,7 @@ impl Task {
             "copy" => {
                 debug!("Executing copy module with args: {:?}", resolved_args);
                 crate::modules::copy::execute(
-                    &client,
+                    client,
                     &Value::Mapping(resolved_args),
                     self.is_become,
                     &self.become_user,
@@ -468,7 +697,7 @@ impl Task {
             "file" => {
                 debug!("Executing file module with args: {:?}", resolved_args);
                 crate::modules::file::execute(
-                    &client,
+                    client,
                     &Value::Mapping(resolved_args),
                     self.is_become,
                     &self.become_user,
@@ -504,7 +733,7 @@ impl Task {
                     template_args
                 );
                 crate::modules::template::execute(
-                    &client,
+                    client,
                     &Value::Mapping(template_args),
                     self.is_become,
                     &self.become_user,
@@ -513,7 +742,7 @@ impl Task {
             "package" => {
                 debug!("Executing package module with args: {:?}", resolved_args);
                 crate::modules::package::execute(
-                    &client,
+                    client,
                     &Value::Mapping(resolved_args),
                     self.is_become,
                     &self.become_user,
@@ -522,20 +751,20 @@ impl Task {
             "service" => {
                 debug!("Executing service module with args: {:?}", resolved_args);
                 crate::modules::service::execute(
-                    &client,
+                    client,
                     &Value::Mapping(resolved_args),
                     self.is_become,
                     &self.become_user,
                 )?
             }
             "lineinfile" => crate::modules::lineinfile::execute(
-                &client,
+                client,
                 &Value::Mapping(resolved_args),
                 self.is_become,
                 &self.become_user,
             )?,
             "user" => crate::modules::user::execute(
-                &client,
+                client,
                 &Value::Mapping(resolved_args),
                 self.is_become,
                 &self.become_user,
@@ -551,6 +780,160 @@ impl Task {
         Ok(TaskResult::from_module_result(&host.name, module_result))
     }
 
+    /// Build the result of a command/shell task, including the fields Ansible registers for them
+    fn command_result(
+        &self,
+        host: &Host,
+        exit_code: i32,
+        stdout: String,
+        stderr: String,
+    ) -> TaskResult {
+        let stdout_lines = text_lines(&stdout);
+        let stderr_lines = text_lines(&stderr);
+        let module_result = self.process_command_result(exit_code, stdout.clone(), stderr.clone());
+
+        let mut result = TaskResult::from_module_result(&host.name, module_result);
+        result
+            .values
+            .insert("rc".to_string(), Value::Number(exit_code.into()));
+        result
+            .values
+            .insert("stdout".to_string(), Value::String(stdout));
+        result
+            .values
+            .insert("stderr".to_string(), Value::String(stderr));
+        result
+            .values
+            .insert("stdout_lines".to_string(), stdout_lines);
+        result
+            .values
+            .insert("stderr_lines".to_string(), stderr_lines);
+        result
+    }
+
+    /// Override the result status with the task's `changed_when` / `failed_when` conditions.
+    /// They see the result under the `register` name, a task without one only its variables.
+    /// Skipped and unreachable results are left as they are.
+    fn apply_result_conditions(
+        &self,
+        result: &mut TaskResult,
+        vars: &HashMap<String, Value>,
+    ) -> Result<()> {
+        if result.skipped
+            || result.unreachable
+            || (self.changed_when.is_none() && self.failed_when.is_none())
+        {
+            return Ok(());
+        }
+
+        let context = self.result_context(result, vars);
+        if let Some(changed_when) = &self.changed_when {
+            result.changed =
+                self.evaluate_condition(changed_when, &mut Tera::default(), &context)?;
+        }
+        if let Some(failed_when) = &self.failed_when {
+            let failed = self.evaluate_condition(failed_when, &mut Tera::default(), &context)?;
+            if failed && !result.failed {
+                result.msg = "failed_when condition matched".to_string();
+            }
+            result.failed = failed;
+        }
+
+        Ok(())
+    }
+
+    /// Variables for conditions on a result: the task's variables plus the result under its
+    /// `register` name. Its fields aren't spread into the variables, where they would shadow
+    /// variables such as `msg` or `changed`.
+    fn result_context(&self, result: &TaskResult, vars: &HashMap<String, Value>) -> TeraContext {
+        let mut condition_vars = vars.clone();
+        if let Some(register) = &self.register {
+            condition_vars.insert(register.clone(), result.registered_value());
+        }
+        crate::playbook::templar::create_tera_context(&condition_vars)
+    }
+
+    /// Run the module once, or with `until` until the condition holds on the result
+    /// or the retries are used up. The number of attempts is recorded in the result.
+    fn run_attempts(
+        &self,
+        module_task: &Task,
+        host: &Host,
+        tera: &mut Tera,
+        context: &TeraContext,
+        vars: &HashMap<String, Value>,
+    ) -> Result<TaskResult> {
+        let Some(until) = &self.until else {
+            let mut result = module_task.execute_module(host, tera, context, vars)?;
+            self.apply_result_conditions(&mut result, vars)?;
+            return Ok(result);
+        };
+
+        self.retry_until(until, host, vars, || {
+            module_task.execute_module(host, tera, context, vars)
+        })
+    }
+
+    /// Repeat `attempt` until the `until` condition holds on its result or the retries are
+    /// used up. An attempt that errors, such as a dropped connection, is retried as well.
+    fn retry_until(
+        &self,
+        until: &Value,
+        host: &Host,
+        vars: &HashMap<String, Value>,
+        mut attempt: impl FnMut() -> Result<TaskResult>,
+    ) -> Result<TaskResult> {
+        let retries = self.retries.unwrap_or(DEFAULT_RETRIES);
+        let delay = self.delay.unwrap_or(DEFAULT_DELAY);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let (mut result, errored) = match attempt() {
+                Ok(mut result) => {
+                    self.apply_result_conditions(&mut result, vars)?;
+                    (result, false)
+                }
+                Err(e) => {
+                    let mut result = TaskResult::new(&host.name);
+                    result.failed = true;
+                    result.msg = format!("Task execution error: {:#}", e);
+                    (result, true)
+                }
+            };
+            result
+                .values
+                .insert("attempts".to_string(), Value::Number(attempts.into()));
+            // Like Ansible, an unreachable host isn't retried
+            if result.skipped || result.unreachable {
+                return Ok(result);
+            }
+
+            // The condition can't hold on an attempt that didn't produce a result
+            if !errored {
+                let result_context = self.result_context(&result, vars);
+                if self.evaluate_condition(until, &mut Tera::default(), &result_context)? {
+                    return Ok(result);
+                }
+            }
+            if attempts > retries {
+                result.failed = true;
+                result.msg = format!(
+                    "until condition not met after {} attempts: {}",
+                    attempts, result.msg
+                );
+                return Ok(result);
+            }
+
+            output::emit(format!(
+                "\x1B[31mFAILED - RETRYING: [{}]: {} ({} retries left).\x1B[0m",
+                host.name,
+                self.name,
+                retries + 1 - attempts
+            ));
+            std::thread::sleep(Duration::from_secs(delay));
+        }
+    }
+
     fn process_command_result(
         &self,
         exit_code: i32,
@@ -595,6 +978,14 @@ impl Task {
             crate::playbook::templar::evaluate_condition(condition_str, tera, context)
         } else if let Value::Bool(b) = condition {
             Ok(*b)
+        } else if let Value::Sequence(conditions) = condition {
+            // A list of conditions only holds when every one of them does
+            for condition in conditions {
+                if !self.evaluate_condition(condition, tera, context)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
         } else {
             warn!(
                 "Could not evaluate non-string/non-bool condition: {:?}",
@@ -686,6 +1077,23 @@ impl Task {
         }
     }
 
+    /// Run the setup module, returning the facts under `ansible_facts` in the result values
+    fn gather_facts(&self, host: &Host, is_local: bool, args: &Value) -> Result<TaskResult> {
+        if !is_local {
+            return with_session(host, |client| {
+                let facts =
+                    crate::modules::setup::gather_facts(&|cmd| client.execute_command(cmd), args)?;
+                Ok(facts_result(host, facts))
+            });
+        }
+
+        let facts = crate::modules::setup::gather_facts(
+            &crate::modules::local::execute_local_command,
+            args,
+        )?;
+        Ok(facts_result(host, facts))
+    }
+
     fn resolve_args(
         &self,
         _tera: &mut Tera, // Original Tera instance (can be kept for potential future shared state)
@@ -887,14 +1295,106 @@ impl Task {
     }
 }
 
+/// Run a module on a pooled SSH session of the host. A session that dropped since its last use
+/// is only noticed when it fails to open a channel. The module is run once more on a new
+/// session only when its very first channel failed, so nothing it sends ran twice; a failure
+/// after some of its commands already ran is returned as is.
+fn with_session(host: &Host, run: impl Fn(&SshClient) -> Result<TaskResult>) -> Result<TaskResult> {
+    let pool = ConnectionPool::global();
+    let client = match pool.get(host) {
+        Ok(client) => client,
+        Err(e) => return Ok(connection_failed(host, &e)),
+    };
+    let channels_before = client.channels_opened();
+    match run(&client) {
+        Err(e) if client.is_broken() && client.channels_opened() == channels_before => {
+            info!(
+                "SSH connection to {} dropped ({:#}), reconnecting",
+                host.name, e
+            );
+            pool.invalidate(host, &client);
+            match pool.get(host) {
+                Ok(client) => run(&client),
+                Err(e) => Ok(connection_failed(host, &e)),
+            }
+        }
+        result => result,
+    }
+}
+
+/// The result of the setup module, with the facts under `ansible_facts`
+fn facts_result(host: &Host, facts: Mapping) -> TaskResult {
+    let mut result = TaskResult::new(&host.name);
+    result.msg = format!("Gathered {} facts", facts.len());
+    result
+        .values
+        .insert("ansible_facts".to_string(), Value::Mapping(facts));
+    result
+}
+
+/// The result of a task on a host that could not be connected to
+fn connection_failed(host: &Host, error: &anyhow::Error) -> TaskResult {
+    let mut result = TaskResult::new(&host.name);
+    result.failed = true;
+    result.unreachable = true;
+    result.msg = format!("Failed to connect to host: {}", error);
+    result
+}
+
+/// The command line of a command/shell task
+fn command_line(args: &Mapping) -> String {
+    match args.get(Value::String("_raw_params".to_string())) {
+        Some(Value::String(cmd)) => cmd.clone(),
+        Some(other) => serde_yaml::to_string(other).unwrap_or_default(),
+        None => String::new(),
+    }
+}
+
+fn text_lines(text: &str) -> Value {
+    Value::Sequence(
+        text.lines()
+            .map(|line| Value::String(line.to_string()))
+            .collect(),
+    )
+}
+
+/// Look up a relative `src` of a role task in the role's `files/` (copy) or `templates/`
+/// (template) directory, then in the role directory itself
+fn resolve_role_src(module: &str, args: &mut Mapping, role_path: &Path) {
+    let dir = match module {
+        "copy" => "files",
+        "template" => "templates",
+        _ => return,
+    };
+    let src_key = Value::String("src".to_string());
+    let Some(Value::String(src)) = args.get(&src_key) else {
+        return;
+    };
+    if Path::new(src).is_absolute() {
+        return;
+    }
+
+    if let Some(found) = [role_path.join(dir).join(src), role_path.join(src)]
+        .into_iter()
+        .find(|candidate| candidate.exists())
+    {
+        debug!("Resolved role source '{}' to {}", src, found.display());
+        args.insert(src_key, Value::String(found.to_string_lossy().to_string()));
+    }
+}
+
 pub(crate) fn print_task_result(
     host_name: &str,
     _task_name: &str,
     result: &TaskResult,
     execution_time: &str,
 ) {
-    let status = if result.failed {
+    let status = if result.unreachable {
+        "unreachable"
+    } else if result.failed {
         "failed"
+    } else if result.ignored {
+        "failed (ignored)"
     } else if result.skipped {
         "skipped"
     } else if result.changed {
@@ -903,7 +1403,7 @@ pub(crate) fn print_task_result(
         "ok"
     };
 
-    let color_code = if result.failed {
+    let color_code = if result.failed || result.unreachable || result.ignored {
         "\x1B[31m"
     } else if result.skipped {
         "\x1B[33m"
@@ -915,20 +1415,20 @@ pub(crate) fn print_task_result(
 
     let reset_code = "\x1B[0m";
 
-    println!(
+    output::emit(format!(
         "{} => {}{}: {} ({}){}",
         host_name, color_code, status, result.msg, execution_time, reset_code
-    );
+    ));
 
     if let Some(Value::String(stdout)) = result.values.get("stdout") {
         if !stdout.is_empty() {
-            println!("    {}", stdout.trim());
+            output::emit(format!("    {}", stdout.trim()));
         }
     }
 
     if let Some(Value::String(stderr)) = result.values.get("stderr") {
         if !stderr.is_empty() {
-            println!("    {}{}{}", color_code, stderr.trim(), reset_code);
+            output::emit(format!("    {}{}{}", color_code, stderr.trim(), reset_code));
         }
     }
 }
@@ -963,7 +1463,7 @@ pub(crate) fn print_loop_iteration_result(
 
     let reset_code = "\x1B[0m";
 
-    println!(
+    output::emit(format!(
         "{}{} (item={}/{}) => {}{}{}: {} ({}){}",
         color_code,
         host_name,
@@ -975,12 +1475,12 @@ pub(crate) fn print_loop_iteration_result(
         result.msg,
         execution_time,
         reset_code
-    );
+    ));
 
     if let Some(Value::String(stdout)) = result.values.get("stdout") {
         if !stdout.is_empty() {
             for line in stdout.trim().lines() {
-                println!("    {}", line);
+                output::emit(format!("    {}", line));
             }
         }
     }
@@ -988,7 +1488,7 @@ pub(crate) fn print_loop_iteration_result(
     if let Some(Value::String(stderr)) = result.values.get("stderr") {
         if !stderr.is_empty() {
             for line in stderr.trim().lines() {
-                println!("    {}{}{}", color_code, line, reset_code);
+                output::emit(format!("    {}{}{}", color_code, line, reset_code));
             }
         }
     }
@@ -1008,12 +1508,23 @@ mod tests {
             become_user: "root".to_string(),
             register: None,
             when: None,
+            changed_when: None,
+            failed_when: None,
+            until: None,
+            retries: None,
+            delay: None,
             notify: Vec::new(),
-            ignore_errors: false,
+            ignore_errors: None,
             tags: Vec::new(),
             loop_items: None,
             loop_var_name: None,
             index_var_name: None,
+            check_mode: None,
+            vars: Mapping::new(),
+            role_vars: Mapping::new(),
+            role_params: Mapping::new(),
+            role_defaults: Mapping::new(),
+            block: None,
         }
     }
 
@@ -1279,6 +1790,231 @@ mod tests {
             .contains("resolved to a non-sequence value"));
     }
 
+    #[test]
+    fn test_resolve_role_src() {
+        let role = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(role.path().join("files")).unwrap();
+        std::fs::write(role.path().join("files/app.conf"), "x").unwrap();
+
+        let mut args = Mapping::new();
+        args.insert(
+            Value::String("src".to_string()),
+            Value::String("app.conf".to_string()),
+        );
+        resolve_role_src("copy", &mut args, role.path());
+        assert_eq!(
+            args.get("src"),
+            Some(&Value::String(
+                role.path()
+                    .join("files/app.conf")
+                    .to_string_lossy()
+                    .to_string()
+            ))
+        );
+
+        // Templates are looked up in templates/, unknown files are left alone
+        args.insert(
+            Value::String("src".to_string()),
+            Value::String("app.conf".to_string()),
+        );
+        resolve_role_src("template", &mut args, role.path());
+        assert_eq!(
+            args.get("src"),
+            Some(&Value::String("app.conf".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_check_mode_skips_command() {
+        let mut task = create_test_task();
+        task.module = "command".to_string();
+        task.args.insert(
+            Value::String("_raw_params".to_string()),
+            Value::String("touch /tmp/rustsible_check_mode_should_not_exist".to_string()),
+        );
+        task.check_mode = Some(true);
+
+        let host = crate::testing::create_test_host("localhost", "localhost", 22, None, None);
+        let result = task.execute(&host, &HashMap::new()).unwrap();
+
+        assert!(result.skipped);
+        assert!(!result.changed);
+        assert!(!std::path::Path::new("/tmp/rustsible_check_mode_should_not_exist").exists());
+    }
+
+    #[test]
+    fn test_changed_when_and_failed_when() {
+        let mut task = create_test_task();
+        task.module = "command".to_string();
+        task.args.insert(
+            Value::String("_raw_params".to_string()),
+            Value::String("echo ready; exit 3".to_string()),
+        );
+        task.register = Some("probe".to_string());
+        task.changed_when = Some(Value::Bool(false));
+        task.failed_when = Some(Value::String("probe.rc != 3".to_string()));
+
+        let host = crate::testing::create_test_host("localhost", "localhost", 22, None, None);
+        let result = task.execute(&host, &HashMap::new()).unwrap();
+        assert!(!result.changed);
+        assert!(!result.failed);
+        assert_eq!(result.values.get("rc"), Some(&Value::Number(3.into())));
+        assert_eq!(
+            result.values.get("stdout_lines"),
+            Some(&Value::Sequence(vec![Value::String("ready".to_string())]))
+        );
+
+        // A list of conditions fails only when all of them hold
+        task.args.insert(
+            Value::String("_raw_params".to_string()),
+            Value::String("echo ready".to_string()),
+        );
+        task.changed_when = None;
+        task.failed_when = Some(Value::Sequence(vec![
+            Value::String("probe.rc == 0".to_string()),
+            Value::String("'ready' in probe.stdout".to_string()),
+        ]));
+        let result = task.execute(&host, &HashMap::new()).unwrap();
+        assert!(result.failed);
+        assert!(result.changed);
+
+        // Without register the conditions only see variables, the result isn't published
+        task.register = None;
+        task.failed_when = Some(Value::String("ansible_result is defined".to_string()));
+        let result = task.execute(&host, &HashMap::new()).unwrap();
+        assert!(!result.failed);
+        task.failed_when = Some(Value::Bool(true));
+        let result = task.execute(&host, &HashMap::new()).unwrap();
+        assert!(result.failed);
+
+        // The conditions don't apply to a host that could not be reached
+        task.failed_when = Some(Value::Bool(false));
+        task.changed_when = Some(Value::Bool(true));
+        let mut result = connection_failed(&host, &anyhow!("connection refused"));
+        task.apply_result_conditions(&mut result, &HashMap::new())
+            .unwrap();
+        assert!(result.failed && result.unreachable);
+        assert!(!result.changed);
+    }
+
+    #[test]
+    fn test_result_conditions_keep_variables() {
+        let mut task = create_test_task();
+        task.module = "command".to_string();
+        task.args.insert(
+            Value::String("_raw_params".to_string()),
+            Value::String("echo ready".to_string()),
+        );
+        task.register = Some("probe".to_string());
+        // msg and changed are result fields too, the play variables must win
+        task.failed_when = Some(Value::String("msg != 'expected'".to_string()));
+        task.changed_when = Some(Value::String("changed == 'yes'".to_string()));
+
+        let vars = HashMap::from([
+            ("msg".to_string(), Value::String("expected".to_string())),
+            ("changed".to_string(), Value::String("no".to_string())),
+        ]);
+        let host = crate::testing::create_test_host("localhost", "localhost", 22, None, None);
+        let result = task.execute(&host, &vars).unwrap();
+        assert!(!result.failed);
+        assert!(!result.changed);
+    }
+
+    #[test]
+    fn test_until_retries_until_condition_holds() {
+        let dir = tempfile::tempdir().unwrap();
+        let counter = dir.path().join("counter");
+        let mut task = create_test_task();
+        task.module = "shell".to_string();
+        task.args.insert(
+            Value::String("_raw_params".to_string()),
+            Value::String(format!("echo x >> {0}; wc -l < {0}", counter.display())),
+        );
+        task.register = Some("poll".to_string());
+        task.until = Some(Value::String("'3' in poll.stdout".to_string()));
+        task.delay = Some(0);
+
+        let host = crate::testing::create_test_host("localhost", "localhost", 22, None, None);
+        let result = task.execute(&host, &HashMap::new()).unwrap();
+        assert!(!result.failed);
+        assert_eq!(
+            result.values.get("attempts"),
+            Some(&Value::Number(3.into()))
+        );
+
+        // Retries exhausted: the first attempt plus one retry
+        task.retries = Some(1);
+        task.until = Some(Value::Bool(false));
+        let result = task.execute(&host, &HashMap::new()).unwrap();
+        assert!(result.failed);
+        assert_eq!(
+            result.values.get("attempts"),
+            Some(&Value::Number(2.into()))
+        );
+    }
+
+    #[test]
+    fn test_until_retries_attempts_that_error() {
+        let mut task = create_test_task();
+        task.register = Some("poll".to_string());
+        task.until = Some(Value::String("poll.stdout == 'ready'".to_string()));
+        task.retries = Some(3);
+        task.delay = Some(0);
+        let until = task.until.clone().unwrap();
+        let host = crate::testing::create_test_host("localhost", "localhost", 22, None, None);
+
+        // The first attempt errors, the second one meets the condition
+        let mut calls = 0;
+        let result = task
+            .retry_until(&until, &host, &HashMap::new(), || {
+                calls += 1;
+                if calls == 1 {
+                    return Err(anyhow!("connection reset"));
+                }
+                let mut result = TaskResult::new("localhost");
+                result
+                    .values
+                    .insert("stdout".to_string(), Value::String("ready".to_string()));
+                Ok(result)
+            })
+            .unwrap();
+        assert!(!result.failed);
+        assert_eq!(
+            result.values.get("attempts"),
+            Some(&Value::Number(2.into()))
+        );
+
+        // Attempts that keep erroring fail the task once the retries are used up
+        let result = task
+            .retry_until(&until, &host, &HashMap::new(), || {
+                Err(anyhow!("connection reset"))
+            })
+            .unwrap();
+        assert!(result.failed);
+        assert!(result.msg.contains("connection reset"), "{}", result.msg);
+        assert_eq!(
+            result.values.get("attempts"),
+            Some(&Value::Number(4.into()))
+        );
+
+        // An unreachable host is not retried
+        let mut calls = 0;
+        let result = task
+            .retry_until(&until, &host, &HashMap::new(), || {
+                calls += 1;
+                let mut result = TaskResult::new("localhost");
+                result.unreachable = true;
+                Ok(result)
+            })
+            .unwrap();
+        assert_eq!(calls, 1);
+        assert!(result.unreachable);
+        assert_eq!(
+            result.values.get("attempts"),
+            Some(&Value::Number(1.into()))
+        );
+    }
+
     #[test]
     fn test_resolve_loop_items_bare_string() {
         let task = create_test_task();
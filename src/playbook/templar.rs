@@ -297,6 +297,16 @@ fn extract_undefined_variables(template: &str, context: &TeraContext) -> Vec<Str
     check_template_variables(template, context)
 }
 
+/// Render a string with the given variables, such as the arguments of an ad-hoc command
+pub fn render_string(input: &str, vars: &HashMap<String, Value>) -> Result<String> {
+    let mut tera = Tera::default();
+    register_ansible_filters(&mut tera);
+    match render_value(input, &mut tera, &create_tera_context(vars), true)? {
+        Value::String(rendered) => Ok(rendered),
+        other => Ok(serde_yaml::to_string(&other)?.trim_end().to_string()),
+    }
+}
+
 /// Helper function to convert a variable map to a Tera Context.
 /// This should ideally happen once before processing a task or loop.
 pub fn create_tera_context(vars: &HashMap<String, Value>) -> TeraContext {
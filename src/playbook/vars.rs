@@ -0,0 +1,347 @@
+use anyhow::{Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Parse the values of `-e/--extra-vars` into one mapping; later values override earlier ones.
+///
+/// A value is either `@file` (a YAML or JSON file of variables), an inline YAML or JSON mapping
+/// such as `{"version": "1.2"}`, or space separated `key=value` pairs. Like in Ansible,
+/// `key=value` values are always strings; use the YAML or JSON form for other types.
+pub fn parse_extra_vars(values: &[String]) -> Result<Mapping> {
+    let mut extra_vars = Mapping::new();
+    for value in values {
+        let value = value.trim();
+        let vars = if let Some(path) = value.strip_prefix('@') {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read extra vars file: {}", path))?;
+            parse_mapping(&content).with_context(|| format!("Invalid extra vars file: {}", path))?
+        } else if value.starts_with('{') {
+            parse_mapping(value).with_context(|| format!("Invalid extra vars: {}", value))?
+        } else {
+            parse_key_values(value)?
+        };
+        for (key, value) in vars {
+            extra_vars.insert(key, value);
+        }
+    }
+    Ok(extra_vars)
+}
+
+/// Where a variable of a host comes from, from the lowest precedence to the highest,
+/// following Ansible's variable precedence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VarSource {
+    RoleDefaults,
+    /// Variables of an inventory group, groups nested deeper win
+    InventoryGroup(String),
+    /// Variables of a group from `group_vars` files
+    GroupVarsFile(String),
+    InventoryHost,
+    HostVarsFile,
+    Facts,
+    PlayVars,
+    VarsFile(String),
+    /// Variables from the `vars/main.yml` of a task's role
+    RoleVars,
+    /// Block vars and task vars, merged when the playbook is loaded
+    TaskVars,
+    Registered,
+    /// Parameters of a role given in `roles:` or in its dependencies
+    RoleParams,
+    ExtraVars,
+}
+
+impl VarSource {
+    fn rank(&self) -> u8 {
+        match self {
+            VarSource::RoleDefaults => 0,
+            VarSource::InventoryGroup(_) => 1,
+            VarSource::GroupVarsFile(_) => 2,
+            VarSource::InventoryHost => 3,
+            VarSource::HostVarsFile => 4,
+            VarSource::Facts => 5,
+            VarSource::PlayVars => 6,
+            VarSource::VarsFile(_) => 7,
+            VarSource::RoleVars => 8,
+            VarSource::TaskVars => 9,
+            VarSource::Registered => 10,
+            VarSource::RoleParams => 11,
+            VarSource::ExtraVars => 12,
+        }
+    }
+}
+
+impl fmt::Display for VarSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VarSource::RoleDefaults => write!(f, "role defaults"),
+            VarSource::InventoryGroup(group) => write!(f, "inventory group '{}'", group),
+            VarSource::GroupVarsFile(group) => write!(f, "group_vars of '{}'", group),
+            VarSource::InventoryHost => write!(f, "inventory host"),
+            VarSource::HostVarsFile => write!(f, "host_vars"),
+            VarSource::Facts => write!(f, "facts"),
+            VarSource::PlayVars => write!(f, "play vars"),
+            VarSource::VarsFile(file) => write!(f, "vars file '{}'", file),
+            VarSource::RoleVars => write!(f, "role vars"),
+            VarSource::TaskVars => write!(f, "task vars"),
+            VarSource::Registered => write!(f, "registered vars"),
+            VarSource::RoleParams => write!(f, "role params"),
+            VarSource::ExtraVars => write!(f, "extra vars"),
+        }
+    }
+}
+
+/// The variables of a host during a play, kept per source so each value keeps its precedence
+/// when facts, registered results or task vars are added later
+#[derive(Debug, Clone, Default)]
+pub struct HostVars {
+    layers: Vec<(VarSource, Mapping)>, // Lowest precedence first
+}
+
+impl HostVars {
+    /// Add the variables of a source; layers of the same rank keep the order they were added in
+    pub fn add_layer(&mut self, source: VarSource, vars: Mapping) {
+        let position = self
+            .layers
+            .iter()
+            .position(|(existing, _)| existing.rank() > source.rank())
+            .unwrap_or(self.layers.len());
+        self.layers.insert(position, (source, vars));
+    }
+
+    /// Set a variable in the layer of `source`, adding the layer if needed
+    pub fn insert(&mut self, source: VarSource, key: &str, value: Value) {
+        let key = Value::String(key.to_string());
+        match self
+            .layers
+            .iter_mut()
+            .find(|(existing, _)| *existing == source)
+        {
+            Some((_, vars)) => {
+                vars.insert(key, value);
+            }
+            None => {
+                let mut vars = Mapping::new();
+                vars.insert(key, value);
+                self.add_layer(source, vars);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, source: &VarSource, key: &str) -> Option<Value> {
+        self.layers
+            .iter_mut()
+            .find(|(existing, _)| existing == source)
+            .and_then(|(_, vars)| vars.remove(key))
+    }
+
+    /// The value of every variable, taken from the source with the highest precedence,
+    /// with the variables of the task about to run (see `Task::var_layers`) at their own precedence
+    pub fn merged_with(&self, task_layers: &[(VarSource, &Mapping)]) -> HashMap<String, Value> {
+        let mut layers: Vec<(&VarSource, &Mapping)> = self
+            .layers
+            .iter()
+            .map(|(source, vars)| (source, vars))
+            .collect();
+        for (source, vars) in task_layers {
+            let position = layers
+                .iter()
+                .position(|(existing, _)| existing.rank() > source.rank())
+                .unwrap_or(layers.len());
+            layers.insert(position, (source, *vars));
+        }
+
+        let mut merged = HashMap::new();
+        for (_, vars) in layers {
+            insert_all(&mut merged, vars);
+        }
+        merged
+    }
+
+    /// Every source that sets each variable, the one whose value is used first
+    pub fn origins(&self) -> BTreeMap<String, Vec<(&VarSource, &Value)>> {
+        let mut origins: BTreeMap<String, Vec<(&VarSource, &Value)>> = BTreeMap::new();
+        for (source, vars) in self.layers.iter().rev() {
+            for (key, value) in vars {
+                if let Value::String(key) = key {
+                    origins
+                        .entry(key.clone())
+                        .or_default()
+                        .push((source, value));
+                }
+            }
+        }
+        origins
+    }
+}
+
+fn insert_all<'a>(
+    merged: &mut HashMap<String, Value>,
+    vars: impl IntoIterator<Item = (&'a Value, &'a Value)>,
+) {
+    for (key, value) in vars {
+        if let Value::String(key) = key {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+fn parse_mapping(content: &str) -> Result<Mapping> {
+    match serde_yaml::from_str(content)? {
+        Value::Mapping(vars) => Ok(vars),
+        Value::Null => Ok(Mapping::new()),
+        _ => Err(anyhow::anyhow!(
+            "extra vars must be a mapping of names to values"
+        )),
+    }
+}
+
+/// Parse `key=value` pairs separated by whitespace, where values may be quoted
+fn parse_key_values(input: &str) -> Result<Mapping> {
+    let mut vars = Mapping::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(vars);
+        }
+
+        let mut word = String::new();
+        let mut quote = None;
+        while let Some(c) = chars.next() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => word.push(c),
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c.is_whitespace() => break,
+                None if c == '\\' => word.extend(chars.next()),
+                None => word.push(c),
+            }
+        }
+        if quote.is_some() {
+            return Err(anyhow::anyhow!(
+                "Unterminated quote in extra vars: {}",
+                input
+            ));
+        }
+
+        match word.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                vars.insert(
+                    Value::String(key.to_string()),
+                    Value::String(value.to_string()),
+                );
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid extra var '{}', expected key=value, a YAML/JSON mapping or @file",
+                    word
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn get<'a>(vars: &'a Mapping, key: &str) -> Option<&'a Value> {
+        vars.get(Value::String(key.to_string()))
+    }
+
+    fn vars(yaml: &str) -> Mapping {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_host_vars_precedence() {
+        let mut host_vars = HostVars::default();
+        // Added out of order, the layers still apply by precedence
+        host_vars.add_layer(VarSource::ExtraVars, vars("{version: extra}"));
+        host_vars.add_layer(
+            VarSource::PlayVars,
+            vars("{version: play, port: 80, user: play}"),
+        );
+        host_vars.add_layer(
+            VarSource::InventoryGroup("all".into()),
+            vars("{user: all, zone: all}"),
+        );
+        host_vars.add_layer(VarSource::InventoryGroup("web".into()), vars("{zone: web}"));
+        host_vars.add_layer(VarSource::RoleDefaults, vars("{port: 8080, workers: 2}"));
+        host_vars.insert(VarSource::Facts, "user", Value::String("fact".into()));
+        host_vars.insert(
+            VarSource::Registered,
+            "result",
+            Value::String("registered".into()),
+        );
+
+        let task_vars = vars("{port: 81, result: task, version: task, role: task}");
+        let role_vars = vars("{port: 82, role: role}");
+        let role_params = vars("{result: param}");
+        let merged = host_vars.merged_with(&[
+            (VarSource::RoleVars, &role_vars),
+            (VarSource::TaskVars, &task_vars),
+            (VarSource::RoleParams, &role_params),
+        ]);
+        let get = |name: &str| {
+            merged
+                .get(name)
+                .map(|value| serde_json::to_string(value).unwrap())
+        };
+        assert_eq!(get("version").as_deref(), Some("\"extra\""));
+        assert_eq!(get("port").as_deref(), Some("81"));
+        assert_eq!(get("user").as_deref(), Some("\"play\""));
+        assert_eq!(get("zone").as_deref(), Some("\"web\""));
+        assert_eq!(get("workers").as_deref(), Some("2"));
+        assert_eq!(get("role").as_deref(), Some("\"task\""));
+        // Role params win over registered results
+        assert_eq!(get("result").as_deref(), Some("\"param\""));
+
+        let origins = host_vars.origins();
+        let zone: Vec<String> = origins["zone"]
+            .iter()
+            .map(|(source, _)| source.to_string())
+            .collect();
+        assert_eq!(zone, ["inventory group 'web'", "inventory group 'all'"]);
+
+        host_vars.remove(&VarSource::Registered, "result");
+        assert!(!host_vars.merged_with(&[]).contains_key("result"));
+    }
+
+    #[test]
+    fn test_parse_extra_vars() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "replicas: 3\nversion: from-file\nfeatures: [a, b]").unwrap();
+
+        let vars = parse_extra_vars(&[
+            "version=1.10 msg='hello world' path=a=b".to_string(),
+            format!("@{}", file.path().display()),
+            r#"{"debug": true, "version": "2.0"}"#.to_string(),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            get(&vars, "msg"),
+            Some(&Value::String("hello world".into()))
+        );
+        assert_eq!(get(&vars, "path"), Some(&Value::String("a=b".into())));
+        assert_eq!(get(&vars, "replicas"), Some(&Value::Number(3.into())));
+        assert_eq!(get(&vars, "debug"), Some(&Value::Bool(true)));
+        assert!(matches!(get(&vars, "features"), Some(Value::Sequence(items)) if items.len() == 2));
+        // The last value wins
+        assert_eq!(get(&vars, "version"), Some(&Value::String("2.0".into())));
+
+        let vars = parse_extra_vars(&["version=1.10".to_string()]).unwrap();
+        assert_eq!(get(&vars, "version"), Some(&Value::String("1.10".into())));
+
+        assert!(parse_extra_vars(&["novalue".to_string()]).is_err());
+        assert!(parse_extra_vars(&["msg='open".to_string()]).is_err());
+        assert!(parse_extra_vars(&["{not: [closed".to_string()]).is_err());
+        assert!(parse_extra_vars(&["@/nonexistent/vars.yml".to_string()]).is_err());
+    }
+}
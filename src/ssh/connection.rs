@@ -6,6 +6,7 @@ use ssh2::Session;
 use std::io::{Read, Write};
 use std::net::TcpStream;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use uuid::Uuid;
 
 use crate::inventory::Host;
@@ -32,6 +33,8 @@ pub struct SshClient {
     session: Session,
     host: String,
     sudo_password: String,
+    broken: AtomicBool,    // Set once the session failed to open a channel
+    channels: AtomicUsize, // Number of channels opened on the session
 }
 
 impl SshConnection for SshClient {
@@ -45,6 +48,8 @@ impl SshConnection for SshClient {
         let mut channel = self
             .session
             .channel_session()
+            .map(|channel| self.count_channel(channel))
+            .map_err(|e| self.mark_broken(e))
             .context("Failed to open SSH channel")?;
 
         channel.exec(command).context(format!(
@@ -234,6 +239,8 @@ impl SshConnection for SshClient {
                 local_content.len() as u64,
                 None,
             )
+            .map(|channel| self.count_channel(channel))
+            .map_err(|e| self.mark_broken(e))
             .context(format!("Failed to initiate SCP upload to {}", remote_path))?;
 
         remote_file
@@ -266,7 +273,7 @@ impl SshClient {
         // 输出所有变量以便调试
         debug!("Host variables:");
         for (key, value) in &host.variables {
-            debug!("  {} = {}", key, value);
+            debug!("  {} = {:?}", key, value);
         }
 
         // 显示将用于连接的重要变量
@@ -298,12 +305,10 @@ impl SshClient {
         session.handshake().context("SSH handshake failed")?;
 
         // 获取用户名
-        let username = host.get_ssh_user().map(|s| s.as_str()).unwrap_or("root");
+        let ssh_user = host.get_ssh_user();
+        let username = ssh_user.as_deref().unwrap_or("root");
         // 获取sudo密码
-        let sudo_password = host
-            .get_ssh_sudo_password()
-            .map(|s| s.as_str())
-            .unwrap_or("");
+        let sudo_password = host.get_ssh_sudo_password().unwrap_or_default();
 
         debug!("Using SSH username: {}", username);
         let mut auth_succeeded = false;
@@ -311,7 +316,7 @@ impl SshClient {
         // 尝试密码认证（ansible_ssh_pass）
         if let Some(password) = host.get_ssh_password() {
             debug!("Attempting password authentication for user {}", username);
-            match session.userauth_password(username, password) {
+            match session.userauth_password(username, &password) {
                 Ok(_) => {
                     info!("Password authentication succeeded for {}", username);
                     auth_succeeded = true;
@@ -325,7 +330,7 @@ impl SshClient {
         // 如果密码认证失败，尝试私钥认证
         if !auth_succeeded {
             if let Some(key_path) = host.get_ssh_private_key() {
-                let path = Path::new(key_path);
+                let path = Path::new(&key_path);
                 debug!(
                     "Attempting private key authentication with key: {}",
                     key_path
@@ -369,9 +374,43 @@ impl SshClient {
             session,
             host: host.name.clone(),
             sudo_password: sudo_password.to_string(),
+            broken: AtomicBool::new(false),
+            channels: AtomicUsize::new(0),
         })
     }
 
+    /// Whether the session failed to open a channel, e.g. after a network drop or server restart
+    pub fn is_broken(&self) -> bool {
+        self.broken.load(Ordering::SeqCst)
+    }
+
+    /// Number of channels opened so far. When it didn't change while some work failed on a
+    /// broken session, none of that work's commands reached the host.
+    pub fn channels_opened(&self) -> usize {
+        self.channels.load(Ordering::SeqCst)
+    }
+
+    fn count_channel<T>(&self, channel: T) -> T {
+        self.channels.fetch_add(1, Ordering::SeqCst);
+        channel
+    }
+
+    fn mark_broken(&self, error: ssh2::Error) -> ssh2::Error {
+        debug!("SSH session to {} is not usable: {}", self.host, error);
+        self.broken.store(true, Ordering::SeqCst);
+        error
+    }
+
+    /// Politely close the session; errors are ignored since the connection is discarded anyway
+    pub fn disconnect(&self) {
+        if let Err(e) = self
+            .session
+            .disconnect(None, "rustsible run finished", None)
+        {
+            debug!("Error while disconnecting from {}: {}", self.host, e);
+        }
+    }
+
     /// Upload a local file with sudo privileges (legacy method, kept for compatibility)
     #[allow(dead_code)]
     pub fn upload_sudo_file(&self, local_path: &str, remote_path: &str) -> Result<()> {
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use log::{debug, info};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::inventory::Host;
+use crate::ssh::connection::SshClient;
+
+/// Identifies a reusable SSH session: the inventory host using it, where it connects and how
+/// it authenticates. A session is not safe to use from several forks at once, and only the
+/// forks of different hosts run at the same time, so aliases of an address don't share one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectionKey {
+    pub host: String,
+    pub hostname: String,
+    pub port: u16,
+    pub user: String,
+    pub private_key: Option<String>,
+}
+
+impl ConnectionKey {
+    pub fn for_host(host: &Host) -> Self {
+        ConnectionKey {
+            host: host.name.clone(),
+            hostname: host.hostname.clone(),
+            port: host.port,
+            user: host.get_ssh_user().unwrap_or_else(|| "root".to_string()),
+            private_key: host.get_ssh_private_key(),
+        }
+    }
+}
+
+/// Cache of authenticated SSH sessions shared by all tasks and handlers of a playbook run
+pub struct ConnectionPool {
+    connections: Mutex<HashMap<ConnectionKey, Arc<SshClient>>>,
+}
+
+static GLOBAL_POOL: Lazy<ConnectionPool> = Lazy::new(ConnectionPool::new);
+
+impl ConnectionPool {
+    pub fn new() -> Self {
+        ConnectionPool {
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The pool used by playbook runs
+    pub fn global() -> &'static ConnectionPool {
+        &GLOBAL_POOL
+    }
+
+    /// Return the cached session for the host, connecting when there is none. Sessions aren't
+    /// probed before use: callers `invalidate` one that turns out to be broken.
+    pub fn get(&self, host: &Host) -> Result<Arc<SshClient>> {
+        let key = ConnectionKey::for_host(host);
+
+        let cached = self.lock().get(&key).cloned();
+        if let Some(client) = cached {
+            if !client.is_broken() {
+                debug!(
+                    "Reusing SSH connection to {}@{}:{}",
+                    key.user, key.hostname, key.port
+                );
+                return Ok(client);
+            }
+            info!(
+                "SSH connection to {}@{}:{} is no longer usable, reconnecting",
+                key.user, key.hostname, key.port
+            );
+            self.invalidate(host, &client);
+        }
+
+        // Do not hold the lock while talking to the network so other hosts can connect meanwhile.
+        // When another thread connected to the same endpoint first, its session is kept and
+        // shared so no session in use gets dropped.
+        let client = Arc::new(SshClient::connect(host)?);
+        let shared = Arc::clone(
+            self.lock()
+                .entry(key)
+                .or_insert_with(|| Arc::clone(&client)),
+        );
+        if !Arc::ptr_eq(&shared, &client) {
+            client.disconnect();
+        }
+        Ok(shared)
+    }
+
+    /// Forget a broken session of the host, unless it was already replaced by a new one
+    pub fn invalidate(&self, host: &Host, client: &Arc<SshClient>) {
+        let key = ConnectionKey::for_host(host);
+        let mut connections = self.lock();
+        if connections
+            .get(&key)
+            .is_some_and(|cached| Arc::ptr_eq(cached, client))
+        {
+            connections.remove(&key);
+        }
+    }
+
+    /// Number of cached sessions
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Disconnect and forget every cached session
+    pub fn close_all(&self) {
+        let connections: Vec<_> = self.lock().drain().collect();
+        if !connections.is_empty() {
+            info!("Closing {} SSH connections", connections.len());
+        }
+        for (key, client) in connections {
+            debug!(
+                "Closing SSH connection to {}@{}:{}",
+                key.user, key.hostname, key.port
+            );
+            client.disconnect();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ConnectionKey, Arc<SshClient>>> {
+        // A panic while holding the lock cannot leave the map inconsistent, so keep using it
+        self.connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for ConnectionPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::create_test_host;
+
+    #[test]
+    fn test_connection_key_for_host() {
+        let host = create_test_host("web1", "10.0.0.1", 2222, Some("deploy"), None);
+        let key = ConnectionKey::for_host(&host);
+
+        assert_eq!(key.hostname, "10.0.0.1");
+        assert_eq!(key.port, 2222);
+        assert_eq!(key.user, "deploy");
+        assert_eq!(key.private_key, None);
+
+        // Aliases pointing at the same endpoint and user get their own session
+        let alias = create_test_host("web1-alias", "10.0.0.1", 2222, Some("deploy"), None);
+        assert_ne!(ConnectionKey::for_host(&alias), key);
+        let same = create_test_host("web1", "10.0.0.1", 2222, Some("deploy"), None);
+        assert_eq!(ConnectionKey::for_host(&same), key);
+
+        let other_user = create_test_host("web1", "10.0.0.1", 2222, Some("admin"), None);
+        assert_ne!(ConnectionKey::for_host(&other_user), key);
+    }
+
+    #[test]
+    fn test_pool_does_not_cache_failed_connections() {
+        let pool = ConnectionPool::new();
+        let host = create_test_host("unreachable", "127.0.0.1", 1, None, None);
+
+        assert!(pool.get(&host).is_err());
+        assert!(pool.is_empty());
+
+        pool.close_all();
+        assert!(pool.is_empty());
+    }
+}